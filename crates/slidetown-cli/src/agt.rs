@@ -1,63 +1,252 @@
 use clap::{Parser, Subcommand};
-use slidetown::parsers::agt;
+use serde::{Deserialize, Serialize};
+use slidetown::{
+    agt::{
+        delta_builder, diff_agt,
+        keys::{self, KeyRecovery},
+        merge_agt, normalize_entry_path, sync_agt, AgtBuilder, AgtBuilderOptions, AgtDiff,
+        AgtEditor, AgtReader, ConflictPolicy,
+    },
+    parsers::{agt::Header, Codepage},
+};
 use std::{
+    collections::{HashMap, HashSet},
     fs::File,
-    io::{Cursor, Read, Seek, SeekFrom},
+    io::{BufReader, BufWriter, ErrorKind, Write},
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::util::{
+    fs::{entry_path_to_relative, visit_files},
+    glob::glob_match,
 };
 
 #[derive(Parser)]
 pub struct AgtOpts {
     #[command(subcommand)]
     cmd: Command,
-    // #[arg(short, long, about = "optional custom key file")]
-    // key_path: Option<String>,
+
+    #[command(flatten)]
+    key_opts: KeyOpts,
+
+    /// codepage of entry paths, e.g. euc-kr or big5; defaults to the manifest's or utf-8
+    #[arg(long, global = true)]
+    codepage: Option<Codepage>,
+}
+
+#[derive(clap::Args)]
+struct KeyOpts {
+    /// optional custom key file
+    #[arg(short, long, global = true)]
+    key_path: Option<String>,
+
+    /// name of a built-in or key directory key, see the keys command; detected from the archive if not given
+    #[arg(long, global = true, conflicts_with = "key_path")]
+    key: Option<String>,
+
+    /// directory of extra key files named after their key, e.g. regional keys like kr.bin
+    #[arg(long, global = true)]
+    keys_dir: Option<String>,
 }
 
 #[derive(Subcommand)]
 enum Command {
     #[command(about = "display info about archive contents")]
     Info(InfoOpts),
+
+    #[command(about = "list entries, optionally filtered and sorted")]
+    Ls(LsOpts),
+
+    #[command(about = "write the contents of one entry to stdout")]
+    Cat(CatOpts),
+
+    #[command(about = "search entry contents for text or bytes")]
+    Grep(GrepOpts),
+
+    #[command(about = "extract all entries and create manifest")]
+    Extract(ExtractOpts),
+
+    #[command(about = "pack a directory or extracted manifest into an archive")]
+    Pack(PackOpts),
+
+    #[command(about = "add, replace or remove entries in an existing archive")]
+    Update(UpdateOpts),
+
+    #[command(about = "check every entry for damaged or truncated data")]
+    Verify(VerifyOpts),
+
+    #[command(about = "compare the entries of two archives")]
+    Diff(DiffOpts),
+
+    #[command(about = "write the entries that changed or were added between two archives")]
+    Delta(DeltaOpts),
+
+    #[command(about = "compare an archive with a directory of loose files and apply the changes")]
+    Sync(SyncOpts),
+
+    #[command(about = "merge several archives into one")]
+    Merge(MergeOpts),
+
+    #[command(about = "list built-in keys and keys from --keys-dir")]
+    Keys,
+
+    #[command(about = "recover the key of an archive encrypted with an unknown key")]
+    RecoverKey(RecoverKeyOpts),
 }
 
-#[derive(Parser)]
-struct InfoOpts {
-    /// input file
-    #[arg(short, long)]
-    input_path: String,
+/// Entry list written next to extracted files, used to rebuild the archive in its original order
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    /// Header of the extracted archive, restored when packing
+    #[serde(default)]
+    header: Option<Header>,
+    /// Codepage entry paths were decoded with, used to encode them again when packing
+    #[serde(default)]
+    codepage: Option<Codepage>,
+    entries: Vec<ManifestEntry>,
 }
 
-static SPOOKY_KEY: &[u8] = &[
-    0x01, 0x05, 0x06, 0x02, 0x04, 0x03, 0x07, 0x08, 0x01, 0x05, 0x06, 0x0F, 0x04, 0x03, 0x07, 0x0C,
-    0x31, 0x85, 0x76, 0x39, 0x34, 0x3D, 0x30, 0xE8, 0x67, 0x36, 0x36, 0x32, 0x3E, 0x33, 0x34, 0x3B,
-    0x11, 0x15, 0x16, 0x16, 0x14, 0x13, 0x1D, 0x18, 0x11, 0x03, 0x06, 0x0C, 0x04, 0x03, 0x06, 0x08,
-    0x2E, 0x55, 0x26, 0x23, 0x2A, 0x23, 0x2E, 0x28, 0x21, 0x21, 0x26, 0x27, 0x2E, 0x00, 0x2D, 0x2D,
-    0xCF, 0xA5, 0x06, 0x02, 0x04, 0x0F, 0x07, 0x18, 0xE1, 0x15, 0x36, 0x18, 0x60, 0x13, 0x1A, 0x19,
-    0x11, 0x15, 0x16, 0x10, 0x12, 0x13, 0x17, 0x38, 0xF1, 0x25,
-];
+/// Name of the manifest extract writes next to the extracted entries
+const MANIFEST_FILE_NAME: &str = "manifest.json";
 
-fn process_info(info_opts: InfoOpts) -> anyhow::Result<()> {
-    let mut file = File::open(info_opts.input_path)?;
-    let header = agt::Header::parse(&mut file)?;
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestEntry {
+    /// Path as stored in the archive, e.g. `NeoData\NC_quest.xlt`
+    path: String,
+}
+
+impl KeyOpts {
+    /// Keys from the keys directory, if one was given
+    fn key_files(&self) -> anyhow::Result<Vec<keys::KeyFile>> {
+        match &self.keys_dir {
+            Some(keys_dir) => keys::load_key_dir(keys_dir),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Pick the key from a key file, a built-in or key directory key name,
+    /// or the first of those keys that opens the archive
+    fn load(&self, archive_path: Option<&str>) -> anyhow::Result<Vec<u8>> {
+        let key = match (&self.key_path, &self.key, archive_path) {
+            (Some(key_path), _, _) => std::fs::read(key_path)?,
+            (None, Some(key_name), _) => {
+                let key_files = self.key_files()?;
+                if let Some(known_key) = keys::find_known_key(key_name) {
+                    known_key.key.to_vec()
+                } else if let Some(key_file) = key_files
+                    .into_iter()
+                    .find(|key_file| key_file.name.eq_ignore_ascii_case(key_name))
+                {
+                    key_file.key
+                } else {
+                    anyhow::bail!(
+                        "unknown key {}, available keys are: {}",
+                        key_name,
+                        self.key_names()?.join(", ")
+                    );
+                }
+            }
+            (None, None, Some(archive_path)) => {
+                let mut file = BufReader::new(File::open(archive_path)?);
+                if let Some(known_key) = keys::detect_key(&mut file) {
+                    known_key.key.to_vec()
+                } else if let Some(key_file) = self
+                    .key_files()?
+                    .into_iter()
+                    .find(|key_file| keys::is_valid_key(&mut file, &key_file.key))
+                {
+                    key_file.key
+                } else {
+                    anyhow::bail!(
+                        "none of the available keys open {}, pass --key-path or try recover-key",
+                        archive_path
+                    );
+                }
+            }
+            (None, None, None) => keys::default_key().key.to_vec(),
+        };
+
+        if key.is_empty() {
+            anyhow::bail!("key must not be empty");
+        }
+
+        Ok(key)
+    }
+
+    fn key_names(&self) -> anyhow::Result<Vec<String>> {
+        let mut key_names = keys::KNOWN_KEYS
+            .iter()
+            .map(|known_key| known_key.name.to_owned())
+            .collect::<Vec<_>>();
+        key_names.extend(self.key_files()?.into_iter().map(|key_file| key_file.name));
+        Ok(key_names)
+    }
+}
+
+/// Compression settings for commands that write archives
+#[derive(clap::Args)]
+struct CompressionOpts {
+    /// zlib compression level, 0-9
+    #[arg(long, default_value_t = 6)]
+    compression_level: u32,
 
-    let entry_count = header.file_count as usize;
-    let max_entry_path_length = 260;
-    let max_entry_size = 4 * 4 + max_entry_path_length;
+    /// recompress chunks that stay above this fraction of their size at the poor compression level
+    #[arg(long)]
+    poor_compression_ratio: Option<f32>,
 
-    let entries_buffer_size = entry_count * max_entry_size;
-    let mut entries_buffer = vec![0u8; entries_buffer_size];
+    /// compression level for chunks that compress badly, 0 stores them
+    #[arg(long, default_value_t = 0)]
+    poor_compression_level: u32,
 
-    file.seek(SeekFrom::Start(32))?;
-    file.read_exact(&mut entries_buffer)?;
+    /// compression threads, 0 uses every core
+    #[arg(long, default_value_t = 0)]
+    threads: usize,
+}
 
-    #[allow(clippy::needless_range_loop)]
-    for i in 0..entries_buffer.len() {
-        let file_index = i + 32;
-        entries_buffer[i] ^= SPOOKY_KEY[file_index % SPOOKY_KEY.len()];
+impl From<CompressionOpts> for AgtBuilderOptions {
+    fn from(compression_opts: CompressionOpts) -> Self {
+        Self {
+            compression_level: compression_opts.compression_level,
+            poor_compression_ratio: compression_opts.poor_compression_ratio,
+            poor_compression_level: compression_opts.poor_compression_level,
+            threads: compression_opts.threads,
+        }
     }
+}
+
+/// Turn a path relative to the input directory into a backslash-separated entry path
+fn relative_to_entry_path(relative: &Path) -> anyhow::Result<String> {
+    let parts = relative
+        .components()
+        .map(|component| match component {
+            Component::Normal(part) => part
+                .to_str()
+                .ok_or_else(|| anyhow::anyhow!("non-UTF-8 file name {:?}", relative)),
+            _ => Err(anyhow::anyhow!(
+                "unexpected path component in {:?}",
+                relative
+            )),
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(parts.join("\\"))
+}
+
+#[derive(Parser)]
+struct InfoOpts {
+    /// input file
+    #[arg(short, long)]
+    input_path: String,
+}
 
-    let mut entries_cursor = Cursor::new(entries_buffer);
+fn process_info(info_opts: InfoOpts, key: &[u8], codepage: Codepage) -> anyhow::Result<()> {
+    let mut file = BufReader::new(File::open(info_opts.input_path)?);
+    let mut agt_reader = AgtReader::new(&mut file, key);
+    agt_reader.set_codepage(codepage);
 
-    let entries = agt::Entry::parse_entries(&mut entries_cursor, entry_count as _)?;
+    let header = agt_reader.read_header()?;
+    let entries = agt_reader.read_entries(header.file_count)?;
 
     println!("Version: {:?}", header.version);
 
@@ -76,8 +265,1183 @@ fn process_info(info_opts: InfoOpts) -> anyhow::Result<()> {
     Ok(())
 }
 
+#[derive(Parser)]
+struct LsOpts {
+    /// input file
+    #[arg(short, long)]
+    input_path: String,
+
+    /// only list entries matching any of these globs, e.g. "NeoData\\*.xlt" or "*.nif"
+    #[arg(short, long = "glob", value_name = "GLOB")]
+    globs: Vec<String>,
+
+    /// sort by path, size or offset instead of archive order
+    #[arg(short, long)]
+    sort: Option<SortKey>,
+
+    /// reverse the listing order
+    #[arg(short, long)]
+    reverse: bool,
+
+    /// print the listing as JSON
+    #[arg(short, long)]
+    json: bool,
+}
+
+#[derive(Clone, Copy)]
+enum SortKey {
+    Path,
+    Size,
+    Offset,
+}
+
+impl FromStr for SortKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "path" => Ok(SortKey::Path),
+            "size" => Ok(SortKey::Size),
+            "offset" => Ok(SortKey::Offset),
+            _ => anyhow::bail!("unknown sort key {}, expected path, size or offset", s),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct LsEntry<'a> {
+    path: &'a str,
+    chunks_offset: u32,
+    chunk_count: u32,
+    decompressed_length: u32,
+}
+
+/// Whether an entry is selected by the given globs, all entries are if there are none
+fn matches_globs(globs: &[String], entry_path: &str) -> bool {
+    globs.is_empty() || globs.iter().any(|glob| glob_match(glob, entry_path))
+}
+
+fn process_ls(ls_opts: LsOpts, key: &[u8], codepage: Codepage) -> anyhow::Result<()> {
+    let mut file = BufReader::new(File::open(ls_opts.input_path)?);
+    let mut agt_reader = AgtReader::new(&mut file, key);
+    agt_reader.set_codepage(codepage);
+
+    let header = agt_reader.read_header()?;
+    let mut entries = agt_reader
+        .read_entries(header.file_count)?
+        .into_iter()
+        .filter(|entry| matches_globs(&ls_opts.globs, &entry.path))
+        .collect::<Vec<_>>();
+
+    match ls_opts.sort {
+        Some(SortKey::Path) => {
+            entries.sort_by_cached_key(|entry| normalize_entry_path(&entry.path))
+        }
+        Some(SortKey::Size) => entries.sort_by_key(|entry| entry.decompressed_length),
+        Some(SortKey::Offset) => entries.sort_by_key(|entry| entry.chunks_offset),
+        None => {}
+    }
+    if ls_opts.reverse {
+        entries.reverse();
+    }
+
+    if ls_opts.json {
+        let ls_entries = entries
+            .iter()
+            .map(|entry| LsEntry {
+                path: &entry.path,
+                chunks_offset: entry.chunks_offset,
+                chunk_count: entry.chunk_count,
+                decompressed_length: entry.decompressed_length,
+            })
+            .collect::<Vec<_>>();
+        println!("{}", serde_json::to_string_pretty(&ls_entries)?);
+    } else {
+        for entry in entries.iter() {
+            println!("{:>10}  {}", entry.decompressed_length, entry.path);
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Parser)]
+struct CatOpts {
+    /// input file
+    #[arg(short, long)]
+    input_path: String,
+
+    /// entry to print, matched ignoring case and slash direction
+    entry_path: String,
+}
+
+fn process_cat(cat_opts: CatOpts, key: &[u8], codepage: Codepage) -> anyhow::Result<()> {
+    let mut file = BufReader::new(File::open(&cat_opts.input_path)?);
+    let mut agt_reader = AgtReader::new(&mut file, key);
+    agt_reader.set_codepage(codepage);
+
+    let header = agt_reader.read_header()?;
+    let normalized_path = normalize_entry_path(&cat_opts.entry_path);
+    let entry = agt_reader
+        .read_entries(header.file_count)?
+        .into_iter()
+        .find(|entry| normalize_entry_path(&entry.path) == normalized_path)
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "no entry {} in {}",
+                cat_opts.entry_path,
+                cat_opts.input_path
+            )
+        })?;
+
+    let mut entry_reader = agt_reader.open_entry(&entry)?;
+    let mut stdout = std::io::stdout().lock();
+    match std::io::copy(&mut entry_reader, &mut stdout).and_then(|_| stdout.flush()) {
+        // Stopping early, e.g. when piped to head, is fine
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+#[derive(Parser)]
+struct GrepOpts {
+    /// input file
+    #[arg(short, long)]
+    input_path: String,
+
+    /// text to search for, or hex bytes with --hex
+    pattern: String,
+
+    /// only search entries matching any of these globs
+    #[arg(short, long = "glob", value_name = "GLOB")]
+    globs: Vec<String>,
+
+    /// encodings to search the text in, defaults to utf-8, utf-16le and euc-kr
+    #[arg(short, long = "encoding", value_name = "ENCODING")]
+    encodings: Vec<TextEncoding>,
+
+    /// treat the pattern as hex bytes, e.g. "4e 61 79 61"
+    #[arg(long, conflicts_with = "encodings")]
+    hex: bool,
+
+    /// ignore the case of ASCII letters
+    #[arg(long, conflicts_with = "hex")]
+    ignore_case: bool,
+
+    /// only print the paths of entries with matches
+    #[arg(short = 'l', long)]
+    files_with_matches: bool,
+}
+
+/// How a text pattern is turned into bytes to search for
+#[derive(Clone, Copy)]
+enum TextEncoding {
+    Utf16Le,
+    Codepage(Codepage),
+}
+
+impl TextEncoding {
+    fn name(&self) -> &'static str {
+        match self {
+            TextEncoding::Utf16Le => "UTF-16LE",
+            TextEncoding::Codepage(codepage) => codepage.name(),
+        }
+    }
+
+    fn encode(&self, text: &str) -> anyhow::Result<Vec<u8>> {
+        match self {
+            TextEncoding::Utf16Le => Ok(text.encode_utf16().flat_map(u16::to_le_bytes).collect()),
+            TextEncoding::Codepage(codepage) => codepage.encode(text),
+        }
+    }
+
+    fn decode_lossy(&self, data: &[u8]) -> String {
+        match self {
+            TextEncoding::Utf16Le => encoding_rs::UTF_16LE
+                .decode_without_bom_handling(data)
+                .0
+                .into_owned(),
+            TextEncoding::Codepage(codepage) => codepage.decode_lossy(data),
+        }
+    }
+
+    /// Lowercase the ASCII letters in data, leaving bytes that are part of
+    /// multi-byte characters alone so their meaning doesn't change
+    fn fold_ascii_case(&self, data: &[u8]) -> Vec<u8> {
+        let mut folded = data.to_vec();
+        match self {
+            TextEncoding::Utf16Le => {
+                for unit in folded.chunks_exact_mut(2) {
+                    if unit[1] == 0 {
+                        unit[0] = unit[0].to_ascii_lowercase();
+                    }
+                }
+            }
+            // ASCII bytes never appear inside other characters
+            TextEncoding::Codepage(Codepage::Utf8 | Codepage::Cp1252) => {
+                folded.make_ascii_lowercase();
+            }
+            // Trail bytes of double-byte characters can look like ASCII letters
+            TextEncoding::Codepage(
+                codepage @ (Codepage::EucKr | Codepage::Big5 | Codepage::Gbk | Codepage::ShiftJis),
+            ) => {
+                let is_lead_byte = |byte: u8| match codepage {
+                    Codepage::ShiftJis => matches!(byte, 0x81..=0x9F | 0xE0..=0xFC),
+                    _ => matches!(byte, 0x81..=0xFE),
+                };
+                let mut i = 0;
+                while i < folded.len() {
+                    if is_lead_byte(folded[i]) {
+                        i += 2;
+                    } else {
+                        folded[i] = folded[i].to_ascii_lowercase();
+                        i += 1;
+                    }
+                }
+            }
+        }
+        folded
+    }
+
+    /// Bytes per code unit, matches only start on unit boundaries
+    fn unit_length(&self) -> usize {
+        match self {
+            TextEncoding::Utf16Le => 2,
+            TextEncoding::Codepage(_) => 1,
+        }
+    }
+
+    fn newline(&self) -> &'static [u8] {
+        match self {
+            TextEncoding::Utf16Le => b"\n\0",
+            TextEncoding::Codepage(_) => b"\n",
+        }
+    }
+}
+
+impl FromStr for TextEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('_', "-").as_str() {
+            "utf-16le" | "utf16le" | "utf-16" | "utf16" => Ok(TextEncoding::Utf16Le),
+            codepage => Ok(TextEncoding::Codepage(codepage.parse()?)),
+        }
+    }
+}
+
+fn parse_hex_pattern(pattern: &str) -> anyhow::Result<Vec<u8>> {
+    let digits = pattern
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>();
+    if digits.is_empty() || digits.len() % 2 != 0 {
+        anyhow::bail!(
+            "hex pattern {:?} must have an even number of digits",
+            pattern
+        );
+    }
+
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&digits[i..i + 2], 16)
+                .map_err(|_| anyhow::anyhow!("invalid hex byte {:?}", &digits[i..i + 2]))
+        })
+        .collect()
+}
+
+/// Offsets of every non-overlapping occurrence of `needle` in `haystack` that starts
+/// at a multiple of `unit_length`
+fn find_all(haystack: &[u8], needle: &[u8], unit_length: usize) -> Vec<usize> {
+    let mut offsets = Vec::new();
+    let mut offset = 0;
+    while let Some(window) = haystack.get(offset..offset + needle.len()) {
+        if window == needle {
+            offsets.push(offset);
+            offset += needle.len().next_multiple_of(unit_length);
+        } else {
+            offset += unit_length;
+        }
+    }
+    offsets
+}
+
+/// The line around a match, limited to a few dozen bytes on either side
+fn match_preview(data: &[u8], offset: usize, length: usize, encoding: TextEncoding) -> String {
+    const CONTEXT: usize = 64;
+    let newline = encoding.newline();
+    let step = newline.len();
+
+    let mut start = offset;
+    while start >= step && offset - start < CONTEXT && &data[start - step..start] != newline {
+        start -= step;
+    }
+    let mut end = offset + length;
+    while end + step <= data.len()
+        && end - offset - length < CONTEXT
+        && &data[end..end + step] != newline
+    {
+        end += step;
+    }
+
+    encoding
+        .decode_lossy(&data[start..end])
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+fn process_grep(grep_opts: GrepOpts, key: &[u8], codepage: Codepage) -> anyhow::Result<()> {
+    // (encoding used for previews, bytes to search for)
+    let needles = if grep_opts.hex {
+        vec![(None, parse_hex_pattern(&grep_opts.pattern)?)]
+    } else {
+        let encodings = if grep_opts.encodings.is_empty() {
+            vec![
+                TextEncoding::Codepage(Codepage::Utf8),
+                TextEncoding::Utf16Le,
+                TextEncoding::Codepage(Codepage::EucKr),
+            ]
+        } else {
+            grep_opts.encodings.clone()
+        };
+
+        let mut needles: Vec<(Option<TextEncoding>, Vec<u8>)> = Vec::new();
+        for encoding in encodings {
+            let pattern = if grep_opts.ignore_case {
+                grep_opts.pattern.to_ascii_lowercase()
+            } else {
+                grep_opts.pattern.clone()
+            };
+            match encoding.encode(&pattern) {
+                // ASCII text is the same in most codepages, search it only once
+                Ok(needle) if needles.iter().any(|(_, existing)| *existing == needle) => {}
+                Ok(needle) => needles.push((Some(encoding), needle)),
+                Err(e) => eprintln!("Skipping {}: {}", encoding.name(), e),
+            }
+        }
+        needles
+    };
+    if needles.iter().any(|(_, needle)| needle.is_empty()) {
+        anyhow::bail!("pattern must not be empty");
+    }
+
+    let mut file = BufReader::new(File::open(&grep_opts.input_path)?);
+    let mut agt_reader = AgtReader::new(&mut file, key);
+    agt_reader.set_codepage(codepage);
+
+    let header = agt_reader.read_header()?;
+    let entries = agt_reader.read_entries(header.file_count)?;
+
+    let mut match_count = 0;
+    for entry in entries
+        .iter()
+        .filter(|entry| matches_globs(&grep_opts.globs, &entry.path))
+    {
+        let data = agt_reader.read_entry_data(entry)?;
+
+        let mut matches = needles
+            .iter()
+            .flat_map(|(encoding, needle)| {
+                // UTF-16 is folded by whole code units, a match between two of them is
+                // made of halves of different characters
+                let offsets = match encoding {
+                    Some(encoding) if grep_opts.ignore_case => find_all(
+                        &encoding.fold_ascii_case(&data),
+                        needle,
+                        encoding.unit_length(),
+                    ),
+                    Some(encoding) => find_all(&data, needle, encoding.unit_length()),
+                    None => find_all(&data, needle, 1),
+                };
+                offsets
+                    .into_iter()
+                    .map(move |offset| (offset, needle.len(), *encoding))
+            })
+            .collect::<Vec<_>>();
+        if matches.is_empty() {
+            continue;
+        }
+        matches.sort_by_key(|(offset, _, _)| *offset);
+        match_count += matches.len();
+
+        if grep_opts.files_with_matches {
+            println!("{}", entry.path);
+            continue;
+        }
+        for (offset, length, encoding) in matches {
+            match encoding {
+                Some(encoding) => println!(
+                    "{}:{:#x}: [{}] {}",
+                    entry.path,
+                    offset,
+                    encoding.name(),
+                    match_preview(&data, offset, length, encoding)
+                ),
+                None => println!("{}:{:#x}", entry.path, offset),
+            }
+        }
+    }
+
+    if match_count == 0 {
+        eprintln!("No matches");
+    }
+
+    Ok(())
+}
+
+#[derive(Parser)]
+struct ExtractOpts {
+    /// input file
+    #[arg(short, long)]
+    input_path: String,
+
+    /// output directory
+    #[arg(short, long)]
+    output_path: String,
+}
+
+fn process_extract(
+    extract_opts: ExtractOpts,
+    key: &[u8],
+    codepage: Codepage,
+) -> anyhow::Result<()> {
+    let mut file = BufReader::new(File::open(&extract_opts.input_path)?);
+    let mut agt_reader = AgtReader::new(&mut file, key);
+    agt_reader.set_codepage(codepage);
+
+    let header = agt_reader.read_header()?;
+    let entries = agt_reader.read_entries(header.file_count)?;
+
+    let out_dir_path = Path::new(&extract_opts.output_path);
+    std::fs::create_dir_all(out_dir_path)?;
+
+    let mut manifest = Manifest {
+        header: Some(header),
+        codepage: Some(codepage),
+        ..Default::default()
+    };
+
+    // Extracted file paths ignoring case, they can't coexist on every file system
+    let mut written_paths = HashMap::new();
+
+    for entry in entries {
+        // Lossy paths would not survive packing again, so refuse them here
+        let entry_path = entry.decode_path(codepage).map_err(|e| {
+            anyhow::anyhow!(
+                "{} in entry path {:?}, pass --codepage to pick another one",
+                e,
+                entry.path
+            )
+        })?;
+
+        let relative = entry_path_to_relative(&entry_path)?;
+        let folded_relative = relative.to_string_lossy().to_lowercase();
+        if folded_relative == MANIFEST_FILE_NAME {
+            anyhow::bail!(
+                "entry {} would overwrite the manifest, extract it with cat instead",
+                entry_path
+            );
+        }
+        // The game reads the first of entries whose paths only differ in case
+        if let Some(first_path) = written_paths.get(&folded_relative) {
+            println!(
+                "Skipping {}, it is shadowed by {} with the same path",
+                entry_path, first_path
+            );
+            continue;
+        }
+        written_paths.insert(folded_relative, entry_path.clone());
+
+        println!("Writing {}", entry_path);
+
+        let entry_file_path = out_dir_path.join(relative);
+        if let Some(entry_dir_path) = entry_file_path.parent() {
+            std::fs::create_dir_all(entry_dir_path)?;
+        }
+
+        let mut entry_reader = agt_reader.open_entry(&entry)?;
+        let mut entry_file = BufWriter::new(File::create(entry_file_path)?);
+        std::io::copy(&mut entry_reader, &mut entry_file)?;
+        entry_file.flush()?;
+
+        manifest.entries.push(ManifestEntry { path: entry_path });
+    }
+
+    {
+        let manifest_file = File::create(out_dir_path.join(MANIFEST_FILE_NAME))?;
+        serde_json::to_writer_pretty(manifest_file, &manifest)?;
+    }
+
+    Ok(())
+}
+
+#[derive(Parser)]
+struct PackOpts {
+    /// input directory, or manifest created by extract
+    #[arg(short, long)]
+    input_path: String,
+
+    /// output file
+    #[arg(short, long)]
+    output_path: String,
+
+    /// prefix prepended to every entry path, e.g. "NeoData\\"
+    #[arg(short, long)]
+    prefix: Option<String>,
+
+    /// existing archive to start from; its header, entry order and unchanged chunks are kept,
+    /// entries missing from the input are dropped and new ones are appended sorted by path
+    #[arg(short, long)]
+    base_path: Option<String>,
+
+    #[command(flatten)]
+    compression: CompressionOpts,
+}
+
+fn is_manifest(file_path: &Path) -> bool {
+    File::open(file_path)
+        .ok()
+        .and_then(|file| serde_json::from_reader::<_, Manifest>(BufReader::new(file)).ok())
+        .is_some()
+}
+
+/// Collect (entry path, file path) pairs for everything under a directory
+fn collect_directory_files(dir_path: &Path) -> anyhow::Result<Vec<(String, PathBuf)>> {
+    let mut file_paths = Vec::new();
+    visit_files(dir_path, &mut |dir_entry| file_paths.push(dir_entry.path()))?;
+
+    let mut files = Vec::new();
+    for file_path in file_paths {
+        let relative = file_path.strip_prefix(dir_path)?;
+        // The manifest left behind by extract is not part of the archive, but a file
+        // that merely has its name is
+        if relative == Path::new(MANIFEST_FILE_NAME) && is_manifest(&file_path) {
+            println!("Skipping {}, it was left by extract", MANIFEST_FILE_NAME);
+            continue;
+        }
+        files.push((relative_to_entry_path(relative)?, file_path));
+    }
+    files.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    Ok(files)
+}
+
+/// Collect (entry path, file path) pairs for every entry listed in an extract manifest
+fn collect_manifest_files(
+    manifest: &Manifest,
+    base_path: &Path,
+) -> anyhow::Result<Vec<(String, PathBuf)>> {
+    manifest
+        .entries
+        .iter()
+        .map(|entry| {
+            let file_path = base_path.join(entry_path_to_relative(&entry.path)?);
+            Ok((entry.path.clone(), file_path))
+        })
+        .collect()
+}
+
+fn process_pack(pack_opts: PackOpts, key: &[u8], codepage: Option<Codepage>) -> anyhow::Result<()> {
+    let input_path = Path::new(&pack_opts.input_path);
+
+    let manifest: Option<Manifest> = if input_path.is_dir() {
+        None
+    } else {
+        let manifest_file = File::open(input_path)?;
+        Some(serde_json::from_reader(manifest_file)?)
+    };
+    let codepage = codepage
+        .or_else(|| manifest.as_ref().and_then(|manifest| manifest.codepage))
+        .unwrap_or_default();
+
+    let mut builder = match &pack_opts.base_path {
+        Some(base_path) => AgtBuilder::from_agt_with_codepage(base_path, key, codepage)?,
+        None => {
+            let mut builder = AgtBuilder::new();
+            builder.set_codepage(codepage);
+            builder
+        }
+    };
+    builder.set_options(pack_opts.compression.into());
+
+    let files = if let Some(manifest) = manifest {
+        // Keep the extracted archive's entry order and header, unless a base archive provides them
+        if pack_opts.base_path.is_none() {
+            builder.set_preserve_order(true);
+            if let Some(header) = manifest.header.clone() {
+                builder.set_header(header);
+            }
+        }
+
+        collect_manifest_files(&manifest, &input_path.with_file_name(""))?
+    } else {
+        collect_directory_files(input_path)?
+    };
+
+    let prefix = match pack_opts.prefix {
+        Some(prefix) if !prefix.is_empty() => {
+            let prefix = prefix.replace('/', "\\");
+            if prefix.ends_with('\\') {
+                prefix
+            } else {
+                prefix + "\\"
+            }
+        }
+        _ => String::new(),
+    };
+
+    let files = files
+        .into_iter()
+        .map(|(entry_path, file_path)| (format!("{}{}", prefix, entry_path), file_path))
+        .collect::<Vec<_>>();
+
+    // The input describes the whole archive, base entries it doesn't list are removed
+    let input_paths = files
+        .iter()
+        .map(|(entry_path, _)| entry_path.as_str())
+        .collect::<HashSet<_>>();
+    let removed_paths = builder
+        .entry_paths()
+        .filter(|entry_path| !input_paths.contains(entry_path))
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+    for entry_path in removed_paths {
+        println!("Removing {}", entry_path);
+        builder.remove_entry(&entry_path);
+    }
+
+    for (entry_path, file_path) in files {
+        println!("Adding {}", entry_path);
+
+        let file_size = std::fs::metadata(&file_path)?.len();
+        builder.add_entry_file(entry_path, file_path, 0, file_size);
+    }
+
+    let mut out_file = BufWriter::new(File::create(pack_opts.output_path)?);
+    builder.write(&mut out_file, key)?;
+    out_file.flush()?;
+
+    Ok(())
+}
+
+#[derive(Parser)]
+struct UpdateOpts {
+    /// input file
+    #[arg(short, long)]
+    input_path: String,
+
+    /// output file, the input file is overwritten if not given
+    #[arg(short, long)]
+    output_path: Option<String>,
+
+    /// add a new entry from a file
+    #[arg(long = "add", value_name = "ENTRY=FILE", value_parser = parse_entry_file)]
+    adds: Vec<(String, PathBuf)>,
+
+    /// replace an existing entry with a file
+    #[arg(long = "replace", value_name = "ENTRY=FILE", value_parser = parse_entry_file)]
+    replacements: Vec<(String, PathBuf)>,
+
+    /// remove an existing entry
+    #[arg(long = "remove", value_name = "ENTRY")]
+    removals: Vec<String>,
+
+    #[command(flatten)]
+    compression: CompressionOpts,
+}
+
+fn parse_entry_file(value: &str) -> Result<(String, PathBuf), String> {
+    match value.split_once('=') {
+        Some((entry_path, file_path)) if !entry_path.is_empty() && !file_path.is_empty() => {
+            Ok((entry_path.to_owned(), PathBuf::from(file_path)))
+        }
+        _ => Err(format!("expected ENTRY=FILE, got {:?}", value)),
+    }
+}
+
+fn process_update(update_opts: UpdateOpts, key: &[u8], codepage: Codepage) -> anyhow::Result<()> {
+    let mut editor = AgtEditor::open_with_codepage(&update_opts.input_path, key, codepage)?;
+    editor.set_options(update_opts.compression.into());
+
+    for entry_path in update_opts.removals {
+        println!("Removing {}", entry_path);
+        editor.remove(&entry_path)?;
+    }
+
+    for (entry_path, file_path) in update_opts.replacements {
+        println!("Replacing {}", entry_path);
+        editor.replace_file(&entry_path, file_path)?;
+    }
+
+    for (entry_path, file_path) in update_opts.adds {
+        println!("Adding {}", entry_path);
+        editor.add_file(entry_path, file_path)?;
+    }
+
+    match update_opts.output_path {
+        Some(output_path) => {
+            let mut out_file = BufWriter::new(File::create(output_path)?);
+            editor.save(&mut out_file)?;
+            out_file.flush()?;
+        }
+        None => editor.save_in_place()?,
+    }
+
+    Ok(())
+}
+
+#[derive(Parser)]
+struct VerifyOpts {
+    /// input file
+    #[arg(short, long)]
+    input_path: String,
+
+    /// print the report as JSON
+    #[arg(short, long)]
+    json: bool,
+}
+
+fn process_verify(verify_opts: VerifyOpts, key: &[u8], codepage: Codepage) -> anyhow::Result<()> {
+    let mut file = BufReader::new(File::open(&verify_opts.input_path)?);
+    let mut agt_reader = AgtReader::new(&mut file, key);
+    agt_reader.set_codepage(codepage);
+
+    let report = agt_reader.verify()?;
+
+    if verify_opts.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for error in report.errors.iter() {
+            println!("Archive: {}", error);
+        }
+        for entry in report.entries.iter() {
+            if entry.is_ok() {
+                println!("OK     {}", entry.path);
+            } else {
+                println!("FAILED {}", entry.path);
+                for error in entry.errors.iter() {
+                    println!("       - {}", error);
+                }
+            }
+        }
+    }
+
+    if !report.is_ok() {
+        anyhow::bail!(
+            "{} failed verification: {} of {} entries damaged{}",
+            verify_opts.input_path,
+            report.failed_entries().count(),
+            report.header.file_count,
+            if report.errors.is_empty() {
+                ""
+            } else {
+                ", table of contents damaged"
+            }
+        );
+    }
+
+    Ok(())
+}
+
+#[derive(Parser)]
+struct DiffOpts {
+    /// old archive
+    #[arg(long)]
+    old_path: String,
+
+    /// new archive
+    #[arg(long)]
+    new_path: String,
+
+    /// print the report as JSON
+    #[arg(short, long)]
+    json: bool,
+}
+
+fn load_diff(
+    old_path: &str,
+    new_path: &str,
+    key_opts: &KeyOpts,
+    codepage: Codepage,
+) -> anyhow::Result<AgtDiff> {
+    let old_key = key_opts.load(Some(old_path))?;
+    let new_key = key_opts.load(Some(new_path))?;
+    diff_agt(old_path, &old_key, new_path, &new_key, codepage)
+}
+
+fn process_diff(diff_opts: DiffOpts, key_opts: &KeyOpts, codepage: Codepage) -> anyhow::Result<()> {
+    let diff = load_diff(&diff_opts.old_path, &diff_opts.new_path, key_opts, codepage)?;
+
+    if diff_opts.json {
+        println!("{}", serde_json::to_string_pretty(&diff)?);
+        return Ok(());
+    }
+
+    for added in diff.added.iter() {
+        println!("+ {} ({} bytes)", added.path, added.decompressed_length);
+    }
+    for removed in diff.removed.iter() {
+        println!("- {} ({} bytes)", removed.path, removed.decompressed_length);
+    }
+    for changed in diff.changed.iter() {
+        println!(
+            "~ {} ({} -> {} bytes)",
+            changed.path, changed.old_length, changed.new_length
+        );
+    }
+    println!(
+        "{} added, {} removed, {} changed, {} unchanged",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.changed.len(),
+        diff.unchanged
+    );
+
+    Ok(())
+}
+
+#[derive(Parser)]
+struct DeltaOpts {
+    /// old archive
+    #[arg(long)]
+    old_path: String,
+
+    /// new archive
+    #[arg(long)]
+    new_path: String,
+
+    /// output file
+    #[arg(short, long)]
+    output_path: String,
+}
+
+fn process_delta(
+    delta_opts: DeltaOpts,
+    key_opts: &KeyOpts,
+    codepage: Codepage,
+) -> anyhow::Result<()> {
+    let diff = load_diff(
+        &delta_opts.old_path,
+        &delta_opts.new_path,
+        key_opts,
+        codepage,
+    )?;
+
+    for path in diff.patched_paths() {
+        println!("Adding {}", path);
+    }
+    if !diff.removed.is_empty() {
+        println!(
+            "{} removed entries can't be expressed in a patch archive and are left out",
+            diff.removed.len()
+        );
+    }
+
+    let new_key = key_opts.load(Some(&delta_opts.new_path))?;
+    let builder = delta_builder(&diff, &delta_opts.new_path, &new_key, codepage)?;
+
+    let mut out_file = BufWriter::new(File::create(delta_opts.output_path)?);
+    builder.write(&mut out_file, &new_key)?;
+    out_file.flush()?;
+
+    Ok(())
+}
+
+#[derive(Parser)]
+struct SyncOpts {
+    /// archive to compare and update
+    archive_path: String,
+
+    /// directory of loose files, e.g. one created by extract
+    dir_path: String,
+
+    /// write the changes to the archive instead of only reporting them
+    #[arg(short, long)]
+    write: bool,
+
+    /// output file when writing, the archive is overwritten if not given
+    #[arg(short, long, requires = "write")]
+    output_path: Option<String>,
+
+    /// also remove entries that have no file in the directory
+    #[arg(long, requires = "write")]
+    delete: bool,
+
+    /// print the report as JSON
+    #[arg(short, long)]
+    json: bool,
+
+    #[command(flatten)]
+    compression: CompressionOpts,
+}
+
+fn process_sync(sync_opts: SyncOpts, key: &[u8], codepage: Codepage) -> anyhow::Result<()> {
+    let dir_path = Path::new(&sync_opts.dir_path);
+    if !dir_path.is_dir() {
+        anyhow::bail!("{} is not a directory", sync_opts.dir_path);
+    }
+
+    let mut files = collect_directory_files(dir_path)?;
+    files.sort_by_cached_key(|(entry_path, _)| normalize_entry_path(entry_path));
+
+    let report = sync_agt(&sync_opts.archive_path, key, codepage, files)?;
+
+    if sync_opts.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for changed in report.changed.iter() {
+            println!(
+                "~ {} ({} -> {} bytes)",
+                changed.path, changed.entry_length, changed.file_length
+            );
+        }
+        for missing in report.missing.iter() {
+            println!("- {} ({} bytes)", missing.path, missing.decompressed_length);
+        }
+        for new in report.new.iter() {
+            println!("+ {} ({} bytes)", new.path, new.length);
+        }
+        println!(
+            "{} changed, {} missing, {} new, {} unchanged",
+            report.changed.len(),
+            report.missing.len(),
+            report.new.len(),
+            report.unchanged
+        );
+    }
+
+    if !sync_opts.write {
+        return Ok(());
+    }
+    if report.changed.is_empty()
+        && report.new.is_empty()
+        && (report.missing.is_empty() || !sync_opts.delete)
+    {
+        eprintln!("Archive is up to date");
+        return Ok(());
+    }
+
+    let mut editor = AgtEditor::open_with_codepage(&sync_opts.archive_path, key, codepage)?;
+    editor.set_options(sync_opts.compression.into());
+    editor.apply_sync(&report, sync_opts.delete)?;
+
+    match sync_opts.output_path {
+        Some(output_path) => {
+            let mut out_file = BufWriter::new(File::create(output_path)?);
+            editor.save(&mut out_file)?;
+            out_file.flush()?;
+        }
+        None => editor.save_in_place()?,
+    }
+
+    Ok(())
+}
+
+#[derive(Parser)]
+struct MergeOpts {
+    /// input files in priority order, repeat for each archive
+    #[arg(short, long = "input-path", required = true)]
+    input_paths: Vec<String>,
+
+    /// output file
+    #[arg(short, long)]
+    output_path: String,
+
+    /// which archive provides entries found in several: last-wins, first-wins or fail
+    #[arg(long, default_value = "last-wins")]
+    policy: ConflictPolicy,
+
+    /// write which archive each entry came from as JSON
+    #[arg(short, long)]
+    report_path: Option<String>,
+}
+
+fn process_merge(
+    merge_opts: MergeOpts,
+    key_opts: &KeyOpts,
+    codepage: Codepage,
+) -> anyhow::Result<()> {
+    let keys = merge_opts
+        .input_paths
+        .iter()
+        .map(|input_path| key_opts.load(Some(input_path)))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let sources = merge_opts
+        .input_paths
+        .iter()
+        .zip(keys.iter())
+        .map(|(input_path, key)| (input_path.as_str(), key.as_slice()))
+        .collect::<Vec<_>>();
+
+    let (builder, report) = merge_agt(&sources, codepage, merge_opts.policy)?;
+
+    for entry in report.entries.iter() {
+        println!(
+            "{} <- {}",
+            entry.path,
+            report.sources[entry.source_index].display()
+        );
+    }
+
+    // Written with the first archive's key, like the header
+    let mut out_file = BufWriter::new(File::create(merge_opts.output_path)?);
+    builder.write(&mut out_file, &keys[0])?;
+    out_file.flush()?;
+
+    if let Some(report_path) = merge_opts.report_path {
+        let report_file = File::create(report_path)?;
+        serde_json::to_writer_pretty(report_file, &report)?;
+    }
+
+    Ok(())
+}
+
+fn process_keys(key_opts: &KeyOpts) -> anyhow::Result<()> {
+    for known_key in keys::KNOWN_KEYS {
+        println!(
+            "- {} ({} bytes): {}",
+            known_key.name,
+            known_key.key.len(),
+            known_key.description
+        );
+    }
+    let key_files = key_opts.key_files()?;
+    for key_file in key_files.iter() {
+        println!(
+            "- {} ({} bytes): from the keys directory",
+            key_file.name,
+            key_file.key.len()
+        );
+    }
+    if key_files.is_empty() {
+        println!("No regional keys are built in, recover them with recover-key and pass their directory with --keys-dir");
+    }
+
+    Ok(())
+}
+
+#[derive(Parser)]
+struct RecoverKeyOpts {
+    /// input file
+    #[arg(short, long)]
+    input_path: String,
+
+    /// output key file
+    #[arg(short, long)]
+    output_path: String,
+
+    /// longest key length to try
+    #[arg(short, long, default_value_t = 256)]
+    max_key_length: usize,
+
+    /// file whose content is known to be stored decrypted at the given archive offset
+    #[arg(long = "plaintext", value_name = "OFFSET=FILE", value_parser = parse_offset_file)]
+    plaintexts: Vec<(usize, PathBuf)>,
+}
+
+fn parse_offset_file(value: &str) -> Result<(usize, PathBuf), String> {
+    match value.split_once('=') {
+        Some((offset, file_path)) if !file_path.is_empty() => {
+            let offset = match offset.strip_prefix("0x") {
+                Some(hex_offset) => usize::from_str_radix(hex_offset, 16),
+                None => offset.parse(),
+            }
+            .map_err(|e| format!("invalid offset {:?}: {}", offset, e))?;
+            Ok((offset, PathBuf::from(file_path)))
+        }
+        _ => Err(format!("expected OFFSET=FILE, got {:?}", value)),
+    }
+}
+
+fn process_recover_key(recover_key_opts: RecoverKeyOpts) -> anyhow::Result<()> {
+    let data = std::fs::read(&recover_key_opts.input_path)?;
+
+    let mut recovery = KeyRecovery::new(&data);
+    recovery.set_max_key_length(recover_key_opts.max_key_length);
+    for (offset, file_path) in recover_key_opts.plaintexts {
+        recovery.add_known_plaintext(offset, &std::fs::read(file_path)?);
+    }
+
+    let key = recovery.recover()?;
+    if let Some(known_key) = keys::KNOWN_KEYS
+        .iter()
+        .find(|known_key| known_key.key == key.as_slice())
+    {
+        println!("Recovered built-in key {}", known_key.name);
+    }
+    println!("Recovered {} byte key", key.len());
+
+    std::fs::write(recover_key_opts.output_path, key)?;
+
+    Ok(())
+}
+
 pub fn process_agt(agt_opts: AgtOpts) -> anyhow::Result<()> {
+    let key_opts = &agt_opts.key_opts;
+    let codepage = agt_opts.codepage;
+
     match agt_opts.cmd {
-        Command::Info(info_opts) => process_info(info_opts),
+        Command::Info(info_opts) => {
+            let key = key_opts.load(Some(&info_opts.input_path))?;
+            process_info(info_opts, &key, codepage.unwrap_or_default())
+        }
+        Command::Ls(ls_opts) => {
+            let key = key_opts.load(Some(&ls_opts.input_path))?;
+            process_ls(ls_opts, &key, codepage.unwrap_or_default())
+        }
+        Command::Cat(cat_opts) => {
+            let key = key_opts.load(Some(&cat_opts.input_path))?;
+            process_cat(cat_opts, &key, codepage.unwrap_or_default())
+        }
+        Command::Grep(grep_opts) => {
+            let key = key_opts.load(Some(&grep_opts.input_path))?;
+            process_grep(grep_opts, &key, codepage.unwrap_or_default())
+        }
+        Command::Extract(extract_opts) => {
+            let key = key_opts.load(Some(&extract_opts.input_path))?;
+            process_extract(extract_opts, &key, codepage.unwrap_or_default())
+        }
+        Command::Pack(pack_opts) => {
+            let key = key_opts.load(pack_opts.base_path.as_deref())?;
+            process_pack(pack_opts, &key, codepage)
+        }
+        Command::Update(update_opts) => {
+            let key = key_opts.load(Some(&update_opts.input_path))?;
+            process_update(update_opts, &key, codepage.unwrap_or_default())
+        }
+        Command::Verify(verify_opts) => {
+            let key = match key_opts.load(Some(&verify_opts.input_path)) {
+                Ok(key) => key,
+                // A damaged table of contents keeps the key from being detected,
+                // verify reports the damage instead of giving up
+                Err(e) if key_opts.key_path.is_none() && key_opts.key.is_none() => {
+                    eprintln!(
+                        "{}\nVerifying with the default key {} instead",
+                        e,
+                        keys::default_key().name
+                    );
+                    keys::default_key().key.to_vec()
+                }
+                Err(e) => return Err(e),
+            };
+            process_verify(verify_opts, &key, codepage.unwrap_or_default())
+        }
+        Command::Diff(diff_opts) => process_diff(diff_opts, key_opts, codepage.unwrap_or_default()),
+        Command::Delta(delta_opts) => {
+            process_delta(delta_opts, key_opts, codepage.unwrap_or_default())
+        }
+        Command::Sync(sync_opts) => {
+            let key = key_opts.load(Some(&sync_opts.archive_path))?;
+            process_sync(sync_opts, &key, codepage.unwrap_or_default())
+        }
+        Command::Merge(merge_opts) => {
+            process_merge(merge_opts, key_opts, codepage.unwrap_or_default())
+        }
+        Command::Keys => process_keys(key_opts),
+        Command::RecoverKey(recover_key_opts) => process_recover_key(recover_key_opts),
     }
 }
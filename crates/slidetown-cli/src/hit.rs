@@ -0,0 +1,313 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use clap::{Parser, Subcommand};
+use nif::{
+    collectors::gltf::json,
+    glam::{Mat4, Vec3},
+};
+use slidetown::parsers::hit;
+
+use crate::util::mesh_gltf::MeshGltf;
+
+#[derive(Parser)]
+pub struct HitOpts {
+    #[command(subcommand)]
+    cmd: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// display info about collision area
+    Info(InfoOpts),
+
+    /// export collision area to obj
+    Obj(ExportOpts),
+
+    /// export collision area to gltf
+    Gltf(ExportOpts),
+
+    /// build collision area from an obj or gltf triangle mesh
+    Import(ImportOpts),
+}
+
+#[derive(Parser)]
+struct InfoOpts {
+    /// input file
+    #[arg(short, long)]
+    input_path: String,
+}
+
+fn process_info(info_opts: InfoOpts) -> anyhow::Result<()> {
+    let mut file = BufReader::new(File::open(info_opts.input_path)?);
+    let hit = hit::Hit::read(&mut file)?;
+
+    println!("Index count: {}", hit.indices.len());
+    println!("Triangle count: {}", hit.triangles().count());
+    println!("Vertex count: {}", hit.verts.len());
+
+    if let Some((min, max)) = bounds(&hit) {
+        println!("Bounds min: {:?}", min);
+        println!("Bounds max: {:?}", max);
+    }
+
+    match hit.check() {
+        Ok(()) => println!("No problems found"),
+        Err(e) => println!("Problem: {}", e),
+    }
+
+    Ok(())
+}
+
+fn bounds(hit: &hit::Hit) -> Option<(Vec3, Vec3)> {
+    let mut verts = hit.verts.iter().map(|&vert| Vec3::from(vert));
+    let first = verts.next()?;
+    Some(verts.fold((first, first), |(min, max), vert| {
+        (min.min(vert), max.max(vert))
+    }))
+}
+
+#[derive(Parser)]
+struct ExportOpts {
+    /// input file
+    #[arg(short, long)]
+    input_path: String,
+
+    /// output file
+    #[arg(short, long)]
+    output_path: String,
+}
+
+fn process_obj(obj_opts: ExportOpts) -> anyhow::Result<()> {
+    let mut file = BufReader::new(File::open(obj_opts.input_path)?);
+    let hit = hit::Hit::read(&mut file)?;
+
+    // Written directly since areas can have more vertices than nif_obj's u16 triangles allow
+    let mut out_obj = BufWriter::new(File::create(obj_opts.output_path)?);
+    writeln!(&mut out_obj, "g Area")?;
+    for vert in hit.verts.iter() {
+        writeln!(&mut out_obj, "v {} {} {}", vert.0, vert.1, vert.2)?;
+    }
+    for [a, b, c] in hit.triangles() {
+        writeln!(&mut out_obj, "f {} {} {}", a + 1, b + 1, c + 1)?;
+    }
+    out_obj.flush()?;
+
+    Ok(())
+}
+
+fn process_gltf(gltf_opts: ExportOpts) -> anyhow::Result<()> {
+    let mut file = BufReader::new(File::open(gltf_opts.input_path)?);
+    let hit = hit::Hit::read(&mut file)?;
+
+    let vertices = hit
+        .verts
+        .iter()
+        .map(|&vert| Vec3::from(vert))
+        .collect::<Vec<_>>();
+
+    let mut gltf = MeshGltf::new();
+    let material = gltf.add_material("Area", [0.0, 0.5, 1.0, 1.0]);
+    let mesh = gltf.add_mesh("Area", &vertices, &hit.indices, Some(material));
+    let node = gltf.add_node(json::Node {
+        mesh: Some(mesh),
+        name: Some("Area".into()),
+        ..Default::default()
+    });
+    gltf.add_scene("Area", vec![node]);
+
+    gltf.write_to_files(Path::new(&gltf_opts.output_path))
+}
+
+#[derive(Parser)]
+struct ImportOpts {
+    /// input mesh, .obj, .gltf or .glb
+    #[arg(short, long)]
+    input_path: String,
+
+    /// output file
+    #[arg(short, long)]
+    output_path: String,
+}
+
+/// Triangle list merged from every mesh of a file
+#[derive(Default)]
+struct TriangleMesh {
+    verts: Vec<(f32, f32, f32)>,
+    indices: Vec<u32>,
+}
+
+fn process_import(import_opts: ImportOpts) -> anyhow::Result<()> {
+    let input_path = Path::new(&import_opts.input_path);
+    let extension = input_path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_lowercase);
+
+    let mesh = match extension.as_deref() {
+        Some("obj") => read_obj(input_path)?,
+        Some("gltf") | Some("glb") => read_gltf(input_path)?,
+        _ => anyhow::bail!("input must be an .obj, .gltf or .glb file"),
+    };
+
+    let hit = hit::Hit::from_triangles(mesh.verts, mesh.indices).map_err(|e| {
+        anyhow::anyhow!(
+            "{} is not a valid collision area: {}",
+            input_path.display(),
+            e
+        )
+    })?;
+
+    println!(
+        "Importing {} triangles with {} vertices",
+        hit.triangles().count(),
+        hit.verts.len()
+    );
+
+    let mut out_file = BufWriter::new(File::create(import_opts.output_path)?);
+    hit.write(&mut out_file)?;
+
+    Ok(())
+}
+
+/// Read vertex positions and faces of an OBJ, splitting polygons into triangle fans
+fn read_obj(path: &Path) -> anyhow::Result<TriangleMesh> {
+    let file = BufReader::new(File::open(path)?);
+    let mut mesh = TriangleMesh::default();
+
+    for (line_index, line) in file.lines().enumerate() {
+        let line = line?;
+        let line_number = line_index + 1;
+        let mut tokens = line.split_whitespace();
+
+        match tokens.next() {
+            Some("v") => {
+                let coords = tokens
+                    .take(3)
+                    .map(str::parse::<f32>)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| anyhow::anyhow!("line {}: invalid vertex: {}", line_number, e))?;
+                let &[x, y, z] = coords.as_slice() else {
+                    anyhow::bail!("line {}: vertex needs 3 coordinates", line_number);
+                };
+                mesh.verts.push((x, y, z));
+            }
+            Some("f") => {
+                let face = tokens
+                    .map(|token| obj_vertex_index(token, mesh.verts.len()))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| anyhow::anyhow!("line {}: invalid face", line_number))?;
+                if face.len() < 3 {
+                    anyhow::bail!("line {}: face needs at least 3 vertices", line_number);
+                }
+                for i in 1..face.len() - 1 {
+                    mesh.indices
+                        .extend_from_slice(&[face[0], face[i], face[i + 1]]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(mesh)
+}
+
+/// Zero-based vertex index of a face token like `3`, `3/1/2` or `-1`.
+///
+/// Negative indices count back from the vertices read so far, bounds are checked later.
+fn obj_vertex_index(token: &str, vert_count: usize) -> Option<u32> {
+    let index = token.split('/').next()?.parse::<i64>().ok()?;
+    let index = match index {
+        0 => return None,
+        index if index < 0 => vert_count as i64 + index,
+        index => index - 1,
+    };
+    u32::try_from(index).ok()
+}
+
+/// Read every triangle primitive in the default scene of a glTF, in world space
+fn read_gltf(path: &Path) -> anyhow::Result<TriangleMesh> {
+    // Only buffers are loaded, images of the materials don't matter here
+    let gltf::Gltf { document, blob } = gltf::Gltf::open(path)?;
+    let buffers = gltf::import_buffers(&document, path.parent(), blob)?;
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or_else(|| anyhow::anyhow!("{} has no scenes", path.display()))?;
+
+    let mut mesh = TriangleMesh::default();
+    let mut stack = scene
+        .nodes()
+        .map(|node| (node, Mat4::IDENTITY))
+        .collect::<Vec<_>>();
+
+    while let Some((node, parent_transform)) = stack.pop() {
+        let transform = parent_transform * Mat4::from_cols_array_2d(&node.transform().matrix());
+        stack.extend(node.children().map(|child| (child, transform)));
+
+        let Some(node_mesh) = node.mesh() else {
+            continue;
+        };
+        let mesh_name = match node_mesh.name() {
+            Some(name) => name.to_owned(),
+            None => format!("#{}", node_mesh.index()),
+        };
+        for primitive in node_mesh.primitives() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                anyhow::bail!(
+                    "mesh {} has a {:?} primitive, only triangles are supported",
+                    mesh_name,
+                    primitive.mode()
+                );
+            }
+
+            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+            let Some(positions) = reader.read_positions() else {
+                continue;
+            };
+
+            let first_vert = mesh.verts.len() as u32;
+            let vert_count = positions.len() as u32;
+            mesh.verts.extend(positions.map(|position| {
+                let vert = transform.transform_point3(Vec3::from(position));
+                (vert.x, vert.y, vert.z)
+            }));
+
+            match reader.read_indices() {
+                Some(indices) => {
+                    for index in indices.into_u32() {
+                        // Past this primitive's vertices an index would silently point into
+                        // another one, so it has to be caught here rather than by Hit::check
+                        let mesh_index = first_vert
+                            .checked_add(index)
+                            .filter(|_| index < vert_count)
+                            .ok_or_else(|| {
+                                anyhow::anyhow!(
+                                    "mesh {} has index {} past its {} vertices",
+                                    mesh_name,
+                                    index,
+                                    vert_count
+                                )
+                            })?;
+                        mesh.indices.push(mesh_index);
+                    }
+                }
+                None => mesh.indices.extend(first_vert..first_vert + vert_count),
+            }
+        }
+    }
+
+    Ok(mesh)
+}
+
+pub fn process_hit(hit_opts: HitOpts) -> anyhow::Result<()> {
+    match hit_opts.cmd {
+        Command::Info(info_opts) => process_info(info_opts),
+        Command::Obj(obj_opts) => process_obj(obj_opts),
+        Command::Gltf(gltf_opts) => process_gltf(gltf_opts),
+        Command::Import(import_opts) => process_import(import_opts),
+    }
+}
@@ -1,12 +1,12 @@
-use std::{
-    fs::File,
-    io::{Cursor, Read, Seek, SeekFrom},
-};
+use std::{collections::HashSet, fs::File, io::Cursor, path::Path};
 
 use clap::{Parser, Subcommand};
-use slidetown::parsers::lbf;
+use slidetown::parsers::{lbf, EmbeddedBlobArchive};
 
-use crate::util::nif_obj;
+use crate::util::{
+    blobs::{nif_file_name, pack_blobs, unpack_blobs},
+    nif_obj,
+};
 
 #[derive(Parser)]
 pub struct LbfOpts {
@@ -19,6 +19,12 @@ enum Command {
     /// display info about archive contents
     Info(InfoOpts),
 
+    /// unpack block object nifs and create manifest
+    Unpack(UnpackOpts),
+
+    /// pack block object nifs using manifest
+    Pack(PackOpts),
+
     /// export preview obj with terrain blocks
     Obj(ObjOpts),
 
@@ -59,34 +65,30 @@ fn process_obj(obj_opts: ObjOpts) -> anyhow::Result<()> {
 
     let mut obj = nif_obj::Obj::default();
 
-    for block in lbf.blocks {
-        for block_object in block.objects {
-            file.seek(SeekFrom::Start(block_object.file_offset as u64))?;
-
-            let mut nif_buf = vec![0u8; block_object.file_length as usize];
-            file.read_exact(&mut nif_buf)?;
-
-            let mut nif_cursor = Cursor::new(nif_buf);
-
-            let nif = match nif::Nif::parse(&mut nif_cursor) {
-                Ok(nif) => nif,
-                Err(e) => {
-                    println!(
-                        "Failed to parse NIF for block index {} unk {}: {:?}",
-                        block_object.block_index, block_object.unk, e
-                    );
-                    continue;
-                }
-            };
-
-            obj.visit_nif(
-                &nif,
-                Some(format!(
-                    "Block{}Object{}",
-                    block_object.block_index, block_object.unk
-                )),
-            );
-        }
+    let block_objects = lbf.blocks.iter().flat_map(|block| block.objects.iter());
+    for (block_object, blob) in block_objects.zip(lbf.blobs()) {
+        let nif_buf = blob.read(&mut file)?;
+
+        let mut nif_cursor = Cursor::new(nif_buf);
+
+        let nif = match nif::Nif::parse(&mut nif_cursor) {
+            Ok(nif) => nif,
+            Err(e) => {
+                println!(
+                    "Failed to parse NIF for block index {} unk {}: {:?}",
+                    block_object.block_index, block_object.unk, e
+                );
+                continue;
+            }
+        };
+
+        obj.visit_nif(
+            &nif,
+            Some(format!(
+                "Block{}Object{}",
+                block_object.block_index, block_object.unk
+            )),
+        );
     }
 
     let obj_path = std::path::PathBuf::from(obj_opts.output_path);
@@ -114,35 +116,31 @@ fn process_gltf(gltf_opts: GltfOpts) -> anyhow::Result<()> {
 
     let mut gltf = nif::collectors::gltf::Gltf::new();
 
-    for block in lbf.blocks {
-        for block_object in block.objects {
-            file.seek(SeekFrom::Start(block_object.file_offset as u64))?;
-
-            let mut nif_buf = vec![0u8; block_object.file_length as usize];
-            file.read_exact(&mut nif_buf)?;
-
-            let mut nif_cursor = Cursor::new(nif_buf);
-
-            let nif = match nif::Nif::parse(&mut nif_cursor) {
-                Ok(nif) => nif,
-                Err(e) => {
-                    println!(
-                        "Failed to parse NIF for block index {} unk {}: {:?}",
-                        block_object.block_index, block_object.unk, e
-                    );
-                    continue;
-                }
-            };
-
-            gltf.visit_nif(
-                &nif,
-                Some("Block Objects"),
-                &format!(
-                    "Block{}Object{}",
-                    block_object.block_index, block_object.unk
-                ),
-            );
-        }
+    let block_objects = lbf.blocks.iter().flat_map(|block| block.objects.iter());
+    for (block_object, blob) in block_objects.zip(lbf.blobs()) {
+        let nif_buf = blob.read(&mut file)?;
+
+        let mut nif_cursor = Cursor::new(nif_buf);
+
+        let nif = match nif::Nif::parse(&mut nif_cursor) {
+            Ok(nif) => nif,
+            Err(e) => {
+                println!(
+                    "Failed to parse NIF for block index {} unk {}: {:?}",
+                    block_object.block_index, block_object.unk, e
+                );
+                continue;
+            }
+        };
+
+        gltf.visit_nif(
+            &nif,
+            Some("Block Objects"),
+            &format!(
+                "Block{}Object{}",
+                block_object.block_index, block_object.unk
+            ),
+        );
     }
 
     let gltf_path = std::path::PathBuf::from(gltf_opts.output_path);
@@ -151,9 +149,77 @@ fn process_gltf(gltf_opts: GltfOpts) -> anyhow::Result<()> {
     Ok(())
 }
 
+#[derive(Parser)]
+struct UnpackOpts {
+    /// input file
+    #[arg(short, long)]
+    input_path: String,
+
+    /// output directory
+    #[arg(short, long)]
+    output_path: String,
+}
+
+fn process_unpack(unpack_opts: UnpackOpts) -> anyhow::Result<()> {
+    let mut file = File::open(&unpack_opts.input_path)?;
+
+    let lbf_archive: lbf::Lbf = lbf::Lbf::parse(&mut file)?;
+
+    // Object files are named by block index and position within the block so pack can
+    // find them again after blocks were reordered, which needs unique block indices
+    let mut blob_keys = HashSet::new();
+    for blob in lbf_archive.blobs() {
+        if !blob_keys.insert(blob.key.clone()) {
+            anyhow::bail!("more than one object is named {}", nif_file_name(&blob));
+        }
+    }
+
+    let out_dir_path = Path::new(&unpack_opts.output_path);
+    std::fs::create_dir_all(out_dir_path)?;
+
+    {
+        let manifest_file = File::create(out_dir_path.join("manifest.json"))?;
+        serde_json::to_writer_pretty(manifest_file, &lbf_archive)?;
+    }
+
+    unpack_blobs(&lbf_archive, &mut file, out_dir_path, nif_file_name)
+}
+
+#[derive(Parser)]
+struct PackOpts {
+    /// input manifest
+    #[arg(short, long)]
+    input_path: String,
+
+    /// output file
+    #[arg(short, long)]
+    output_path: String,
+}
+
+fn process_pack(pack_opts: PackOpts) -> anyhow::Result<()> {
+    let input_path = Path::new(&pack_opts.input_path);
+
+    let mut lbf_archive: lbf::Lbf = {
+        let manifest_file = File::open(input_path)?;
+        serde_json::from_reader(manifest_file)?
+    };
+
+    // Objects may have been added to or removed from the manifest
+    lbf_archive.update_counts();
+
+    pack_blobs(
+        &mut lbf_archive,
+        input_path,
+        Path::new(&pack_opts.output_path),
+        nif_file_name,
+    )
+}
+
 pub fn process_lbf(lbf_opts: LbfOpts) -> anyhow::Result<()> {
     match lbf_opts.cmd {
         Command::Info(info_opts) => process_info(info_opts),
+        Command::Unpack(unpack_opts) => process_unpack(unpack_opts),
+        Command::Pack(pack_opts) => process_pack(pack_opts),
         Command::Obj(obj_opts) => process_obj(obj_opts),
         Command::Gltf(gltf_opts) => process_gltf(gltf_opts),
     }
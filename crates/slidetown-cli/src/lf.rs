@@ -1,14 +1,12 @@
-use std::{
-    convert::TryInto,
-    fs::File,
-    io::{BufWriter, Cursor, Read, Seek, SeekFrom, Write},
-    path::Path,
-};
+use std::{fs::File, io::Cursor, path::Path};
 
 use clap::{Parser, Subcommand};
-use slidetown::parsers::{lf, EntryOffsets};
+use slidetown::parsers::{lf, EmbeddedBlobArchive};
 
-use crate::util::nif_obj;
+use crate::util::{
+    blobs::{nif_file_name, pack_blobs, unpack_blobs},
+    nif_obj,
+};
 
 #[derive(Parser)]
 pub struct LfOpts {
@@ -77,11 +75,8 @@ fn process_obj(obj_opts: ObjOpts) -> anyhow::Result<()> {
 
     let mut obj = nif_obj::Obj::default();
 
-    for block in lf.blocks {
-        file.seek(SeekFrom::Start(block.file_offset as u64))?;
-
-        let mut nif_buf = vec![0u8; block.file_length as usize];
-        file.read_exact(&mut nif_buf)?;
+    for (block, blob) in lf.blocks.iter().zip(lf.blobs()) {
+        let nif_buf = blob.read(&mut file)?;
 
         let mut nif_cursor = Cursor::new(nif_buf);
 
@@ -124,11 +119,8 @@ fn process_gltf(gltf_opts: GltfOpts) -> anyhow::Result<()> {
 
     let mut gltf = nif::collectors::gltf::Gltf::new();
 
-    for block in lf.blocks {
-        file.seek(SeekFrom::Start(block.file_offset as u64))?;
-
-        let mut nif_buf = vec![0u8; block.file_length as usize];
-        file.read_exact(&mut nif_buf)?;
+    for (block, blob) in lf.blocks.iter().zip(lf.blobs()) {
+        let nif_buf = blob.read(&mut file)?;
 
         let mut nif_cursor = Cursor::new(nif_buf);
 
@@ -177,25 +169,7 @@ fn process_unpack(unpack_opts: UnpackOpts) -> anyhow::Result<()> {
         serde_json::to_writer_pretty(manifest_file, &lf_archive)?;
     }
 
-    for lf_block in lf_archive.blocks {
-        println!("Writing block {}", lf_block.index);
-
-        let nif_position: u64 = lf_block.file_offset.into();
-        let nif_length: usize = lf_block
-            .file_length
-            .try_into()
-            .expect("Block file size too high");
-
-        let mut nif_buffer = vec![0u8; nif_length];
-
-        file.seek(SeekFrom::Start(nif_position))?;
-        file.read_exact(&mut nif_buffer)?;
-
-        let mut nif_file = File::create(out_dir_path.join(format!("{}.nif", lf_block.index)))?;
-        nif_file.write_all(&nif_buffer)?;
-    }
-
-    Ok(())
+    unpack_blobs(&lf_archive, &mut file, out_dir_path, nif_file_name)
 }
 
 #[derive(Parser)]
@@ -219,27 +193,12 @@ fn process_pack(pack_opts: PackOpts) -> anyhow::Result<()> {
 
     lf_archive.header.version_date = 20090406;
 
-    let offsets = EntryOffsets::default();
-
-    let mut out_file = BufWriter::new(File::create(pack_opts.output_path)?);
-    lf_archive.write_without_data(&mut out_file, offsets.clone())?;
-
-    for (block, &header_offset) in lf_archive.blocks.iter().zip(offsets.borrow().iter()) {
-        let block_file_path = input_path.with_file_name(format!("{}.nif", block.index));
-
-        let file_offset = out_file.stream_position()? as u32;
-
-        let mut block_file = File::open(block_file_path)?;
-        let file_length = std::io::copy(&mut block_file, &mut out_file)? as u32;
-
-        // Go back and fill in header offsets
-        out_file.seek(SeekFrom::Start(header_offset))?;
-        out_file.write_all(&file_offset.to_le_bytes())?;
-        out_file.write_all(&file_length.to_le_bytes())?;
-        out_file.seek(SeekFrom::Start((file_offset + file_length).into()))?;
-    }
-
-    Ok(())
+    pack_blobs(
+        &mut lf_archive,
+        input_path,
+        Path::new(&pack_opts.output_path),
+        nif_file_name,
+    )
 }
 
 pub fn process_lf(lf_opts: LfOpts) -> anyhow::Result<()> {
@@ -1,12 +1,7 @@
-use std::{
-    fs::File,
-    io::{Cursor, Read, Seek, SeekFrom},
-};
-
 use clap::{Parser, Subcommand};
 use slidetown::parsers::llf;
 
-use crate::util::nif_obj;
+use crate::util::blobs::{BlockNifArchive, GltfOpts, InfoOpts, ObjOpts, PackOpts, UnpackOpts};
 
 #[derive(Parser)]
 pub struct LlfOpts {
@@ -19,6 +14,12 @@ enum Command {
     /// display info about archive contents
     Info(InfoOpts),
 
+    /// unpack lane decal nifs and create manifest
+    Unpack(UnpackOpts),
+
+    /// pack lane decal nifs using manifest
+    Pack(PackOpts),
+
     /// export preview obj with terrain lane decals
     Obj(ObjOpts),
 
@@ -26,122 +27,18 @@ enum Command {
     Gltf(GltfOpts),
 }
 
-#[derive(Parser)]
-struct InfoOpts {
-    /// input file
-    #[arg(short, long)]
-    input_path: String,
-}
-
-fn process_info(info_opts: InfoOpts) -> anyhow::Result<()> {
-    let mut file = File::open(info_opts.input_path)?;
-    let llf: llf::Llf = llf::Llf::read(&mut file)?;
-
-    println!("Block count: {}", llf.blocks.len());
-
-    Ok(())
-}
-
-#[derive(Parser)]
-struct ObjOpts {
-    /// input file
-    #[arg(short, long)]
-    input_path: String,
-
-    /// output file
-    #[arg(short, long)]
-    output_path: String,
-}
-
-fn process_obj(obj_opts: ObjOpts) -> anyhow::Result<()> {
-    let mut file = File::open(&obj_opts.input_path)?;
-    let llf: llf::Llf = llf::Llf::read(&mut file)?;
-
-    let mut obj = nif_obj::Obj::default();
-
-    for block in llf.blocks {
-        file.seek(SeekFrom::Start(block.file_offset as u64))?;
-
-        let mut nif_buf = vec![0u8; block.file_length as usize];
-        file.read_exact(&mut nif_buf)?;
-
-        let mut nif_cursor = Cursor::new(nif_buf);
-
-        let nif = match nif::Nif::parse(&mut nif_cursor) {
-            Ok(nif) => nif,
-            Err(e) => {
-                println!(
-                    "Failed to parse NIF for block index {}: {:?}",
-                    block.block_index, e
-                );
-                continue;
-            }
-        };
-
-        obj.visit_nif(&nif, Some(format!("Block{}Lane", block.block_index)));
-    }
-
-    let obj_path = std::path::PathBuf::from(obj_opts.output_path);
-    let mtl_path = obj_path.with_extension("mtl");
-
-    obj.write_to_files(obj_path, mtl_path)?;
-
-    Ok(())
-}
-
-#[derive(Parser)]
-struct GltfOpts {
-    /// input file
-    #[arg(short, long)]
-    input_path: String,
-
-    /// output file
-    #[arg(short, long)]
-    output_path: String,
-}
-
-fn process_gltf(gltf_opts: GltfOpts) -> anyhow::Result<()> {
-    let mut file = File::open(&gltf_opts.input_path)?;
-    let llf: llf::Llf = llf::Llf::read(&mut file)?;
-
-    let mut gltf = nif::collectors::gltf::Gltf::new();
-
-    for block in llf.blocks {
-        file.seek(SeekFrom::Start(block.file_offset as u64))?;
-
-        let mut nif_buf = vec![0u8; block.file_length as usize];
-        file.read_exact(&mut nif_buf)?;
-
-        let mut nif_cursor = Cursor::new(nif_buf);
-
-        let nif = match nif::Nif::parse(&mut nif_cursor) {
-            Ok(nif) => nif,
-            Err(e) => {
-                println!(
-                    "Failed to parse NIF for block index {} : {:?}",
-                    block.block_index, e
-                );
-                continue;
-            }
-        };
-
-        gltf.visit_nif(
-            &nif,
-            Some("Block Lane Decals"),
-            &format!("Block{}Lane", block.block_index),
-        );
-    }
-
-    let gltf_path = std::path::PathBuf::from(gltf_opts.output_path);
-    gltf.write_to_files(gltf_path)?;
-
-    Ok(())
-}
+const ARCHIVE: BlockNifArchive<llf::Llf> = BlockNifArchive {
+    read: llf::Llf::read,
+    object_name: "Lane",
+    group_name: "Block Lane Decals",
+};
 
 pub fn process_llf(llf_opts: LlfOpts) -> anyhow::Result<()> {
     match llf_opts.cmd {
-        Command::Info(info_opts) => process_info(info_opts),
-        Command::Obj(obj_opts) => process_obj(obj_opts),
-        Command::Gltf(gltf_opts) => process_gltf(gltf_opts),
+        Command::Info(info_opts) => ARCHIVE.process_info(info_opts),
+        Command::Unpack(unpack_opts) => ARCHIVE.process_unpack(unpack_opts),
+        Command::Pack(pack_opts) => ARCHIVE.process_pack(pack_opts),
+        Command::Obj(obj_opts) => ARCHIVE.process_obj(obj_opts),
+        Command::Gltf(gltf_opts) => ARCHIVE.process_gltf(gltf_opts),
     }
 }
@@ -1,20 +1,31 @@
-use std::{
-    collections::HashMap,
-    convert::TryInto,
-    fs::File,
-    io::{BufWriter, Cursor, Read, Seek, SeekFrom, Write},
-    path::Path,
-};
+use std::{collections::HashMap, fs::File, io::Cursor, path::Path};
 
 use clap::{Parser, Subcommand};
-use slidetown::parsers::{lof, EntryOffsets};
+use serde::{Deserialize, Serialize};
+use slidetown::parsers::{lof, Codepage, EmbeddedBlobArchive};
 
-use crate::util::nif_obj::Obj;
+use crate::util::{
+    blobs::{pack_blobs, unpack_blobs},
+    nif_obj::Obj,
+};
 
 #[derive(Parser)]
 pub struct LofOpts {
     #[command(subcommand)]
     cmd: Command,
+
+    /// codepage of model names, e.g. big5 or gbk; defaults to the manifest's or euc-kr
+    #[arg(long, global = true)]
+    codepage: Option<Codepage>,
+}
+
+/// Model table written next to unpacked models, along with the codepage its names were read in
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    codepage: Option<Codepage>,
+    #[serde(flatten)]
+    lof: lof::Lof,
 }
 
 #[derive(Subcommand)]
@@ -42,9 +53,9 @@ struct InfoOpts {
     input_path: String,
 }
 
-fn process_info(info_opts: InfoOpts) -> anyhow::Result<()> {
+fn process_info(info_opts: InfoOpts, codepage: Codepage) -> anyhow::Result<()> {
     let mut file = File::open(info_opts.input_path)?;
-    let lof: lof::Lof = lof::Lof::read_without_data(&mut file)?;
+    let lof: lof::Lof = lof::Lof::read_without_data_with_codepage(&mut file, codepage)?;
 
     println!("Model count: {}", lof.models.len());
 
@@ -62,45 +73,27 @@ struct UnpackOpts {
     output_path: String,
 }
 
-fn process_unpack(unpack_opts: UnpackOpts) -> anyhow::Result<()> {
+fn process_unpack(unpack_opts: UnpackOpts, codepage: Codepage) -> anyhow::Result<()> {
     let mut file = File::open(&unpack_opts.input_path)?;
 
-    let lof_archive: lof::Lof =
-        lof::Lof::read_without_data(&mut file).expect("Could not parse LOF");
+    let lof_archive: lof::Lof = lof::Lof::read_without_data_with_codepage(&mut file, codepage)
+        .map_err(|e| anyhow::anyhow!("Could not parse LOF, try another --codepage: {}", e))?;
+    let manifest = Manifest {
+        codepage: Some(codepage),
+        lof: lof_archive,
+    };
 
     let out_dir_path = Path::new(&unpack_opts.output_path);
     std::fs::create_dir_all(out_dir_path).expect("Could not create output directory");
 
     {
         let manifest_file = File::create(out_dir_path.join("manifest.json"))?;
-        serde_json::to_writer_pretty(manifest_file, &lof_archive)?;
-    }
-
-    for lof_model in lof_archive.models {
-        println!("Writing model {}", lof_model.file_name);
-
-        let nif_position: u64 = lof_model.file_offset.into();
-        let nif_length: usize = lof_model
-            .file_length
-            .try_into()
-            .expect("Model file size too high");
-
-        let mut nif_buffer = vec![0u8; nif_length];
-
-        file.seek(SeekFrom::Start(nif_position))?;
-        file.read_exact(&mut nif_buffer)?;
-
-        let nif_path = out_dir_path.join(lof_model.file_name);
-        let nif_dir = nif_path.with_file_name("");
-        std::fs::create_dir_all(nif_dir).expect("Could not create directory for model");
-
-        let mut nif_file = File::create(nif_path).expect("Failed to open model file for writing");
-        nif_file
-            .write_all(&nif_buffer)
-            .expect("Failed to write to model file");
+        serde_json::to_writer_pretty(manifest_file, &manifest)?;
     }
 
-    Ok(())
+    unpack_blobs(&manifest.lof, &mut file, out_dir_path, |blob| {
+        blob.key.clone()
+    })
 }
 
 #[derive(Parser)]
@@ -114,40 +107,25 @@ struct PackOpts {
     output_path: String,
 }
 
-fn process_pack(pack_opts: PackOpts) -> anyhow::Result<()> {
+fn process_pack(pack_opts: PackOpts, codepage: Option<Codepage>) -> anyhow::Result<()> {
     let input_path = Path::new(&pack_opts.input_path);
 
-    let lof_archive: lof::Lof = {
+    let manifest: Manifest = {
         let manifest_file = File::open(input_path).expect("Failed to open manifest for reading");
         serde_json::from_reader(manifest_file).expect("Failed to parse manifest")
     };
-
-    let mut out_file = BufWriter::new(
-        File::create(pack_opts.output_path).expect("Failed to create lof for writing"),
-    );
-
-    let offsets = EntryOffsets::default();
-
-    lof_archive.write_without_data(&mut out_file, offsets.clone())?;
-
-    for (model, &header_offset) in lof_archive.models.iter().zip(offsets.borrow().iter()) {
-        let model_file_path = input_path.with_file_name("").join(&model.file_name);
-
-        let file_offset = out_file.stream_position()? as u32;
-
-        let mut model_file =
-            File::open(model_file_path).expect("Failed to open model for writing into lof");
-        let file_length = std::io::copy(&mut model_file, &mut out_file)
-            .expect("Failed to write model to lof") as u32;
-
-        // Go back and fill in header offsets
-        out_file.seek(SeekFrom::Start(header_offset))?;
-        out_file.write_all(&file_offset.to_le_bytes())?;
-        out_file.write_all(&file_length.to_le_bytes())?;
-        out_file.seek(SeekFrom::Start((file_offset + file_length).into()))?;
-    }
-
-    Ok(())
+    let codepage = codepage
+        .or(manifest.codepage)
+        .unwrap_or(lof::Lof::DEFAULT_CODEPAGE);
+    let mut lof_archive = manifest.lof;
+    lof_archive.codepage = codepage;
+
+    pack_blobs(
+        &mut lof_archive,
+        input_path,
+        Path::new(&pack_opts.output_path),
+        |blob| blob.key.clone(),
+    )
 }
 
 #[derive(Parser)]
@@ -161,17 +139,17 @@ struct ObjOpts {
     output_dir: String,
 }
 
-pub fn process_obj_inner(input_path: &str) -> anyhow::Result<HashMap<u32, Obj>> {
+pub fn process_obj_inner(
+    input_path: &str,
+    codepage: Codepage,
+) -> anyhow::Result<HashMap<u32, Obj>> {
     let mut file = File::open(input_path)?;
-    let lof: lof::Lof = lof::Lof::read_without_data(&mut file)?;
+    let lof: lof::Lof = lof::Lof::read_without_data_with_codepage(&mut file, codepage)?;
 
     let mut models = HashMap::new();
 
-    for model in lof.models {
-        file.seek(SeekFrom::Start(model.file_offset as u64))?;
-
-        let mut nif_buf = vec![0u8; model.file_length as usize];
-        file.read_exact(&mut nif_buf)?;
+    for (model, blob) in lof.models.iter().zip(lof.blobs()) {
+        let nif_buf = blob.read(&mut file)?;
 
         let mut nif_cursor = Cursor::new(nif_buf);
 
@@ -195,8 +173,8 @@ pub fn process_obj_inner(input_path: &str) -> anyhow::Result<HashMap<u32, Obj>>
     Ok(models)
 }
 
-fn process_obj(obj_opts: ObjOpts) -> anyhow::Result<()> {
-    let models = process_obj_inner(&obj_opts.input_path)?;
+fn process_obj(obj_opts: ObjOpts, codepage: Codepage) -> anyhow::Result<()> {
+    let models = process_obj_inner(&obj_opts.input_path, codepage)?;
 
     let obj_dir = Path::new(&obj_opts.output_dir);
     std::fs::create_dir_all(obj_dir).expect("Could not create output directory");
@@ -225,6 +203,7 @@ struct GltfOpts {
 pub fn process_gltf_inner(
     input_path: &str,
     scene_name: Option<&str>,
+    codepage: Codepage,
 ) -> anyhow::Result<(
     nif::collectors::gltf::Gltf,
     std::collections::HashMap<
@@ -233,16 +212,13 @@ pub fn process_gltf_inner(
     >,
 )> {
     let mut file = File::open(input_path)?;
-    let lof: lof::Lof = lof::Lof::read_without_data(&mut file)?;
+    let lof: lof::Lof = lof::Lof::read_without_data_with_codepage(&mut file, codepage)?;
 
     let mut gltf = nif::collectors::gltf::Gltf::new();
     let mut model_indices = std::collections::HashMap::new();
 
-    for model in lof.models {
-        file.seek(SeekFrom::Start(model.file_offset as u64))?;
-
-        let mut nif_buf = vec![0u8; model.file_length as usize];
-        file.read_exact(&mut nif_buf)?;
+    for (model, blob) in lof.models.iter().zip(lof.blobs()) {
+        let nif_buf = blob.read(&mut file)?;
 
         let mut nif_cursor = Cursor::new(nif_buf);
 
@@ -266,8 +242,9 @@ pub fn process_gltf_inner(
     Ok((gltf, model_indices))
 }
 
-fn process_gltf(gltf_opts: GltfOpts) -> anyhow::Result<()> {
-    let (gltf, _model_indices) = process_gltf_inner(&gltf_opts.input_path, Some("Models"))?;
+fn process_gltf(gltf_opts: GltfOpts, codepage: Codepage) -> anyhow::Result<()> {
+    let (gltf, _model_indices) =
+        process_gltf_inner(&gltf_opts.input_path, Some("Models"), codepage)?;
 
     let gltf_path = std::path::PathBuf::from(gltf_opts.output_path);
     gltf.write_to_files(gltf_path)?;
@@ -276,11 +253,21 @@ fn process_gltf(gltf_opts: GltfOpts) -> anyhow::Result<()> {
 }
 
 pub fn process_lof(lof_opts: LofOpts) -> anyhow::Result<()> {
+    let codepage = lof_opts.codepage;
+
     match lof_opts.cmd {
-        Command::Info(info_opts) => process_info(info_opts),
-        Command::Unpack(unpack_opts) => process_unpack(unpack_opts),
-        Command::Pack(pack_opts) => process_pack(pack_opts),
-        Command::Obj(obj_opts) => process_obj(obj_opts),
-        Command::Gltf(gltf_opts) => process_gltf(gltf_opts),
+        Command::Info(info_opts) => {
+            process_info(info_opts, codepage.unwrap_or(lof::Lof::DEFAULT_CODEPAGE))
+        }
+        Command::Unpack(unpack_opts) => {
+            process_unpack(unpack_opts, codepage.unwrap_or(lof::Lof::DEFAULT_CODEPAGE))
+        }
+        Command::Pack(pack_opts) => process_pack(pack_opts, codepage),
+        Command::Obj(obj_opts) => {
+            process_obj(obj_opts, codepage.unwrap_or(lof::Lof::DEFAULT_CODEPAGE))
+        }
+        Command::Gltf(gltf_opts) => {
+            process_gltf(gltf_opts, codepage.unwrap_or(lof::Lof::DEFAULT_CODEPAGE))
+        }
     }
 }
@@ -1,7 +1,7 @@
 use std::{collections::HashSet, fs::File, io::BufWriter, path::Path};
 
 use clap::{Parser, Subcommand};
-use slidetown::parsers::loi;
+use slidetown::parsers::{lof::Lof, loi, Codepage};
 
 #[derive(Parser)]
 pub struct LoiOpts {
@@ -22,6 +22,9 @@ enum Command {
 
     /// export preview gltf with instanced objects
     Gltf(GltfOpts),
+
+    /// export collider boxes and capsules to obj or gltf
+    Colliders(CollidersOpts),
 }
 
 #[derive(Parser)]
@@ -220,6 +223,10 @@ struct GltfOpts {
     #[arg(short, long)]
     lof_path: String,
 
+    /// codepage of model names in the LOF, e.g. big5 or gbk; defaults to euc-kr
+    #[arg(long)]
+    codepage: Option<Codepage>,
+
     /// output file
     #[arg(short, long)]
     output_path: String,
@@ -233,8 +240,12 @@ fn process_gltf(gltf_opts: GltfOpts) -> anyhow::Result<()> {
     let mut file = File::open(&gltf_opts.loi_path)?;
     let loi: loi::Loi = loi::Loi::read(&mut file, gltf_opts.total_block_count)?;
 
-    let (mut gltf, model_indices) =
-        crate::lof::process_gltf_inner(&gltf_opts.lof_path, None).expect("failed to process lof");
+    let (mut gltf, model_indices) = crate::lof::process_gltf_inner(
+        &gltf_opts.lof_path,
+        None,
+        gltf_opts.codepage.unwrap_or(Lof::DEFAULT_CODEPAGE),
+    )
+    .expect("failed to process lof");
 
     let mut instance_indices = Vec::new();
 
@@ -274,11 +285,39 @@ fn process_gltf(gltf_opts: GltfOpts) -> anyhow::Result<()> {
     Ok(())
 }
 
+#[derive(Parser)]
+struct CollidersOpts {
+    /// input file
+    #[arg(short, long)]
+    input_path: String,
+
+    /// output file, .obj or .gltf
+    #[arg(short, long)]
+    output_path: String,
+
+    /// total block count, as specified by the LF
+    #[arg(short, long)]
+    total_block_count: usize,
+}
+
+fn process_colliders(colliders_opts: CollidersOpts) -> anyhow::Result<()> {
+    let mut file = File::open(&colliders_opts.input_path)?;
+    let loi: loi::Loi = loi::Loi::read(&mut file, colliders_opts.total_block_count)?;
+
+    println!("Exporting {} colliders", loi.colliders.len());
+
+    crate::util::colliders::write_colliders(
+        Path::new(&colliders_opts.output_path),
+        &[("Colliders".to_string(), &loi.colliders)],
+    )
+}
+
 pub fn process_loi(loi_opts: LoiOpts) -> anyhow::Result<()> {
     match loi_opts.cmd {
         Command::Info(info_opts) => process_info(info_opts),
         Command::Unpack(unpack_opts) => process_unpack(unpack_opts),
         Command::Pack(pack_opts) => process_pack(pack_opts),
         Command::Gltf(gltf_opts) => process_gltf(gltf_opts),
+        Command::Colliders(colliders_opts) => process_colliders(colliders_opts),
     }
 }
@@ -3,6 +3,7 @@ use std::time::Instant;
 use clap::{Parser, Subcommand};
 
 mod agt;
+mod hit;
 mod lbf;
 mod levelmodifier;
 mod lf;
@@ -45,6 +46,9 @@ enum Archive {
     /// LOI object list
     Loi(loi::LoiOpts),
 
+    /// HIT collision areas
+    Hit(hit::HitOpts),
+
     /// World/city
     World(world::WorldOpts),
 
@@ -66,6 +70,7 @@ fn main() -> anyhow::Result<()> {
         Archive::Lgf(lgf_opts) => lgf::process_lgf(lgf_opts),
         Archive::Lof(lof_opts) => lof::process_lof(lof_opts),
         Archive::Loi(loi_opts) => loi::process_loi(loi_opts),
+        Archive::Hit(hit_opts) => hit::process_hit(hit_opts),
         Archive::World(world_opts) => world::process_world(world_opts),
         Archive::LevelModifier(levelmodifier_opts) => {
             levelmodifier::process_levelmodifier(levelmodifier_opts)
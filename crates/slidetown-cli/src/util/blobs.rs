@@ -0,0 +1,233 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Cursor, Read, Seek, Write},
+    path::Path,
+};
+
+use clap::Parser;
+use serde::{de::DeserializeOwned, Serialize};
+use slidetown::parsers::{EmbeddedBlob, EmbeddedBlobArchive};
+
+use super::{fs::entry_path_to_relative, nif_obj};
+
+/// File name of an unpacked blob whose key is not a file name itself
+pub fn nif_file_name(blob: &EmbeddedBlob) -> String {
+    format!("{}.nif", blob.key)
+}
+
+/// Write every blob of an archive into a directory, named by `file_name`
+pub fn unpack_blobs<A, R>(
+    archive: &A,
+    reader: &mut R,
+    out_dir_path: &Path,
+    file_name: impl Fn(&EmbeddedBlob) -> String,
+) -> anyhow::Result<()>
+where
+    A: EmbeddedBlobArchive,
+    R: Read + Seek,
+{
+    for blob in archive.blobs() {
+        let file_name = file_name(&blob);
+        println!("Writing {}", file_name);
+
+        // Keys can come from the file itself, e.g. LOF model file names
+        let file_path = out_dir_path.join(entry_path_to_relative(&file_name)?);
+
+        let data = blob.read(reader)?;
+        if let Some(dir_path) = file_path.parent() {
+            std::fs::create_dir_all(dir_path)?;
+        }
+        std::fs::write(file_path, data)?;
+    }
+
+    Ok(())
+}
+
+/// Write an archive with the blobs read from the directory of `input_path`, named by `file_name`
+pub fn pack_blobs<A>(
+    archive: &mut A,
+    input_path: &Path,
+    output_path: &Path,
+    file_name: impl Fn(&EmbeddedBlob) -> String,
+) -> anyhow::Result<()>
+where
+    A: EmbeddedBlobArchive,
+{
+    let in_dir_path = input_path.with_file_name("");
+    let blobs = archive.blobs();
+
+    let mut out_file = BufWriter::new(File::create(output_path)?);
+    archive.write_with_blobs(&mut out_file, |index, out_file| {
+        let blob_file_path = in_dir_path.join(entry_path_to_relative(&file_name(&blobs[index]))?);
+        let mut blob_file = File::open(&blob_file_path)
+            .map_err(|e| anyhow::anyhow!("could not open {}: {}", blob_file_path.display(), e))?;
+        std::io::copy(&mut blob_file, out_file)?;
+        Ok(())
+    })?;
+    out_file.flush()?;
+
+    Ok(())
+}
+
+#[derive(Parser)]
+pub struct InfoOpts {
+    /// input file
+    #[arg(short, long)]
+    input_path: String,
+}
+
+#[derive(Parser)]
+pub struct ObjOpts {
+    /// input file
+    #[arg(short, long)]
+    input_path: String,
+
+    /// output file
+    #[arg(short, long)]
+    output_path: String,
+}
+
+#[derive(Parser)]
+pub struct GltfOpts {
+    /// input file
+    #[arg(short, long)]
+    input_path: String,
+
+    /// output file
+    #[arg(short, long)]
+    output_path: String,
+}
+
+#[derive(Parser)]
+pub struct UnpackOpts {
+    /// input file
+    #[arg(short, long)]
+    input_path: String,
+
+    /// output directory
+    #[arg(short, long)]
+    output_path: String,
+}
+
+#[derive(Parser)]
+pub struct PackOpts {
+    /// input manifest
+    #[arg(short, long)]
+    input_path: String,
+
+    /// output file
+    #[arg(short, long)]
+    output_path: String,
+}
+
+/// Commands shared by archives holding one NIF per terrain block, whose blobs are
+/// keyed by block index
+pub struct BlockNifArchive<A> {
+    pub read: fn(&mut File) -> anyhow::Result<A>,
+    /// Suffix of exported object names, e.g. `Guardrail` for `Block12Guardrail`
+    pub object_name: &'static str,
+    /// Name of the exported glTF node group
+    pub group_name: &'static str,
+}
+
+impl<A> BlockNifArchive<A>
+where
+    A: EmbeddedBlobArchive + Serialize + DeserializeOwned,
+{
+    /// Parse the NIF of every block, skipping the ones that fail to parse
+    fn visit_nifs(
+        &self,
+        input_path: &str,
+        mut visit: impl FnMut(&nif::Nif, String),
+    ) -> anyhow::Result<()> {
+        let mut file = File::open(input_path)?;
+        let archive = (self.read)(&mut file)?;
+
+        for blob in archive.blobs() {
+            let nif_buf = blob.read(&mut file)?;
+
+            let mut nif_cursor = Cursor::new(nif_buf);
+
+            let nif = match nif::Nif::parse(&mut nif_cursor) {
+                Ok(nif) => nif,
+                Err(e) => {
+                    println!("Failed to parse NIF for block index {}: {:?}", blob.key, e);
+                    continue;
+                }
+            };
+
+            visit(&nif, format!("Block{}{}", blob.key, self.object_name));
+        }
+
+        Ok(())
+    }
+
+    pub fn process_info(&self, info_opts: InfoOpts) -> anyhow::Result<()> {
+        let mut file = File::open(info_opts.input_path)?;
+        let archive = (self.read)(&mut file)?;
+
+        println!("Block count: {}", archive.blobs().len());
+
+        Ok(())
+    }
+
+    pub fn process_obj(&self, obj_opts: ObjOpts) -> anyhow::Result<()> {
+        let mut obj = nif_obj::Obj::default();
+
+        self.visit_nifs(&obj_opts.input_path, |nif, name| {
+            obj.visit_nif(nif, Some(name))
+        })?;
+
+        let obj_path = std::path::PathBuf::from(obj_opts.output_path);
+        let mtl_path = obj_path.with_extension("mtl");
+
+        obj.write_to_files(obj_path, mtl_path)?;
+
+        Ok(())
+    }
+
+    pub fn process_gltf(&self, gltf_opts: GltfOpts) -> anyhow::Result<()> {
+        let mut gltf = nif::collectors::gltf::Gltf::new();
+
+        self.visit_nifs(&gltf_opts.input_path, |nif, name| {
+            gltf.visit_nif(nif, Some(self.group_name), &name);
+        })?;
+
+        let gltf_path = std::path::PathBuf::from(gltf_opts.output_path);
+        gltf.write_to_files(gltf_path)?;
+
+        Ok(())
+    }
+
+    pub fn process_unpack(&self, unpack_opts: UnpackOpts) -> anyhow::Result<()> {
+        let mut file = File::open(&unpack_opts.input_path)?;
+
+        let archive = (self.read)(&mut file)?;
+
+        let out_dir_path = Path::new(&unpack_opts.output_path);
+        std::fs::create_dir_all(out_dir_path)?;
+
+        {
+            let manifest_file = File::create(out_dir_path.join("manifest.json"))?;
+            serde_json::to_writer_pretty(manifest_file, &archive)?;
+        }
+
+        unpack_blobs(&archive, &mut file, out_dir_path, nif_file_name)
+    }
+
+    pub fn process_pack(&self, pack_opts: PackOpts) -> anyhow::Result<()> {
+        let input_path = Path::new(&pack_opts.input_path);
+
+        let mut archive: A = {
+            let manifest_file = File::open(input_path)?;
+            serde_json::from_reader(manifest_file)?
+        };
+
+        pack_blobs(
+            &mut archive,
+            input_path,
+            Path::new(&pack_opts.output_path),
+            nif_file_name,
+        )
+    }
+}
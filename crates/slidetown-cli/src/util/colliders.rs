@@ -0,0 +1,153 @@
+use std::path::Path;
+
+use nif::{
+    collectors::gltf::json,
+    common::{Color3, Triangle},
+    glam::{Mat3, Quat, Vec3},
+};
+use slidetown::{
+    colliders::{collider_name, ColliderExtras, ColliderMesh},
+    parsers::loi::Collider,
+};
+
+use super::{
+    mesh_gltf::MeshGltf,
+    nif_obj::{Obj, ObjMaterial, ObjMesh},
+};
+
+pub const MATERIAL_NAME: &str = "Collider";
+/// Translucent red, so colliders stand out against the visual models
+pub const COLOR: [f32; 4] = [1.0, 0.0, 0.0, 0.5];
+
+/// Rotation of a collider as a quaternion, or none if its matrix also scales, shears or mirrors
+fn collider_quat(collider: &Collider) -> Option<Quat> {
+    let (x, y, z) = collider.rotation;
+    let rotation =
+        Mat3::from_cols_array(&[x.0, x.1, x.2, y.0, y.1, y.2, z.0, z.1, z.2]).transpose();
+
+    let is_orthonormal = (rotation * rotation.transpose()).abs_diff_eq(Mat3::IDENTITY, 1e-4);
+    (is_orthonormal && rotation.determinant() > 0.0).then(|| Quat::from_mat3(&rotation))
+}
+
+fn triangles(mesh: &ColliderMesh) -> impl Iterator<Item = Triangle> + '_ {
+    mesh.triangles.iter().map(|&[a, b, c]| Triangle { a, b, c })
+}
+
+/// Add a group for every collider of a known type, named with `prefix` and the collider's indices
+pub fn add_colliders_to_obj(obj: &mut Obj, colliders: &[Collider], prefix: &str) {
+    obj.materials
+        .entry(MATERIAL_NAME.to_string())
+        .or_insert_with(|| ObjMaterial {
+            diffuse_color: Color3 {
+                r: COLOR[0],
+                g: COLOR[1],
+                b: COLOR[2],
+            },
+            alpha: COLOR[3],
+            ..Default::default()
+        });
+
+    for collider in colliders {
+        let Some(mesh) = ColliderMesh::new(collider) else {
+            continue;
+        };
+
+        // OBJ has no transforms, so vertices are placed in the world directly
+        obj.meshes.push(ObjMesh {
+            name: format!("{}{}", prefix, collider_name(collider)),
+            vertices: mesh
+                .world_vertices(collider)
+                .into_iter()
+                .map(Vec3::from)
+                .collect(),
+            normals: None,
+            uvs: None,
+            triangles: Some(triangles(&mesh).collect()),
+            material_name: Some(MATERIAL_NAME.to_string()),
+        });
+    }
+}
+
+/// Add a scene with a node for every collider of a known type, carrying its
+/// `object_index`, `collider_index` and `type` as extras, and return the nodes
+pub fn add_colliders_to_gltf(
+    gltf: &mut MeshGltf,
+    material: json::Index<json::Material>,
+    scene_name: &str,
+    colliders: &[Collider],
+) -> anyhow::Result<Vec<json::Index<json::Node>>> {
+    let mut nodes = Vec::new();
+
+    for collider in colliders {
+        let Some(mesh) = ColliderMesh::new(collider) else {
+            continue;
+        };
+
+        let name = collider_name(collider);
+
+        // A node transform can't shear, so colliders whose matrix isn't a plain rotation
+        // are placed in the world directly like in OBJ
+        let (vertices, rotation, translation) = match collider_quat(collider) {
+            Some(quat) => (
+                mesh.vertices.clone(),
+                Some(json::scene::UnitQuaternion(quat.to_array())),
+                Some(collider.position.into()),
+            ),
+            None => (mesh.world_vertices(collider), None, None),
+        };
+        let vertices = vertices.into_iter().map(Vec3::from).collect::<Vec<_>>();
+        let indices = mesh
+            .triangles
+            .iter()
+            .flatten()
+            .map(|&index| u32::from(index))
+            .collect::<Vec<_>>();
+        let mesh_index = gltf.add_mesh(&name, &vertices, &indices, Some(material));
+
+        let extras = serde_json::value::to_raw_value(&ColliderExtras::from(collider))?;
+
+        nodes.push(gltf.add_node(json::Node {
+            extras: Some(extras),
+            mesh: Some(mesh_index),
+            name: Some(name),
+            rotation,
+            translation,
+            ..Default::default()
+        }));
+    }
+
+    gltf.add_scene(scene_name, nodes.clone());
+
+    Ok(nodes)
+}
+
+/// Write colliders grouped by name to an OBJ or glTF file, depending on its extension
+pub fn write_colliders(
+    output_path: &Path,
+    collider_groups: &[(String, &[Collider])],
+) -> anyhow::Result<()> {
+    let extension = output_path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_lowercase);
+
+    match extension.as_deref() {
+        Some("obj") => {
+            let mut obj = Obj::default();
+            for (name, colliders) in collider_groups {
+                // Spaces would split OBJ group names
+                add_colliders_to_obj(&mut obj, colliders, &format!("{}_", name.replace(' ', "_")));
+            }
+            obj.write_to_files(output_path.to_owned(), output_path.with_extension("mtl"))
+        }
+        Some("gltf") => {
+            let mut gltf = MeshGltf::new();
+            let material = gltf.add_material(MATERIAL_NAME, COLOR);
+            for (name, colliders) in collider_groups {
+                add_colliders_to_gltf(&mut gltf, material, name, colliders)?;
+            }
+            gltf.write_to_files(output_path)
+        }
+        _ => anyhow::bail!("output must be an .obj or .gltf file"),
+    }
+}
@@ -1,6 +1,6 @@
 use std::{
     fs::{read_dir, DirEntry},
-    path::Path,
+    path::{Component, Path, PathBuf},
 };
 
 pub fn visit_files(dir: &Path, mut cb: &mut dyn FnMut(DirEntry)) -> anyhow::Result<()> {
@@ -33,3 +33,22 @@ pub fn visit_dirs(dir: &Path, mut cb: &mut dyn FnMut(DirEntry)) -> anyhow::Resul
     }
     Ok(())
 }
+
+/// Turn a backslash-separated path stored in an archive into a path relative to the output
+/// directory, refusing anything that would end up outside of it
+pub fn entry_path_to_relative(entry_path: &str) -> anyhow::Result<PathBuf> {
+    let relative: PathBuf = entry_path
+        .split(['\\', '/'])
+        .filter(|part| !part.is_empty())
+        .collect();
+
+    if relative.as_os_str().is_empty()
+        || !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    {
+        anyhow::bail!("unsafe path {:?}", entry_path);
+    }
+
+    Ok(relative)
+}
@@ -0,0 +1,50 @@
+/// Match an archive entry path against a glob pattern.
+///
+/// Matching ignores case and treats `/` and `\` alike. `*` matches within one path
+/// component, `**` across components and `?` any single character but a separator.
+/// Patterns without a separator are matched against the file name only, so `*.xlt`
+/// finds XLT files in every folder.
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    let normalize = |s: &str| -> Vec<char> {
+        s.chars()
+            .map(|c| if c == '/' { '\\' } else { c })
+            .flat_map(char::to_lowercase)
+            .collect()
+    };
+
+    let pattern = normalize(pattern);
+    let path = normalize(path);
+
+    if pattern.contains(&'\\') {
+        match_from(&pattern, &path)
+    } else {
+        let file_name_start = path
+            .iter()
+            .rposition(|&c| c == '\\')
+            .map_or(0, |index| index + 1);
+        match_from(&pattern, &path[file_name_start..])
+    }
+}
+
+fn match_from(pattern: &[char], path: &[char]) -> bool {
+    match pattern {
+        [] => path.is_empty(),
+        ['*', '*', rest @ ..] => {
+            // `**\` may also match no components at all
+            let rest_after_separator = match rest {
+                ['\\', after @ ..] => Some(after),
+                _ => None,
+            };
+            (0..=path.len()).any(|skip| {
+                match_from(rest, &path[skip..])
+                    || rest_after_separator.is_some_and(|after| match_from(after, &path[skip..]))
+            })
+        }
+        ['*', rest @ ..] => {
+            let component_len = path.iter().position(|&c| c == '\\').unwrap_or(path.len());
+            (0..=component_len).any(|skip| match_from(rest, &path[skip..]))
+        }
+        ['?', rest @ ..] => matches!(path, [c, ..] if *c != '\\') && match_from(rest, &path[1..]),
+        [c, rest @ ..] => path.first() == Some(c) && match_from(rest, &path[1..]),
+    }
+}
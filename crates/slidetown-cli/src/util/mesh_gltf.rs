@@ -0,0 +1,221 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use nif::{
+    collectors::gltf::json::{self, validation::Checked::Valid},
+    glam::Vec3,
+};
+
+/// glTF of plain triangle meshes that don't come from NIFs, e.g. colliders or collision areas.
+///
+/// All vertex and index data goes into a single buffer file next to the glTF.
+#[derive(Default)]
+pub struct MeshGltf {
+    root: json::Root,
+    buffer: Vec<u8>,
+    /// Index the buffer gets when it's added to the root on writing
+    buffer_index: u32,
+}
+
+impl MeshGltf {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add meshes to an existing glTF, e.g. one written by the NIF collector, whose own
+    /// buffers are kept as they are
+    pub fn from_root(root: json::Root) -> Self {
+        Self {
+            buffer_index: root.buffers.len() as u32,
+            root,
+            buffer: Vec::new(),
+        }
+    }
+
+    pub fn root_mut(&mut self) -> &mut json::Root {
+        &mut self.root
+    }
+
+    /// Add an unlit-looking double-sided material, blended if `color` is translucent
+    pub fn add_material(&mut self, name: &str, color: [f32; 4]) -> json::Index<json::Material> {
+        let alpha_mode = if color[3] < 1.0 {
+            json::material::AlphaMode::Blend
+        } else {
+            json::material::AlphaMode::Opaque
+        };
+
+        self.root.materials.push(json::Material {
+            alpha_mode: Valid(alpha_mode),
+            double_sided: true,
+            name: Some(name.into()),
+            pbr_metallic_roughness: json::material::PbrMetallicRoughness {
+                base_color_factor: json::material::PbrBaseColorFactor(color),
+                metallic_factor: json::material::StrengthFactor(0.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        json::Index::new(self.root.materials.len() as u32 - 1)
+    }
+
+    pub fn add_mesh(
+        &mut self,
+        name: &str,
+        vertices: &[Vec3],
+        indices: &[u32],
+        material: Option<json::Index<json::Material>>,
+    ) -> json::Index<json::Mesh> {
+        let vertex_data = vertices
+            .iter()
+            .flat_map(|vertex| vertex.to_array())
+            .flat_map(f32::to_le_bytes)
+            .collect::<Vec<_>>();
+        let vertices_view = self.push_buffer_view(&vertex_data, json::buffer::Target::ArrayBuffer);
+        let (min, max) = vertices.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), &vertex| (min.min(vertex), max.max(vertex)),
+        );
+        let positions = self.push_accessor(
+            vertices_view,
+            vertices.len(),
+            json::accessor::ComponentType::F32,
+            json::accessor::Type::Vec3,
+            Some((min, max)),
+        );
+
+        let index_data = indices
+            .iter()
+            .flat_map(|index| index.to_le_bytes())
+            .collect::<Vec<_>>();
+        let indices_view =
+            self.push_buffer_view(&index_data, json::buffer::Target::ElementArrayBuffer);
+        let indices = self.push_accessor(
+            indices_view,
+            indices.len(),
+            json::accessor::ComponentType::U32,
+            json::accessor::Type::Scalar,
+            None,
+        );
+
+        self.root.meshes.push(json::Mesh {
+            extensions: None,
+            extras: Default::default(),
+            name: Some(name.into()),
+            primitives: vec![json::mesh::Primitive {
+                attributes: BTreeMap::from([(Valid(json::mesh::Semantic::Positions), positions)]),
+                extensions: None,
+                extras: Default::default(),
+                indices: Some(indices),
+                material,
+                mode: Valid(json::mesh::Mode::Triangles),
+                targets: None,
+            }],
+            weights: None,
+        });
+
+        json::Index::new(self.root.meshes.len() as u32 - 1)
+    }
+
+    pub fn add_node(&mut self, node: json::Node) -> json::Index<json::Node> {
+        self.root.nodes.push(node);
+        json::Index::new(self.root.nodes.len() as u32 - 1)
+    }
+
+    /// Add a scene, the first one added becomes the default
+    pub fn add_scene(&mut self, name: &str, nodes: Vec<json::Index<json::Node>>) {
+        self.root.scenes.push(json::Scene {
+            extensions: None,
+            extras: Default::default(),
+            name: Some(name.into()),
+            nodes,
+        });
+        if self.root.scene.is_none() {
+            self.root.scene = Some(json::Index::new(0));
+        }
+    }
+
+    fn push_buffer_view(
+        &mut self,
+        data: &[u8],
+        target: json::buffer::Target,
+    ) -> json::Index<json::buffer::View> {
+        // Accessors need their data aligned to the component size
+        while !self.buffer.len().is_multiple_of(4) {
+            self.buffer.push(0);
+        }
+
+        self.root.buffer_views.push(json::buffer::View {
+            buffer: json::Index::new(self.buffer_index),
+            byte_length: data.len().into(),
+            byte_offset: Some(self.buffer.len().into()),
+            byte_stride: None,
+            name: None,
+            target: Some(Valid(target)),
+            extensions: Default::default(),
+            extras: Default::default(),
+        });
+        self.buffer.extend_from_slice(data);
+
+        json::Index::new(self.root.buffer_views.len() as u32 - 1)
+    }
+
+    fn push_accessor(
+        &mut self,
+        buffer_view: json::Index<json::buffer::View>,
+        count: usize,
+        component_type: json::accessor::ComponentType,
+        type_: json::accessor::Type,
+        min_max: Option<(Vec3, Vec3)>,
+    ) -> json::Index<json::Accessor> {
+        self.root.accessors.push(json::Accessor {
+            buffer_view: Some(buffer_view),
+            byte_offset: Some(json::validation::USize64(0)),
+            count: count.into(),
+            component_type: Valid(json::accessor::GenericComponentType(component_type)),
+            extensions: None,
+            extras: Default::default(),
+            type_: Valid(type_),
+            min: min_max.map(|(min, _)| json::Value::from(min.to_array().to_vec())),
+            max: min_max.map(|(_, max)| json::Value::from(max.to_array().to_vec())),
+            name: None,
+            normalized: false,
+            sparse: None,
+        });
+
+        json::Index::new(self.root.accessors.len() as u32 - 1)
+    }
+
+    pub fn write_to_files(mut self, gltf_path: &Path) -> anyhow::Result<()> {
+        let gltf_file_name = gltf_path
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .ok_or_else(|| anyhow::anyhow!("invalid gltf file name"))?;
+        let buffer_file_name = format!("{}.bin", gltf_file_name);
+
+        // Buffers can't be empty
+        if !self.buffer.is_empty() {
+            self.root.buffers.push(json::Buffer {
+                byte_length: self.buffer.len().into(),
+                name: None,
+                uri: Some(buffer_file_name.clone()),
+                extensions: Default::default(),
+                extras: Default::default(),
+            });
+
+            let mut buffer_writer =
+                BufWriter::new(File::create(gltf_path.with_file_name(buffer_file_name))?);
+            buffer_writer.write_all(&self.buffer)?;
+            buffer_writer.flush()?;
+        }
+
+        let gltf_writer = BufWriter::new(File::create(gltf_path)?);
+        json::serialize::to_writer_pretty(gltf_writer, &self.root)?;
+
+        Ok(())
+    }
+}
@@ -1,2 +1,6 @@
+pub mod blobs;
+pub mod colliders;
 pub mod fs;
+pub mod glob;
+pub mod mesh_gltf;
 pub mod nif_obj;
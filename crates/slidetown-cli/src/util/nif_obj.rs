@@ -387,9 +387,10 @@ impl Obj {
         nif: &Nif,
         texturing_property: &NiTexturingProperty,
     ) -> Option<String> {
-        if texturing_property.has_base_texture && texturing_property.base_texture.is_some() {
-            let diffuse_tex_desc = texturing_property.base_texture.as_ref().unwrap();
-
+        if let (true, Some(diffuse_tex_desc)) = (
+            texturing_property.has_base_texture,
+            texturing_property.base_texture.as_ref(),
+        ) {
             let diffuse_source = diffuse_tex_desc
                 .source_ref
                 .get(&nif.blocks)
@@ -1,15 +1,27 @@
 use std::{
+    collections::HashMap,
     fs::File,
-    io::{Cursor, Read, Seek, SeekFrom, Write},
-    path::Path,
+    io::{BufReader, Cursor},
+    path::{Path, PathBuf},
 };
 
 use clap::{Parser, Subcommand};
+use nif::collectors::gltf::{json, Gltf};
+use slidetown::{
+    parsers::{lof::Lof, loi::Collider, Codepage, EmbeddedBlobArchive},
+    world::{World, WorldFile},
+};
+
+use crate::util::{colliders, mesh_gltf::MeshGltf};
 
 #[derive(Parser)]
 pub struct WorldOpts {
     #[command(subcommand)]
     cmd: Command,
+
+    /// codepage of model names, e.g. big5 or gbk; defaults to euc-kr
+    #[arg(long, global = true)]
+    codepage: Option<Codepage>,
 }
 
 #[derive(Subcommand)]
@@ -18,6 +30,12 @@ enum Command {
     Info(InfoOpts),
     #[command(about = "print object density map for world")]
     Map(InfoOpts),
+    #[command(about = "export gltf with terrain, block objects, decals, guardrails and objects")]
+    Gltf(GltfOpts),
+    #[command(
+        about = "export colliders of every layer to obj or gltf, one group or scene per layer"
+    )]
+    Colliders(CollidersOpts),
 }
 
 #[derive(Parser)]
@@ -27,36 +45,27 @@ struct InfoOpts {
     input_path: String,
 }
 
-fn try_parse_nifs<I, T>(file: &mut File, named_offsets: I) -> anyhow::Result<()>
-where
-    I: Iterator<Item = (T, u32, u32)>,
-    T: std::fmt::Debug,
-{
+fn open_world(info_opts: &InfoOpts, codepage: Option<Codepage>) -> anyhow::Result<World> {
+    World::open_with_codepage(
+        &info_opts.input_path,
+        codepage.unwrap_or(Lof::DEFAULT_CODEPAGE),
+    )
+}
+
+fn try_parse_nifs<A: EmbeddedBlobArchive>(archive: &WorldFile<A>) -> anyhow::Result<()> {
+    let mut file = BufReader::new(File::open(&archive.path)?);
     let mut nif_errors = Vec::new();
     let mut count = 0;
 
-    for (idx, pos, len) in named_offsets {
+    for blob in archive.blobs().into_iter().filter(|blob| blob.length > 0) {
         count += 1;
 
-        file.seek(SeekFrom::Start(pos as u64))?;
-        let mut buf = vec![0u8; len as usize];
-        file.read_exact(&mut buf)?;
-
-        let mut nif_cursor = Cursor::new(buf);
+        let buf = blob.read(&mut file)?;
+        let mut nif_cursor = Cursor::new(&buf);
 
         if let Err(e) = nif::Nif::parse(&mut nif_cursor) {
-            println!("Failed to parse nif id {:?} - {:#?}", idx, e);
+            println!("Failed to parse nif {} - {:#?}", blob.key, e);
             nif_errors.push(e);
-
-            // dump for debug
-            file.seek(SeekFrom::Start(pos as u64))
-                .expect("failed to seek for dump");
-            let mut buf = vec![0u8; len as usize];
-            file.read_exact(&mut buf).expect("failed to read for dump");
-            let mut dump_file =
-                std::fs::File::create("dump.nif").expect("failed to create file for dump");
-            dump_file.write_all(&buf).expect("failed to write for dump");
-            panic!("dumped file");
         }
     }
 
@@ -72,12 +81,10 @@ where
     Ok(())
 }
 
-fn process_info(info_opts: InfoOpts) -> anyhow::Result<()> {
-    let input_path = Path::new(&info_opts.input_path);
-
-    let mut lf_file = File::open(input_path.join("terrain0.lf"))?;
-    let lf = slidetown::parsers::lf::Lf::read_without_data(&mut lf_file)?;
+fn process_info(info_opts: InfoOpts, codepage: Option<Codepage>) -> anyhow::Result<()> {
+    let world = open_world(&info_opts, codepage)?;
 
+    let lf = world.lf();
     println!(
         "[lf] Terrain dimensions: {:?}",
         (lf.size_x, lf.size_y, lf.size_idx)
@@ -86,84 +93,97 @@ fn process_info(info_opts: InfoOpts) -> anyhow::Result<()> {
     println!("[lf] Blocks in terrain: {}", lf.blocks.len());
 
     println!("[lf] Parsing nifs..");
-    try_parse_nifs(
-        &mut lf_file,
-        lf.blocks
-            .iter()
-            .filter(|b| b.file_length > 0)
-            .map(|b| (b.index, b.file_offset, b.file_length)),
-    )?;
-
-    let mut lbf_file = File::open(input_path.join("blockObj0.lbf"))?;
-    let lbf = slidetown::parsers::lbf::Lbf::parse(&mut lbf_file)?;
+    try_parse_nifs(lf)?;
+
+    if let Some(lbf) = world.lbf() {
+        println!(
+            "[lbf] Blocks in blockObj header: {}",
+            lbf.header.block_count
+        );
+        println!("[lbf] Blocks in blockObj: {}", lbf.blocks.len());
+        println!(
+            "[lbf] Block objects in blockObj header: {}",
+            lbf.header.block_object_count
+        );
+        println!(
+            "[lbf] Block objects in blockObj: {}",
+            lbf.blocks.iter().map(|b| b.objects.len()).sum::<usize>()
+        );
+
+        println!("[lbf] Parsing nifs..");
+        try_parse_nifs(lbf)?;
+    }
 
-    println!(
-        "[lbf] Blocks in blockObj header: {}",
-        lbf.header.block_count
-    );
-    println!("[lbf] Blocks in blockObj: {}", lbf.blocks.len());
-    println!(
-        "[lbf] Block objects in blockObj header: {}",
-        lbf.header.block_object_count
-    );
-    println!(
-        "[lbf] Block objects in blockObj: {}",
-        lbf.blocks
-            .iter()
-            .map(|b: &slidetown::parsers::lbf::Block| b.object_count)
-            .sum::<u32>()
-    );
+    if let Some(lgf) = world.lgf() {
+        println!("[lgf] Blocks with guardrails: {}", lgf.blocks.len());
+    }
 
-    println!("[lbf] Parsing nifs..");
-    try_parse_nifs(
-        &mut lbf_file,
-        lbf.blocks.iter().flat_map(|b| {
-            b.objects
-                .iter()
-                .map(|bo| (bo.unk, bo.file_offset, bo.file_length))
-        }),
-    )?;
+    if let Some(llf) = world.llf() {
+        println!("[llf] Blocks with lane decals: {}", llf.blocks.len());
+    }
 
-    let mut lof_file = File::open(input_path.join("modeltable0.lof"))?;
-    let lof = slidetown::parsers::lof::Lof::read_without_data(&mut lof_file)?;
+    if let Some(lof) = world.lof() {
+        println!("[lof] Models in table: {}", lof.models.len());
 
-    println!("[lof] Models in table: {}", lof.models.len());
+        println!("[lof] Parsing nifs..");
+        try_parse_nifs(lof)?;
+    }
 
-    println!("[lof] Parsing nifs..");
-    try_parse_nifs(
-        &mut lof_file,
-        lof.models
-            .iter()
-            .map(|m| (&m.file_name, m.file_offset, m.file_length)),
-    )?;
+    for layer in world.layers() {
+        if let Some(loi) = layer.loi() {
+            println!(
+                "[{}/loi] Blocks in object index: {}",
+                layer.name(),
+                loi.blocks.len()
+            );
+            println!(
+                "[{}/loi] Blocks with 1 or more objects: {}",
+                layer.name(),
+                loi.blocks.iter().filter(|b| !b.objects.is_empty()).count()
+            );
+            println!(
+                "[{}/loi] Objects in all blocks combined: {}",
+                layer.name(),
+                loi.blocks.iter().map(|b| b.objects.len()).sum::<usize>()
+            );
+        }
+        if let Some(lif) = layer.lif() {
+            println!("[{}/lif] Blocks: {}", layer.name(), lif.blocks.len());
+        }
+        if let Some(hit) = layer.hit() {
+            println!(
+                "[{}/hit] Triangles: {}",
+                layer.name(),
+                hit.indices.len() / 3
+            );
+        }
+    }
 
-    let mut loi_file = File::open(input_path.join("Main\\object0.loI"))?;
-    let loi = slidetown::parsers::loi::Loi::read(&mut loi_file, lf.block_count as _)?;
+    for chpath in world.chpaths() {
+        println!(
+            "[chpath] {}: {} paths",
+            chpath.path.display(),
+            chpath.paths.len()
+        );
+    }
 
-    println!("[loi] Blocks in object index: {}", loi.blocks.len());
-    println!(
-        "[loi] Blocks with 1 or more objects: {}",
-        loi.blocks.iter().filter(|b| !b.objects.is_empty()).count()
-    );
-    println!(
-        "[loi] Objects in all blocks combined: {}",
-        loi.blocks.iter().map(|b| b.objects.len()).sum::<usize>()
-    );
+    let problems = world.check();
+    if problems.is_empty() {
+        println!("[world] No inconsistencies found");
+    }
+    for problem in problems {
+        println!("[world] {}", problem);
+    }
 
     Ok(())
 }
 
-fn process_map(info_opts: InfoOpts) -> anyhow::Result<()> {
-    let input_path = Path::new(&info_opts.input_path);
+fn process_map(info_opts: InfoOpts, codepage: Option<Codepage>) -> anyhow::Result<()> {
+    let world = open_world(&info_opts, codepage)?;
 
-    let lf = {
-        let mut file = File::open(input_path.join("terrain0.lf"))?;
-        slidetown::parsers::lf::Lf::read_without_data(&mut file)?
-    };
-
-    let loi = {
-        let mut file = File::open(input_path.join("Main\\object0.loI"))?;
-        slidetown::parsers::loi::Loi::read(&mut file, lf.block_count as _)?
+    let lf = world.lf();
+    let Some(loi) = world.main_layer().and_then(|layer| layer.loi()) else {
+        anyhow::bail!("world has no Main object list");
     };
 
     println!("Object count by block:");
@@ -206,9 +226,306 @@ fn process_map(info_opts: InfoOpts) -> anyhow::Result<()> {
     Ok(())
 }
 
+#[derive(Parser)]
+struct GltfOpts {
+    /// input directory
+    #[arg(short, long)]
+    input_path: String,
+
+    /// output file
+    #[arg(short, long)]
+    output_path: String,
+
+    /// also export the colliders of every layer, one scene per layer
+    #[arg(long)]
+    colliders: bool,
+}
+
+/// Collects every part of a world into one glTF.
+///
+/// Materials and textures are shared by the collector itself. Identical NIFs are only
+/// visited once, later copies clone the first node and so share its meshes.
+struct WorldGltf {
+    gltf: Gltf,
+    /// Node visited for each distinct NIF by SHA-1 of its data, or none if it failed to parse
+    nodes_by_hash: HashMap<[u8; 20], Option<json::Index<json::Node>>>,
+    /// Names of cloned NIF nodes, which the collector names after the node they were
+    /// cloned from, applied after writing
+    clone_names: Vec<(json::Index<json::Node>, String)>,
+    scenes: Vec<(String, Vec<json::Index<json::Node>>)>,
+}
+
+impl WorldGltf {
+    fn new() -> Self {
+        Self {
+            gltf: Gltf::new(),
+            nodes_by_hash: HashMap::new(),
+            clone_names: Vec::new(),
+            scenes: Vec::new(),
+        }
+    }
+
+    fn add_nif(&mut self, data: Vec<u8>, name: &str) -> Option<json::Index<json::Node>> {
+        let hash = sha1_smol::Sha1::from(&data).digest().bytes();
+        if let Some(&node_index) = self.nodes_by_hash.get(&hash) {
+            let clone_index = self.gltf.clone_node(node_index?, None, None, None);
+            self.clone_names.push((clone_index, name.to_string()));
+            return Some(clone_index);
+        }
+
+        let node_index = match nif::Nif::parse(&mut Cursor::new(&data)) {
+            Ok(nif) => Some(self.gltf.visit_nif(&nif, None, name)),
+            Err(e) => {
+                println!("Failed to parse NIF for {}: {:?}", name, e);
+                None
+            }
+        };
+
+        self.nodes_by_hash.insert(hash, node_index);
+        node_index
+    }
+
+    /// Add every non-empty blob of an archive, named in table order
+    fn add_archive<A: EmbeddedBlobArchive>(
+        &mut self,
+        archive: &WorldFile<A>,
+        names: impl Iterator<Item = String>,
+    ) -> anyhow::Result<Vec<json::Index<json::Node>>> {
+        let mut file = BufReader::new(File::open(&archive.path)?);
+        let mut node_indices = Vec::new();
+
+        for (blob, name) in archive.blobs().into_iter().zip(names) {
+            if blob.length == 0 {
+                continue;
+            }
+            let data = blob.read(&mut file)?;
+            node_indices.extend(self.add_nif(data, &name));
+        }
+
+        Ok(node_indices)
+    }
+
+    fn add_scene(&mut self, name: String, node_indices: Vec<json::Index<json::Node>>) {
+        println!("[gltf] {}: {} nodes", name, node_indices.len());
+        self.scenes.push((name, node_indices));
+    }
+
+    /// Write the glTF, followed by a scene for each group of colliders
+    fn write_to_files(
+        mut self,
+        gltf_path: PathBuf,
+        collider_groups: &[(String, &[Collider])],
+    ) -> anyhow::Result<()> {
+        // The first scene is the default one, so it gets everything
+        let all_node_indices = self
+            .scenes
+            .iter()
+            .flat_map(|(_, node_indices)| node_indices.iter().copied())
+            .collect();
+        self.gltf
+            .get_or_create_scene("World", Some(all_node_indices));
+
+        for (name, node_indices) in self.scenes {
+            self.gltf.get_or_create_scene(&name, Some(node_indices));
+        }
+
+        self.gltf.write_to_files(gltf_path.clone())?;
+
+        if self.clone_names.is_empty() && collider_groups.is_empty() {
+            return Ok(());
+        }
+
+        // The collector can neither rename nodes nor hold meshes that don't come from
+        // NIFs, so both are added to what it wrote
+        let root = json::Root::from_reader(BufReader::new(File::open(&gltf_path)?))?;
+        let mut mesh_gltf = MeshGltf::from_root(root);
+
+        for (node_index, name) in self.clone_names {
+            mesh_gltf.root_mut().nodes[node_index.value()].name = Some(name);
+        }
+
+        if !collider_groups.is_empty() {
+            let material = mesh_gltf.add_material(colliders::MATERIAL_NAME, colliders::COLOR);
+            for (name, colliders) in collider_groups {
+                let node_indices =
+                    colliders::add_colliders_to_gltf(&mut mesh_gltf, material, name, colliders)?;
+                println!("[gltf] {}: {} nodes", name, node_indices.len());
+
+                let world_scene = &mut mesh_gltf.root_mut().scenes[0];
+                world_scene.nodes.extend(node_indices);
+            }
+        }
+
+        mesh_gltf.write_to_files(&gltf_path)
+    }
+}
+
+fn process_gltf(gltf_opts: GltfOpts, codepage: Option<Codepage>) -> anyhow::Result<()> {
+    let world = World::open_with_codepage(
+        &gltf_opts.input_path,
+        codepage.unwrap_or(Lof::DEFAULT_CODEPAGE),
+    )?;
+
+    let mut world_gltf = WorldGltf::new();
+
+    let lf = world.lf();
+    let terrain = world_gltf.add_archive(
+        lf,
+        lf.blocks
+            .iter()
+            .map(|block| format!("Block{}", block.index)),
+    )?;
+    world_gltf.add_scene("Terrain".to_string(), terrain);
+
+    if let Some(lbf) = world.lbf() {
+        let block_objects = world_gltf.add_archive(
+            lbf,
+            lbf.blocks
+                .iter()
+                .flat_map(|block| block.objects.iter())
+                .map(|block_object| {
+                    format!(
+                        "Block{}Object{}",
+                        block_object.block_index, block_object.unk
+                    )
+                }),
+        )?;
+        world_gltf.add_scene("Block Objects".to_string(), block_objects);
+    }
+
+    if let Some(llf) = world.llf() {
+        let lanes = world_gltf.add_archive(
+            llf,
+            llf.blocks
+                .iter()
+                .map(|block| format!("Block{}Lane", block.block_index)),
+        )?;
+        world_gltf.add_scene("Block Lane Decals".to_string(), lanes);
+    }
+
+    if let Some(lgf) = world.lgf() {
+        let guardrails = world_gltf.add_archive(
+            lgf,
+            lgf.blocks
+                .iter()
+                .map(|block| format!("Block{}Guardrail", block.block_index)),
+        )?;
+        world_gltf.add_scene("Block Guardrails".to_string(), guardrails);
+    }
+
+    if let Some(lof) = world.lof() {
+        // Models are only templates for the object lists, so they are not put in a scene
+        let model_nodes = {
+            let mut file = BufReader::new(File::open(&lof.path)?);
+            let mut model_nodes = HashMap::new();
+            for (model, blob) in lof.models.iter().zip(lof.blobs()) {
+                let data = blob.read(&mut file)?;
+                if let Some(node_index) = world_gltf.add_nif(data, &format!("Model{}", model.index))
+                {
+                    model_nodes.insert(model.index, node_index);
+                }
+            }
+            model_nodes
+        };
+
+        for layer in world.layers() {
+            let Some(loi) = layer.loi() else {
+                continue;
+            };
+
+            let mut instances = Vec::new();
+            for block_object in loi.blocks.iter().flat_map(|block| block.objects.iter()) {
+                let Some(&model_node_index) = model_nodes.get(&block_object.model_table_index)
+                else {
+                    println!(
+                        "Skipping object {} in {}, model {} is missing",
+                        block_object.object_index,
+                        layer.name(),
+                        block_object.model_table_index
+                    );
+                    continue;
+                };
+
+                let (position, rotation) = (block_object.position, block_object.rotation);
+                instances.push(world_gltf.gltf.clone_node(
+                    model_node_index,
+                    Some([position.0, position.1, position.2]),
+                    Some([
+                        rotation.0 .0,
+                        rotation.0 .1,
+                        rotation.0 .2,
+                        rotation.1 .0,
+                        rotation.1 .1,
+                        rotation.1 .2,
+                        rotation.2 .0,
+                        rotation.2 .1,
+                        rotation.2 .2,
+                    ]),
+                    Some(block_object.scale),
+                ));
+            }
+            world_gltf.add_scene(format!("{} Objects", layer.name()), instances);
+        }
+    }
+
+    let collider_groups = if gltf_opts.colliders {
+        collider_groups(&world)
+    } else {
+        Vec::new()
+    };
+
+    world_gltf.write_to_files(PathBuf::from(gltf_opts.output_path), &collider_groups)
+}
+
+/// Colliders of every layer with an object list, named after the layer
+fn collider_groups(world: &World) -> Vec<(String, &[Collider])> {
+    world
+        .layers()
+        .iter()
+        .filter_map(|layer| {
+            let loi = layer.loi()?;
+            println!("[{}/loi] Colliders: {}", layer.name(), loi.colliders.len());
+            Some((
+                format!("{} Colliders", layer.name()),
+                loi.colliders.as_slice(),
+            ))
+        })
+        .collect()
+}
+
+#[derive(Parser)]
+struct CollidersOpts {
+    /// input directory
+    #[arg(short, long)]
+    input_path: String,
+
+    /// output .obj or .gltf file
+    #[arg(short, long)]
+    output_path: String,
+}
+
+fn process_colliders(
+    colliders_opts: CollidersOpts,
+    codepage: Option<Codepage>,
+) -> anyhow::Result<()> {
+    let world = World::open_with_codepage(
+        &colliders_opts.input_path,
+        codepage.unwrap_or(Lof::DEFAULT_CODEPAGE),
+    )?;
+
+    colliders::write_colliders(
+        Path::new(&colliders_opts.output_path),
+        &collider_groups(&world),
+    )
+}
+
 pub fn process_world(world_opts: WorldOpts) -> anyhow::Result<()> {
     match world_opts.cmd {
-        Command::Info(info_opts) => process_info(info_opts),
-        Command::Map(info_opts) => process_map(info_opts),
+        Command::Info(info_opts) => process_info(info_opts, world_opts.codepage),
+        Command::Map(info_opts) => process_map(info_opts, world_opts.codepage),
+        Command::Gltf(gltf_opts) => process_gltf(gltf_opts, world_opts.codepage),
+        Command::Colliders(colliders_opts) => {
+            process_colliders(colliders_opts, world_opts.codepage)
+        }
     }
 }
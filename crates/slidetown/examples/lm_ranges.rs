@@ -10,11 +10,7 @@ fn main() -> anyhow::Result<()> {
     let mut min_max_map = HashMap::new();
 
     for accel_option in lm.accel {
-        for (value, id) in accel_option
-            .values
-            .into_iter()
-            .zip(lm.accel_ids.clone().into_iter())
-        {
+        for (value, id) in accel_option.values.into_iter().zip(lm.accel_ids.clone()) {
             let (min, max) = min_max_map.entry(id).or_insert((f32::MAX, f32::MIN));
             if value < *min {
                 *min = value;
@@ -26,11 +22,7 @@ fn main() -> anyhow::Result<()> {
     }
 
     for speed_option in lm.speed {
-        for (value, id) in speed_option
-            .values
-            .into_iter()
-            .zip(lm.speed_ids.clone().into_iter())
-        {
+        for (value, id) in speed_option.values.into_iter().zip(lm.speed_ids.clone()) {
             let (min, max) = min_max_map.entry(id).or_insert((f32::MAX, f32::MIN));
             if value < *min {
                 *min = value;
@@ -42,11 +34,7 @@ fn main() -> anyhow::Result<()> {
     }
 
     for boost_option in lm.boost {
-        for (value, id) in boost_option
-            .values
-            .into_iter()
-            .zip(lm.boost_ids.clone().into_iter())
-        {
+        for (value, id) in boost_option.values.into_iter().zip(lm.boost_ids.clone()) {
             let (min, max) = min_max_map.entry(id).or_insert((f32::MAX, f32::MIN));
             if value < *min {
                 *min = value;
@@ -58,11 +46,7 @@ fn main() -> anyhow::Result<()> {
     }
 
     for dura_option in lm.dura {
-        for (value, id) in dura_option
-            .values
-            .into_iter()
-            .zip(lm.dura_ids.clone().into_iter())
-        {
+        for (value, id) in dura_option.values.into_iter().zip(lm.dura_ids.clone()) {
             let (min, max) = min_max_map.entry(id).or_insert((f32::MAX, f32::MIN));
             if value < *min {
                 *min = value;
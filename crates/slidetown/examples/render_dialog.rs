@@ -102,19 +102,14 @@ fn draw_control(
     Ok(())
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 enum Alignment {
     None,
     Half,
+    #[default]
     Full,
 }
 
-impl Default for Alignment {
-    fn default() -> Self {
-        Self::Full
-    }
-}
-
 impl Alignment {
     pub fn from_usize(val: usize) -> Self {
         if val & 0b001 > 0 || val & 0b100 > 0 {
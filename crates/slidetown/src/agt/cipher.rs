@@ -1,14 +1,14 @@
 use binrw::io::{Read, Seek, Write};
 
-pub struct XorReader<'cipher, 'reader, T: Read + Seek> {
-    reader: &'reader mut T,
+pub struct XorReader<'cipher, T: Read + Seek> {
+    reader: T,
     pos: u64,
     cipher_offset: usize,
     cipher: &'cipher [u8],
 }
 
-impl<'cipher, 'reader, T: Read + Seek> XorReader<'cipher, 'reader, T> {
-    pub fn new(reader: &'reader mut T, cipher: &'cipher [u8], cipher_offset: usize) -> Self {
+impl<'cipher, T: Read + Seek> XorReader<'cipher, T> {
+    pub fn new(mut reader: T, cipher: &'cipher [u8], cipher_offset: usize) -> Self {
         let pos: u64 = reader.stream_position().unwrap();
         Self {
             reader,
@@ -19,7 +19,7 @@ impl<'cipher, 'reader, T: Read + Seek> XorReader<'cipher, 'reader, T> {
     }
 }
 
-impl<'cipher, 'reader, T: Read + Seek> Read for XorReader<'cipher, 'reader, T> {
+impl<'cipher, T: Read + Seek> Read for XorReader<'cipher, T> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         let bytes_read = self.reader.read(buf)?;
         let cipher_len = self.cipher.len();
@@ -35,7 +35,7 @@ impl<'cipher, 'reader, T: Read + Seek> Read for XorReader<'cipher, 'reader, T> {
     }
 }
 
-impl<'cipher, 'reader, T: Read + Seek> Seek for XorReader<'cipher, 'reader, T> {
+impl<'cipher, T: Read + Seek> Seek for XorReader<'cipher, T> {
     fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
         self.pos = self.reader.seek(pos)?;
         Ok(self.pos)
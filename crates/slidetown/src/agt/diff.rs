@@ -0,0 +1,193 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::parsers::{agt::Entry, Codepage};
+
+use super::{normalize_entry_path, AgtBuilder, AgtReader, CHUNK_SIZE};
+
+/// Entry that only exists in one of the compared archives
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffEntry {
+    pub path: String,
+    pub decompressed_length: u32,
+    /// SHA-1 of the decompressed data, in hex
+    pub hash: String,
+}
+
+/// Entry that exists in both archives with different content
+#[derive(Debug, Clone, Serialize)]
+pub struct ChangedEntry {
+    /// Path as stored in the new archive
+    pub path: String,
+    pub old_length: u32,
+    pub new_length: u32,
+    pub old_hash: String,
+    pub new_hash: String,
+}
+
+/// Differences between two archives, matching entries by path regardless of case and slashes
+#[derive(Debug, Default, Serialize)]
+pub struct AgtDiff {
+    pub added: Vec<DiffEntry>,
+    pub removed: Vec<DiffEntry>,
+    pub changed: Vec<ChangedEntry>,
+    pub unchanged: usize,
+}
+
+impl AgtDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Paths, as stored in the new archive, of the entries a patch has to contain
+    pub fn patched_paths(&self) -> impl Iterator<Item = &str> {
+        self.changed
+            .iter()
+            .map(|changed| changed.path.as_str())
+            .chain(self.added.iter().map(|added| added.path.as_str()))
+    }
+}
+
+impl<'cipher, 'reader, T: Read + Seek> AgtReader<'cipher, 'reader, T> {
+    /// SHA-1 of an entry's decompressed data, in hex
+    pub fn hash_entry(&mut self, entry: &Entry) -> anyhow::Result<String> {
+        let mut entry_reader = self.open_entry(entry)?;
+
+        let mut hasher = sha1_smol::Sha1::new();
+        let mut buf = vec![0u8; CHUNK_SIZE];
+        loop {
+            let bytes_read = entry_reader.read(&mut buf)?;
+            if bytes_read == 0 {
+                break;
+            }
+            hasher.update(&buf[..bytes_read]);
+        }
+
+        Ok(hasher.digest().to_string())
+    }
+}
+
+/// Read every entry of an archive along with the hash of its content
+fn hash_entries<P: AsRef<Path>>(
+    path: P,
+    cipher: &[u8],
+    codepage: Codepage,
+) -> anyhow::Result<Vec<(Entry, String)>> {
+    let mut file = BufReader::new(File::open(path)?);
+    let mut agt_reader = AgtReader::new(&mut file, cipher);
+    agt_reader.set_codepage(codepage);
+
+    let header = agt_reader.read_header()?;
+    let entries = agt_reader.read_entries_strict(header.file_count)?;
+
+    entries
+        .into_iter()
+        .map(|entry| {
+            let hash = agt_reader.hash_entry(&entry)?;
+            Ok((entry, hash))
+        })
+        .collect()
+}
+
+/// Compare two archives whose entry paths are in the given codepage by entry path
+/// and decompressed content
+pub fn diff_agt<P: AsRef<Path>, Q: AsRef<Path>>(
+    old_path: P,
+    old_cipher: &[u8],
+    new_path: Q,
+    new_cipher: &[u8],
+    codepage: Codepage,
+) -> anyhow::Result<AgtDiff> {
+    let old_entries = hash_entries(old_path, old_cipher, codepage)?;
+    let new_entries = hash_entries(new_path, new_cipher, codepage)?;
+
+    // Like the game, the first entry wins if a path appears twice
+    let mut old_by_path = HashMap::new();
+    for (entry, hash) in old_entries {
+        old_by_path
+            .entry(normalize_entry_path(&entry.path))
+            .or_insert((entry, hash));
+    }
+
+    let mut diff = AgtDiff::default();
+    let mut seen_paths = HashSet::new();
+
+    for (new_entry, new_hash) in new_entries {
+        let normalized_path = normalize_entry_path(&new_entry.path);
+        if !seen_paths.insert(normalized_path.clone()) {
+            continue;
+        }
+
+        match old_by_path.remove(&normalized_path) {
+            Some((_, old_hash)) if old_hash == new_hash => diff.unchanged += 1,
+            Some((old_entry, old_hash)) => diff.changed.push(ChangedEntry {
+                path: new_entry.path,
+                old_length: old_entry.decompressed_length,
+                new_length: new_entry.decompressed_length,
+                old_hash,
+                new_hash,
+            }),
+            None => diff.added.push(DiffEntry {
+                path: new_entry.path,
+                decompressed_length: new_entry.decompressed_length,
+                hash: new_hash,
+            }),
+        }
+    }
+
+    diff.removed = old_by_path
+        .into_values()
+        .map(|(entry, hash)| DiffEntry {
+            path: entry.path,
+            decompressed_length: entry.decompressed_length,
+            hash,
+        })
+        .collect();
+
+    diff.added.sort_by(|a, b| a.path.cmp(&b.path));
+    diff.removed.sort_by(|a, b| a.path.cmp(&b.path));
+    diff.changed.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(diff)
+}
+
+/// Start a patch archive holding the changed and added entries of the new archive.
+///
+/// Entries keep the new archive's order and header, and their chunks are copied
+/// without recompressing them. The codepage has to be the one the diff was made with.
+pub fn delta_builder<P: AsRef<Path>>(
+    diff: &AgtDiff,
+    new_path: P,
+    new_cipher: &[u8],
+    codepage: Codepage,
+) -> anyhow::Result<AgtBuilder> {
+    let mut file = BufReader::new(File::open(new_path.as_ref())?);
+    let mut agt_reader = AgtReader::new(&mut file, new_cipher);
+    agt_reader.set_codepage(codepage);
+
+    let header = agt_reader.read_header()?;
+    let entries = agt_reader.read_entries_strict(header.file_count)?;
+
+    let mut builder = AgtBuilder::new();
+    builder.set_codepage(codepage);
+    builder.set_header(header);
+    builder.set_preserve_order(true);
+
+    let patched_paths = diff.patched_paths().collect::<HashSet<_>>();
+    let mut seen_paths = HashSet::new();
+    for entry in entries {
+        // Like diff_agt, only the first entry with a path is the one the game reads
+        if !seen_paths.insert(normalize_entry_path(&entry.path)) {
+            continue;
+        }
+        if patched_paths.contains(&entry.path.as_str()) {
+            builder.add_agt_entry(new_path.as_ref(), new_cipher, entry);
+        }
+    }
+
+    Ok(builder)
+}
@@ -0,0 +1,144 @@
+use std::fs::File;
+use std::io::{BufWriter, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use crate::parsers::Codepage;
+
+use super::{normalize_entry_path, AgtBuilder, AgtBuilderOptions};
+
+/// Edits an existing AGT file.
+///
+/// Adds, replacements and removals are queued up and applied when saving. Untouched
+/// entries are copied over with their compressed chunks as-is, in their original order.
+pub struct AgtEditor {
+    path: PathBuf,
+    cipher: Vec<u8>,
+    builder: AgtBuilder,
+}
+
+impl AgtEditor {
+    pub fn open<P: AsRef<Path>>(path: P, cipher: &[u8]) -> anyhow::Result<Self> {
+        Self::open_with_codepage(path, cipher, Default::default())
+    }
+
+    /// Open an archive whose entry paths are in the given codepage
+    pub fn open_with_codepage<P: AsRef<Path>>(
+        path: P,
+        cipher: &[u8],
+        codepage: Codepage,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            path: path.as_ref().to_owned(),
+            cipher: cipher.to_vec(),
+            builder: AgtBuilder::from_agt_with_codepage(path, cipher, codepage)?,
+        })
+    }
+
+    /// How new or replaced entries are compressed
+    pub fn set_options(&mut self, options: AgtBuilderOptions) {
+        self.builder.set_options(options);
+    }
+
+    /// Find the stored path of an entry, ignoring case and slash direction
+    pub fn find_entry(&self, path: &str) -> Option<String> {
+        if self.builder.contains_entry(path) {
+            return Some(path.to_owned());
+        }
+
+        let normalized_path = normalize_entry_path(path);
+        self.builder
+            .entry_paths()
+            .find(|entry_path| normalize_entry_path(entry_path) == normalized_path)
+            .map(str::to_owned)
+    }
+
+    /// Paths of all entries that will be written, in order
+    pub fn entry_paths(&self) -> impl Iterator<Item = &str> {
+        self.builder.entry_paths()
+    }
+
+    /// Queue a new entry, fails if the archive already has it
+    pub fn add_memory(&mut self, path: String, data: &[u8]) -> anyhow::Result<()> {
+        self.ensure_missing(&path)?;
+        self.builder.add_entry_memory(path, data);
+        Ok(())
+    }
+
+    /// Queue a new entry read from a file, fails if the archive already has it
+    pub fn add_file<P: AsRef<Path>>(&mut self, path: String, file_path: P) -> anyhow::Result<()> {
+        self.ensure_missing(&path)?;
+        let file_size = std::fs::metadata(file_path.as_ref())?.len();
+        self.builder.add_entry_file(path, file_path, 0, file_size);
+        Ok(())
+    }
+
+    /// Queue new data for an existing entry, fails if the archive doesn't have it
+    pub fn replace_memory(&mut self, path: &str, data: &[u8]) -> anyhow::Result<()> {
+        let existing_path = self.ensure_existing(path)?;
+        self.builder.add_entry_memory(existing_path, data);
+        Ok(())
+    }
+
+    /// Queue new data read from a file for an existing entry, fails if the archive doesn't have it
+    pub fn replace_file<P: AsRef<Path>>(&mut self, path: &str, file_path: P) -> anyhow::Result<()> {
+        let existing_path = self.ensure_existing(path)?;
+        let file_size = std::fs::metadata(file_path.as_ref())?.len();
+        self.builder
+            .add_entry_file(existing_path, file_path, 0, file_size);
+        Ok(())
+    }
+
+    /// Queue the removal of an existing entry, fails if the archive doesn't have it.
+    ///
+    /// Entries whose path doesn't decode in the archive's codepage can't be told apart
+    /// by their lossy path and fail as well.
+    pub fn remove(&mut self, path: &str) -> anyhow::Result<()> {
+        let existing_path = self.ensure_existing(path)?;
+        if !self.builder.remove_entry(&existing_path) {
+            anyhow::bail!(
+                "can't remove {}, its path doesn't decode in the archive's codepage",
+                existing_path
+            );
+        }
+        Ok(())
+    }
+
+    /// Write the edited archive to a new location
+    pub fn save<W: Write + Seek>(self, writer: &mut W) -> anyhow::Result<()> {
+        self.builder.write(writer, &self.cipher)
+    }
+
+    /// Overwrite the original archive, going through a temporary file next to it
+    pub fn save_in_place(self) -> anyhow::Result<()> {
+        let mut temp_path = self.path.clone().into_os_string();
+        temp_path.push(".tmp");
+        let temp_path = PathBuf::from(temp_path);
+
+        let result = (|| {
+            let mut temp_file = BufWriter::new(File::create(&temp_path)?);
+            self.builder.write(&mut temp_file, &self.cipher)?;
+            temp_file.flush()?;
+            Ok(())
+        })();
+
+        match result {
+            Ok(()) => Ok(std::fs::rename(&temp_path, &self.path)?),
+            Err(e) => {
+                let _ = std::fs::remove_file(&temp_path);
+                Err(e)
+            }
+        }
+    }
+
+    fn ensure_missing(&self, path: &str) -> anyhow::Result<()> {
+        match self.find_entry(path) {
+            Some(existing_path) => anyhow::bail!("archive already has entry {}", existing_path),
+            None => Ok(()),
+        }
+    }
+
+    fn ensure_existing(&self, path: &str) -> anyhow::Result<String> {
+        self.find_entry(path)
+            .ok_or_else(|| anyhow::anyhow!("archive has no entry {}", path))
+    }
+}
@@ -0,0 +1,180 @@
+use binrw::io::{Read, Seek, SeekFrom};
+use flate2::read::ZlibDecoder;
+use std::io::{Error, ErrorKind};
+
+use crate::parsers::agt::Entry;
+
+use super::{cipher::XorReader, CHUNK_SIZE, MAX_INFLATE_RATIO};
+
+/// Streaming reader for the data of a single entry.
+///
+/// Chunks are decompressed one at a time as they are reached, and seeking jumps straight
+/// to the chunk containing the target position using the fixed 16 KiB chunk size.
+pub struct AgtEntryReader<R: Read + Seek> {
+    /// Decrypted archive stream
+    reader: R,
+    /// Absolute offset of every compressed chunk in the archive
+    chunk_offsets: Vec<u64>,
+    /// Compressed length of every chunk
+    chunk_lengths: Vec<u16>,
+    decompressed_length: u64,
+    /// Position within the decompressed data
+    pos: u64,
+    /// Index and decompressed data of the currently loaded chunk
+    chunk: Option<(usize, Vec<u8>)>,
+}
+
+impl<R: Read + Seek> AgtEntryReader<R> {
+    /// Open an entry on an already decrypted archive stream, reading its chunk lengths
+    pub fn new(mut reader: R, entry: &Entry) -> anyhow::Result<Self> {
+        // Check what the table of contents claims before anything is allocated for it
+        let decompressed_length = entry.decompressed_length as u64;
+        let expected_chunk_count = decompressed_length.div_ceil(CHUNK_SIZE as u64);
+        if entry.chunk_count as u64 != expected_chunk_count {
+            anyhow::bail!(
+                "entry {} has {} chunk(s), expected {} for {} bytes",
+                entry.path,
+                entry.chunk_count,
+                expected_chunk_count,
+                decompressed_length
+            );
+        }
+
+        let stream_length = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(entry.chunks_offset as u64))?;
+
+        let mut len_buf = vec![0u8; entry.chunk_count as usize * 2];
+        reader.read_exact(&mut len_buf)?;
+        let chunk_lengths = len_buf
+            .chunks_exact(2)
+            .map(|len| u16::from_le_bytes([len[0], len[1]]))
+            .collect::<Vec<_>>();
+
+        let mut chunk_offsets = Vec::with_capacity(chunk_lengths.len());
+        let mut chunk_offset = entry.chunks_offset as u64 + len_buf.len() as u64;
+        for &len in chunk_lengths.iter() {
+            chunk_offsets.push(chunk_offset);
+            chunk_offset += len as u64;
+        }
+
+        if chunk_offset > stream_length {
+            anyhow::bail!(
+                "entry {} chunks end at {}, past the end of the archive at {}",
+                entry.path,
+                chunk_offset,
+                stream_length
+            );
+        }
+        let compressed_length = chunk_offset - entry.chunks_offset as u64 - len_buf.len() as u64;
+        if decompressed_length > compressed_length * MAX_INFLATE_RATIO {
+            anyhow::bail!(
+                "entry {} claims {} bytes, more than its {} compressed bytes can hold",
+                entry.path,
+                decompressed_length,
+                compressed_length
+            );
+        }
+
+        Ok(Self {
+            reader,
+            chunk_offsets,
+            chunk_lengths,
+            decompressed_length,
+            pos: 0,
+            chunk: None,
+        })
+    }
+
+    /// Length of the decompressed entry data
+    pub fn len(&self) -> u64 {
+        self.decompressed_length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.decompressed_length == 0
+    }
+
+    /// Return the decompressed data of the given chunk, loading it if necessary
+    fn load_chunk(&mut self, chunk_index: usize) -> std::io::Result<&[u8]> {
+        if !matches!(self.chunk, Some((loaded_index, _)) if loaded_index == chunk_index) {
+            let mut data = self.chunk.take().map(|(_, data)| data).unwrap_or_default();
+            data.clear();
+
+            self.reader
+                .seek(SeekFrom::Start(self.chunk_offsets[chunk_index]))?;
+            let mut decoder =
+                ZlibDecoder::new((&mut self.reader).take(self.chunk_lengths[chunk_index] as u64));
+            decoder.read_to_end(&mut data)?;
+
+            let chunk_start = chunk_index as u64 * CHUNK_SIZE as u64;
+            let expected_length =
+                (self.decompressed_length.saturating_sub(chunk_start)).min(CHUNK_SIZE as u64);
+            if data.len() as u64 != expected_length {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "chunk {} decompressed to {} bytes, expected {}",
+                        chunk_index,
+                        data.len(),
+                        expected_length
+                    ),
+                ));
+            }
+
+            self.chunk = Some((chunk_index, data));
+        }
+
+        Ok(self
+            .chunk
+            .as_ref()
+            .map(|(_, data)| data.as_slice())
+            .unwrap())
+    }
+}
+
+impl<'cipher, T: Read + Seek> AgtEntryReader<XorReader<'cipher, T>> {
+    /// Open an entry directly on an encrypted archive stream, taking ownership of it
+    pub fn open(reader: T, cipher: &'cipher [u8], entry: &Entry) -> anyhow::Result<Self> {
+        Self::new(XorReader::new(reader, cipher, 32), entry)
+    }
+}
+
+impl<R: Read + Seek> Read for AgtEntryReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() || self.pos >= self.decompressed_length {
+            return Ok(0);
+        }
+
+        let chunk_index = (self.pos / CHUNK_SIZE as u64) as usize;
+        let offset_in_chunk = (self.pos % CHUNK_SIZE as u64) as usize;
+
+        let chunk = self.load_chunk(chunk_index)?;
+        let available = &chunk[offset_in_chunk..];
+        let bytes_read = available.len().min(buf.len());
+        buf[..bytes_read].copy_from_slice(&available[..bytes_read]);
+
+        self.pos += bytes_read as u64;
+        Ok(bytes_read)
+    }
+}
+
+impl<R: Read + Seek> Seek for AgtEntryReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.decompressed_length.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        };
+
+        match new_pos {
+            Some(new_pos) => {
+                self.pos = new_pos;
+                Ok(new_pos)
+            }
+            None => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
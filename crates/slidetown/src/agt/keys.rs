@@ -0,0 +1,398 @@
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::parsers::agt::Header;
+
+use super::{XorReader, CHUNK_SIZE};
+
+/// Everything before this offset is stored in plain text
+const CIPHER_OFFSET: usize = 32;
+
+/// Cipher key for AGT files from a known client build
+#[derive(Debug)]
+pub struct KnownKey {
+    /// Short name used to select the key, e.g. on the command line
+    pub name: &'static str,
+    /// Where the key comes from or which clients use it
+    pub description: &'static str,
+    pub key: &'static [u8],
+}
+
+/// Keys built into the library.
+///
+/// Only the sample key is known so far, the keys of retail clients for each region still have
+/// to be recovered from their archives with [`KeyRecovery`]. Until they are added here, they are
+/// loaded from a directory with [`load_key_dir`].
+pub static KNOWN_KEYS: &[KnownKey] = &[KnownKey {
+    name: "spooky",
+    description: "key the bundled dev_neodata.agt sample is encrypted with",
+    key: &[
+        0x01, 0x05, 0x06, 0x02, 0x04, 0x03, 0x07, 0x08, 0x01, 0x05, 0x06, 0x0F, 0x04, 0x03, 0x07,
+        0x0C, 0x31, 0x85, 0x76, 0x39, 0x34, 0x3D, 0x30, 0xE8, 0x67, 0x36, 0x36, 0x32, 0x3E, 0x33,
+        0x34, 0x3B, 0x11, 0x15, 0x16, 0x16, 0x14, 0x13, 0x1D, 0x18, 0x11, 0x03, 0x06, 0x0C, 0x04,
+        0x03, 0x06, 0x08, 0x2E, 0x55, 0x26, 0x23, 0x2A, 0x23, 0x2E, 0x28, 0x21, 0x21, 0x26, 0x27,
+        0x2E, 0x00, 0x2D, 0x2D, 0xCF, 0xA5, 0x06, 0x02, 0x04, 0x0F, 0x07, 0x18, 0xE1, 0x15, 0x36,
+        0x18, 0x60, 0x13, 0x1A, 0x19, 0x11, 0x15, 0x16, 0x10, 0x12, 0x13, 0x17, 0x38, 0xF1, 0x25,
+    ],
+}];
+
+/// Key used when writing new archives and nothing else was asked for
+pub fn default_key() -> &'static KnownKey {
+    &KNOWN_KEYS[0]
+}
+
+pub fn find_known_key(name: &str) -> Option<&'static KnownKey> {
+    KNOWN_KEYS
+        .iter()
+        .find(|known_key| known_key.name.eq_ignore_ascii_case(name))
+}
+
+/// Cipher key loaded from a file at runtime, e.g. a regional key found with [`KeyRecovery`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyFile {
+    /// File stem of the key file, used to select the key like a built-in key name
+    pub name: String,
+    pub key: Vec<u8>,
+}
+
+/// Load every `.bin` key file in a directory, sorted by name.
+///
+/// Keys for client regions that aren't in [`KNOWN_KEYS`] are kept in such a directory
+/// and named after the region, e.g. `kr.bin` or `tw.bin`.
+pub fn load_key_dir<P: AsRef<Path>>(dir: P) -> anyhow::Result<Vec<KeyFile>> {
+    let mut key_files = Vec::new();
+    for dir_entry in std::fs::read_dir(dir.as_ref())? {
+        let path = dir_entry?.path();
+        if !path.is_file() || path.extension().is_none_or(|extension| extension != "bin") {
+            continue;
+        }
+
+        let name = path
+            .file_stem()
+            .and_then(|file_stem| file_stem.to_str())
+            .ok_or_else(|| anyhow::anyhow!("invalid key file name {}", path.display()))?
+            .to_owned();
+        let key = std::fs::read(&path)?;
+        if key.is_empty() {
+            anyhow::bail!("key file {} is empty", path.display());
+        }
+
+        key_files.push(KeyFile { name, key });
+    }
+    key_files.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(key_files)
+}
+
+/// Find the first known key that opens the given archive
+pub fn detect_key<R: Read + Seek>(reader: &mut R) -> Option<&'static KnownKey> {
+    KNOWN_KEYS
+        .iter()
+        .find(|known_key| is_valid_key(reader, known_key.key))
+}
+
+/// Check whether a key decodes a sane table of contents.
+///
+/// Only the header and table of contents are looked at, so a key is still recognized
+/// when entry data is damaged or the archive is truncated.
+pub fn is_valid_key<R: Read + Seek>(reader: &mut R, cipher: &[u8]) -> bool {
+    if cipher.is_empty() {
+        return false;
+    }
+
+    check_key(reader, cipher).unwrap_or(false)
+}
+
+/// Longest entry path accepted while probing keys, so wrong keys can't cause huge allocations
+const MAX_PATH_LENGTH: usize = 1024;
+
+fn check_key<R: Read + Seek>(reader: &mut R, cipher: &[u8]) -> anyhow::Result<bool> {
+    let file_length = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(0))?;
+
+    let header = Header::parse(reader)?;
+    // Entries take at least 16 bytes each
+    if header.file_count as u64 * 16 > file_length {
+        return Ok(false);
+    }
+
+    let mut reader = XorReader::new(reader, cipher, CIPHER_OFFSET);
+    for _ in 0..header.file_count {
+        let mut fields = [0u8; 16];
+        reader.read_exact(&mut fields)?;
+        let field = |i: usize| u32::from_le_bytes(fields[i * 4..i * 4 + 4].try_into().unwrap());
+
+        let path_length = field(3) as usize;
+        if path_length == 0 || path_length > MAX_PATH_LENGTH {
+            return Ok(false);
+        }
+        let mut path = vec![0u8; path_length];
+        reader.read_exact(&mut path)?;
+        if path.iter().any(|&byte| byte < 0x20) {
+            return Ok(false);
+        }
+
+        // A wrong key turns these into noise that practically never agrees
+        let (chunk_count, decompressed_length) = (field(1), field(2));
+        let expected_chunk_count = (decompressed_length as u64).div_ceil(CHUNK_SIZE as u64);
+        if chunk_count as u64 != expected_chunk_count {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Recovers an unknown cipher key from an encrypted archive.
+///
+/// XOR with a repeating key leaks through wherever the plain text is predictable:
+/// the table of contents is mostly zero bytes (high bytes of small integers) and every
+/// compressed chunk starts with the same zlib header. The key length is found from
+/// coincidences between bytes one key length apart in the table of contents, then every
+/// key byte is voted on by those predictable bytes and any known plain text that was added.
+///
+/// Recovery relies on statistics, so it needs archives with a decent number of entries.
+/// Real client archives with hundreds or thousands of entries work well; tiny ones may
+/// need some known plain text, like the content of one of their files.
+pub struct KeyRecovery<'a> {
+    data: &'a [u8],
+    max_key_length: usize,
+    known_plaintext: Vec<(usize, u8)>,
+}
+
+impl<'a> KeyRecovery<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            max_key_length: 256,
+            known_plaintext: Vec::new(),
+        }
+    }
+
+    /// Longest key length to consider, defaults to 256
+    pub fn set_max_key_length(&mut self, max_key_length: usize) {
+        self.max_key_length = max_key_length;
+    }
+
+    /// Add plain text known to be at the given offset of the archive
+    pub fn add_known_plaintext(&mut self, offset: usize, plaintext: &[u8]) {
+        self.known_plaintext.extend(
+            plaintext
+                .iter()
+                .enumerate()
+                .map(|(i, &byte)| (offset + i, byte))
+                .filter(|&(pos, _)| pos >= CIPHER_OFFSET && pos < self.data.len()),
+        );
+    }
+
+    pub fn recover(&self) -> anyhow::Result<Vec<u8>> {
+        if self.data.len() < CIPHER_OFFSET || &self.data[..8] != b"NayaPack" {
+            anyhow::bail!("not an AGT file");
+        }
+
+        let file_count = u32::from_le_bytes(self.data[16..20].try_into().unwrap()) as usize;
+        if file_count == 0 {
+            anyhow::bail!("archive has no entries to recover a key from");
+        }
+
+        for key_length in self.key_length_candidates(file_count) {
+            let votes = self.votes(key_length, file_count);
+            let mut key = best_votes(&votes);
+
+            for _ in 0..REFINE_ROUNDS {
+                if is_valid_key(&mut Cursor::new(self.data), &key) {
+                    return Ok(key);
+                }
+
+                let mut refined_votes = votes.clone();
+                self.add_layout_votes(&key, file_count, &mut refined_votes);
+                let refined_key = best_votes(&refined_votes);
+                if refined_key == key {
+                    break;
+                }
+                key = refined_key;
+            }
+        }
+
+        anyhow::bail!("could not recover key, try adding known plain text")
+    }
+
+    /// Range assumed to be table of contents, entries are at least 16 bytes plus a short path
+    fn toc_range(&self, file_count: usize) -> std::ops::Range<usize> {
+        let toc_end = (CIPHER_OFFSET + file_count * 24).min(self.data.len());
+        CIPHER_OFFSET..toc_end
+    }
+
+    /// Key lengths ordered by how often bytes one key length apart match in the table of contents.
+    /// Multiples of the real length score just as well, so shorter lengths win ties.
+    fn key_length_candidates(&self, file_count: usize) -> Vec<usize> {
+        let toc = &self.data[self.toc_range(file_count)];
+
+        let mut scores = (1..=self.max_key_length.min(toc.len().saturating_sub(1)))
+            .map(|key_length| {
+                let pairs = toc.len() - key_length;
+                let matches = toc
+                    .iter()
+                    .zip(toc[key_length..].iter())
+                    .filter(|(a, b)| a == b)
+                    .count();
+                (key_length, matches as f64 / pairs as f64)
+            })
+            .collect::<Vec<_>>();
+
+        let best_score = scores.iter().map(|&(_, score)| score).fold(0.0, f64::max);
+
+        // Anything close to the best score first, shortest first, then the rest of the top scores
+        let mut candidates = scores
+            .iter()
+            .filter(|&&(_, score)| score >= best_score * 0.6)
+            .map(|&(key_length, _)| key_length)
+            .collect::<Vec<_>>();
+
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+        for (key_length, _) in scores.into_iter().take(8) {
+            if !candidates.contains(&key_length) {
+                candidates.push(key_length);
+            }
+        }
+
+        candidates
+    }
+
+    /// Votes for every key byte from predictable plain text
+    fn votes(&self, key_length: usize, file_count: usize) -> Vec<[u32; 256]> {
+        let mut votes = vec![[0u32; 256]; key_length];
+
+        // Table of contents, assuming zero bytes
+        for pos in self.toc_range(file_count) {
+            votes[pos % key_length][self.data[pos] as usize] += 1;
+        }
+
+        // Chunk starts, assuming the default zlib header 78 9C
+        let data_start = self.toc_range(file_count).end;
+        let data_end = self.data.len().min(data_start + 16 * 1024 * 1024);
+        let mut pair_counts = vec![HashMap::<(u8, u8), u32>::new(); key_length];
+        for pos in data_start..data_end.saturating_sub(1) {
+            let pair = (self.data[pos] ^ 0x78, self.data[pos + 1] ^ 0x9C);
+            *pair_counts[pos % key_length].entry(pair).or_default() += 1;
+        }
+        for (residue, counts) in pair_counts.iter().enumerate() {
+            let mut sorted_counts = counts.iter().collect::<Vec<_>>();
+            sorted_counts.sort_by(|a, b| b.1.cmp(a.1));
+            if let [(&(first, second), &count), rest @ ..] = sorted_counts.as_slice() {
+                let runner_up = rest.first().map_or(0, |(_, &count)| count);
+                // Only trust pairs that clearly stand out from the noise of compressed data
+                if count >= 4 && count >= runner_up * 2 {
+                    votes[residue][first as usize] += count - runner_up;
+                    votes[(residue + 1) % key_length][second as usize] += count - runner_up;
+                }
+            }
+        }
+
+        // Known plain text is certain
+        for &(pos, byte) in self.known_plaintext.iter() {
+            votes[pos % key_length][(self.data[pos] ^ byte) as usize] += 1_000_000;
+        }
+
+        votes
+    }
+
+    /// Walk the table of contents with a guessed key and vote for the bytes its layout implies.
+    ///
+    /// A mostly right key decodes most entries, which pins down the high bytes of path lengths
+    /// and chunk counts, chunk counts derived from decompressed lengths, and the zlib header
+    /// of each entry's first chunk. Walking stops at the first entry that doesn't make sense.
+    fn add_layout_votes(&self, key: &[u8], file_count: usize, votes: &mut [[u32; 256]]) {
+        let key_length = key.len();
+        let plain = |pos: usize| self.data[pos] ^ key[pos % key_length];
+        let mut vote = |pos: usize, byte: u8| {
+            if pos < self.data.len() {
+                votes[pos % key_length][(self.data[pos] ^ byte) as usize] += LAYOUT_VOTE_WEIGHT;
+            }
+        };
+
+        let mut pos = CIPHER_OFFSET;
+        for file_index in 0..file_count {
+            if pos + 16 > self.data.len() {
+                break;
+            }
+            let field = |i: usize| {
+                u32::from_le_bytes([
+                    plain(pos + i * 4),
+                    plain(pos + i * 4 + 1),
+                    plain(pos + i * 4 + 2),
+                    plain(pos + i * 4 + 3),
+                ])
+            };
+
+            // Bytes that are zero in any sane entry: high bytes of the chunk count and path length
+            let zero_bytes = |entry_pos: usize| {
+                [6, 7, 13, 14, 15]
+                    .iter()
+                    .filter(|&&i| entry_pos + i < self.data.len() && plain(entry_pos + i) == 0)
+                    .count()
+            };
+
+            // A wrong key byte in the path length would derail the walk, so prefer the length
+            // after which the next entry looks right, and fall back to the decoded length
+            let decoded_path_length = plain(pos + 12) as usize;
+            let path_length = if file_index + 1 < file_count {
+                (1..=u8::MAX as usize)
+                    .max_by_key(|&path_length| {
+                        (
+                            zero_bytes(pos + 16 + path_length),
+                            path_length == decoded_path_length,
+                        )
+                    })
+                    .unwrap()
+            } else {
+                decoded_path_length
+            };
+            if path_length == 0 || pos + 16 + path_length > self.data.len() {
+                break;
+            }
+            vote(pos + 12, path_length as u8);
+            for i in 13..16 {
+                vote(pos + i, 0);
+            }
+
+            let chunk_count = (field(2) as usize).div_ceil(CHUNK_SIZE);
+            if chunk_count <= u16::MAX as usize {
+                for (i, &byte) in (chunk_count as u32).to_le_bytes().iter().enumerate() {
+                    vote(pos + 4 + i, byte);
+                }
+
+                let first_chunk = field(0) as usize + chunk_count * 2;
+                if chunk_count > 0 && first_chunk + 1 < self.data.len() {
+                    vote(first_chunk, 0x78);
+                    vote(first_chunk + 1, 0x9C);
+                }
+            }
+
+            pos += 16 + path_length;
+        }
+    }
+}
+
+/// Weight of a byte implied by the decoded layout, worth more than a plain statistical guess
+const LAYOUT_VOTE_WEIGHT: u32 = 4;
+
+/// How many times to walk the table of contents with an improved key before giving up
+const REFINE_ROUNDS: usize = 8;
+
+fn best_votes(votes: &[[u32; 256]]) -> Vec<u8> {
+    votes
+        .iter()
+        .map(|residue_votes| {
+            (0..=255u8)
+                .max_by_key(|&byte| residue_votes[byte as usize])
+                .unwrap()
+        })
+        .collect()
+}
+
+/// Recover the key of an archive using only its own predictable structure
+pub fn recover_key(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    KeyRecovery::new(data).recover()
+}
@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::parsers::Codepage;
+
+use super::{normalize_entry_path, AgtBuilder, AgtReader};
+
+/// What to do when several archives being merged have the same entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictPolicy {
+    /// The archive latest in the list provides the entry, like patches applied over a base
+    LastWins,
+    /// The archive earliest in the list provides the entry
+    FirstWins,
+    /// Refuse to merge
+    Fail,
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "last-wins" => Ok(Self::LastWins),
+            "first-wins" => Ok(Self::FirstWins),
+            "fail" => Ok(Self::Fail),
+            _ => anyhow::bail!(
+                "unknown conflict policy {}, expected last-wins, first-wins or fail",
+                s
+            ),
+        }
+    }
+}
+
+/// Where an entry of a merged archive came from
+#[derive(Debug, Clone, Serialize)]
+pub struct MergedEntry {
+    /// Path as first seen among the sources
+    pub path: String,
+    /// Index of the source archive that provides the data
+    pub source_index: usize,
+    /// Indices of other source archives that had the entry too
+    pub shadowed_source_indices: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MergeReport {
+    /// Source archives in priority order
+    pub sources: Vec<PathBuf>,
+    /// Entries in the order they are written
+    pub entries: Vec<MergedEntry>,
+}
+
+/// Merge archives given in priority order, with entry paths in the given codepage,
+/// into a single builder.
+///
+/// Entries keep the position and path spelling of their first appearance, so the first
+/// archive's order is kept and new entries from later ones follow it. Paths are matched
+/// ignoring case and slash direction, and like the game only the first of several
+/// entries with the same path in one archive is used. Chunks are copied from the winning source without
+/// recompressing them, and the header comes from the first archive.
+pub fn merge_agt<P: AsRef<Path>>(
+    sources: &[(P, &[u8])],
+    codepage: Codepage,
+    policy: ConflictPolicy,
+) -> anyhow::Result<(AgtBuilder, MergeReport)> {
+    let mut builder = AgtBuilder::new();
+    builder.set_preserve_order(true);
+    builder.set_codepage(codepage);
+
+    let mut report = MergeReport {
+        sources: sources
+            .iter()
+            .map(|(path, _)| path.as_ref().to_owned())
+            .collect(),
+        entries: Vec::new(),
+    };
+    // Index into the report's entries and stored path bytes by normalized path
+    let mut entry_indices = HashMap::new();
+
+    for (source_index, (path, cipher)) in sources.iter().enumerate() {
+        let mut file = BufReader::new(File::open(path.as_ref())?);
+        let mut agt_reader = AgtReader::new(&mut file, cipher);
+        agt_reader.set_codepage(codepage);
+
+        let header = agt_reader.read_header()?;
+        let entries = agt_reader.read_entries_strict(header.file_count)?;
+        if source_index == 0 {
+            builder.set_header(header);
+        }
+
+        for mut entry in entries {
+            let normalized_path = normalize_entry_path(&entry.path);
+            let Some((entry_index, raw_path)) = entry_indices.get(&normalized_path) else {
+                entry_indices.insert(
+                    normalized_path,
+                    (report.entries.len(), entry.raw_path.clone()),
+                );
+                report.entries.push(MergedEntry {
+                    path: entry.path.clone(),
+                    source_index,
+                    shadowed_source_indices: Vec::new(),
+                });
+                builder.add_agt_entry(path, cipher, entry);
+                continue;
+            };
+
+            let merged_entry: &mut MergedEntry = &mut report.entries[*entry_index];
+            // Like the game, the first entry wins if a path appears twice in one archive
+            if merged_entry.source_index == source_index
+                || merged_entry.shadowed_source_indices.last() == Some(&source_index)
+            {
+                continue;
+            }
+            match policy {
+                ConflictPolicy::Fail => anyhow::bail!(
+                    "entry {} exists in both {} and {}",
+                    entry.path,
+                    report.sources[merged_entry.source_index].display(),
+                    path.as_ref().display()
+                ),
+                ConflictPolicy::FirstWins => {
+                    merged_entry.shadowed_source_indices.push(source_index);
+                }
+                ConflictPolicy::LastWins => {
+                    merged_entry
+                        .shadowed_source_indices
+                        .push(merged_entry.source_index);
+                    merged_entry.source_index = source_index;
+
+                    // Replace under the first spelling so the entry keeps its position
+                    entry.path = merged_entry.path.clone();
+                    entry.raw_path = raw_path.clone();
+                    builder.add_agt_entry(path, cipher, entry);
+                }
+            }
+        }
+    }
+
+    Ok((builder, report))
+}
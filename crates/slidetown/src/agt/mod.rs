@@ -2,31 +2,69 @@ use binrw::{
     io::{Read, Seek},
     BinWriterExt,
 };
-use flate2::read::ZlibDecoder;
 use flate2::write::ZlibEncoder;
 use flate2::Compression;
-use std::io::{Cursor, SeekFrom};
-use std::{collections::BTreeMap, io::Write};
+use std::collections::{hash_map, HashMap};
+use std::fs::File;
+use std::io::{BufReader, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 
 mod cipher;
+mod diff;
+mod editor;
+mod entry_reader;
+pub mod keys;
+mod merge;
+mod slice_reader;
+mod sync;
+mod verify;
 
 use crate::parsers::agt::Entry;
 use crate::parsers::agt::Header;
+use crate::parsers::Codepage;
 
-use self::cipher::XorReader;
+pub(crate) use self::cipher::XorReader;
 use self::cipher::XorWriter;
+pub use self::diff::{delta_builder, diff_agt, AgtDiff, ChangedEntry, DiffEntry};
+pub use self::editor::AgtEditor;
+pub use self::entry_reader::AgtEntryReader;
+pub use self::merge::{merge_agt, ConflictPolicy, MergeReport, MergedEntry};
+pub use self::slice_reader::AgtSliceReader;
+pub use self::sync::{sync_agt, SyncChange, SyncFile, SyncReport};
+pub use self::verify::{EntryReport, VerifyReport};
+
+/// Uncompressed size of every chunk but the last one in an entry
+const CHUNK_SIZE: usize = 16384;
+
+/// Deflate can't expand data by more than this factor, entries claiming more are corrupt
+const MAX_INFLATE_RATIO: u64 = 1032;
+
+/// Normalize an entry path for comparison, the game treats paths case-insensitively
+/// and accepts either slash as a separator
+pub fn normalize_entry_path(path: &str) -> String {
+    path.replace('/', "\\").to_lowercase()
+}
 
 pub struct AgtReader<'cipher, 'reader, T: Read + Seek> {
-    reader: XorReader<'cipher, 'reader, T>,
+    reader: XorReader<'cipher, &'reader mut T>,
+    codepage: Codepage,
 }
 
 impl<'cipher, 'reader, T: Read + Seek> AgtReader<'cipher, 'reader, T> {
     pub fn new(reader: &'reader mut T, cipher: &'cipher [u8]) -> Self {
         Self {
             reader: XorReader::new(reader, cipher, 32),
+            codepage: Default::default(),
         }
     }
 
+    /// Codepage entry paths are decoded with, UTF-8 by default
+    pub fn set_codepage(&mut self, codepage: Codepage) {
+        self.codepage = codepage;
+    }
+
     pub fn read_header(&mut self) -> anyhow::Result<Header> {
         self.reader.seek(SeekFrom::Start(0))?;
         Header::parse(&mut self.reader)
@@ -34,48 +72,70 @@ impl<'cipher, 'reader, T: Read + Seek> AgtReader<'cipher, 'reader, T> {
 
     pub fn read_entries(&mut self, file_count: u32) -> anyhow::Result<Vec<Entry>> {
         self.reader.seek(SeekFrom::Start(32))?;
-        Entry::parse_entries(&mut self.reader, file_count)
+        Entry::parse_entries(&mut self.reader, file_count, self.codepage)
     }
 
-    /// Read and decrypt the data for the given entry
-    pub fn read_entry_data(&mut self, entry: &Entry) -> anyhow::Result<Vec<u8>> {
-        self.reader
-            .seek(SeekFrom::Start(entry.chunks_offset as u64))?;
-
-        let mut chunk_lengths = Vec::new();
-
-        for _ in 0..entry.chunk_count {
-            let mut len_buf = [0u8; 2];
-            self.reader.read_exact(&mut len_buf)?;
-            chunk_lengths.push(u16::from_le_bytes(len_buf));
+    /// Read entries, failing if any path isn't valid in the reader's codepage.
+    ///
+    /// Lossily decoded paths can collide, so anything that looks entries up by path
+    /// reads them this way instead of silently merging distinct entries.
+    pub fn read_entries_strict(&mut self, file_count: u32) -> anyhow::Result<Vec<Entry>> {
+        let entries = self.read_entries(file_count)?;
+        for entry in entries.iter() {
+            entry.decode_path(self.codepage).map_err(|e| {
+                anyhow::anyhow!(
+                    "{} in entry path {}, the archive uses another codepage",
+                    e,
+                    entry.path
+                )
+            })?;
         }
+        Ok(entries)
+    }
 
-        let mut result = Vec::new();
+    /// Open a streaming reader over the decompressed data for the given entry
+    pub fn open_entry(
+        &mut self,
+        entry: &Entry,
+    ) -> anyhow::Result<AgtEntryReader<&mut XorReader<'cipher, &'reader mut T>>> {
+        AgtEntryReader::new(&mut self.reader, entry)
+    }
 
-        for len in chunk_lengths {
-            let mut compressed_buf = vec![0u8; len as _];
-            self.reader.read_exact(&mut compressed_buf)?;
-            let mut decoder = ZlibDecoder::new(Cursor::new(compressed_buf));
-            decoder.read_to_end(&mut result)?;
-        }
+    /// Read and decrypt the data for the given entry
+    pub fn read_entry_data(&mut self, entry: &Entry) -> anyhow::Result<Vec<u8>> {
+        let mut entry_reader = self.open_entry(entry)?;
+
+        let mut result = Vec::with_capacity(entry_reader.len() as usize);
+        entry_reader.read_to_end(&mut result)?;
 
         Ok(result)
     }
 }
 
+/// An entry in an existing AGT file, whose compressed chunks can be copied as-is
+#[derive(Debug)]
+struct AgtFileSource {
+    /// Path to the source archive
+    path: PathBuf,
+    /// Cipher the source archive is encrypted with
+    cipher: Vec<u8>,
+    /// Entry in the source archive
+    entry: Entry,
+}
+
 #[derive(Debug)]
 enum AgtBuilderEntrySource {
-    // /// The compressed data for this entry will be copied from an existing AGT file
-    // AgtFile { path: String, entry: Entry },
-    // /// The uncompressed data for this entry will be read from a file
-    // File {
-    //     /// Path to the file
-    //     path: String,
-    //     /// Offset within the file
-    //     offset: usize,
-    //     /// Length of the data
-    //     size: usize,
-    // },
+    /// The compressed data for this entry will be copied from an existing AGT file
+    AgtFile(AgtFileSource),
+    /// The uncompressed data for this entry will be read from a file
+    File {
+        /// Path to the file
+        path: PathBuf,
+        /// Offset within the file
+        offset: u64,
+        /// Length of the data
+        size: u64,
+    },
     /// The entry will be dumped from memory
     Memory {
         /// Raw, uncompressed data
@@ -84,144 +144,634 @@ enum AgtBuilderEntrySource {
 }
 
 impl AgtBuilderEntrySource {
-    pub fn entry(&self, path: String) -> Entry {
-        match self {
-            // AgtBuilderEntrySource::AgtFile { entry, .. } => Entry {
-            //     chunks_offset: 0,
-            //     chunk_count: (entry.decompressed_length as f64 / 16384.0).ceil() as u32,
-            //     decompressed_length: entry.decompressed_length,
-            //     path,
-            // },
-            AgtBuilderEntrySource::Memory { data } => Entry {
-                chunks_offset: 0,
-                chunk_count: (data.len() as f64 / 16384.0).ceil() as u32,
-                decompressed_length: data.len() as _,
-                path,
-            },
+    /// Table of contents entry without its chunks offset, paths are encoded in the codepage
+    /// unless they are copied unchanged from an existing archive
+    pub fn entry(&self, path: String, codepage: Codepage) -> anyhow::Result<Entry> {
+        let (chunk_count, decompressed_length) = match self {
+            AgtBuilderEntrySource::AgtFile(AgtFileSource { entry, .. }) => {
+                if entry.path == path {
+                    return Ok(Entry {
+                        chunks_offset: 0,
+                        ..entry.clone()
+                    });
+                }
+                (entry.chunk_count, entry.decompressed_length)
+            }
+            AgtBuilderEntrySource::File { size, .. } => {
+                ((*size as f64 / CHUNK_SIZE as f64).ceil() as u32, *size as _)
+            }
+            AgtBuilderEntrySource::Memory { data } => (
+                (data.len() as f64 / CHUNK_SIZE as f64).ceil() as u32,
+                data.len() as _,
+            ),
+        };
+
+        Entry::new(path, codepage, 0, chunk_count, decompressed_length)
+    }
+}
+
+#[derive(Debug)]
+struct AgtBuilderEntry {
+    path: String,
+    /// Path bytes the entry is looked up by, see [`AgtBuilder::entry_key`]
+    key: Vec<u8>,
+    source: AgtBuilderEntrySource,
+    /// Entry from an existing AGT file that this one replaced.
+    /// Its compressed chunks are reused if the new data turns out to be identical.
+    original: Option<AgtFileSource>,
+    /// Key of the earlier entry with the same path that the game reads instead of this one.
+    /// Shadowed entries are only kept to write the archive back as it was.
+    shadowed_by: Option<Vec<u8>>,
+}
+
+/// How AgtBuilder compresses entries that aren't copied from an existing archive
+#[derive(Debug, Clone)]
+pub struct AgtBuilderOptions {
+    /// zlib compression level from 0 (stored) to 9, defaults to 6 like the original archives
+    pub compression_level: u32,
+    /// Chunks that still take more than this fraction of their uncompressed size are
+    /// compressed again at `poor_compression_level`, e.g. already compressed textures
+    pub poor_compression_ratio: Option<f32>,
+    /// Level for chunks that compress badly, defaults to 0 (stored)
+    pub poor_compression_level: u32,
+    /// Number of threads compressing chunks, 0 uses every available core.
+    /// The output is the same regardless of the thread count.
+    pub threads: usize,
+}
+
+impl Default for AgtBuilderOptions {
+    fn default() -> Self {
+        Self {
+            compression_level: 6,
+            poor_compression_ratio: None,
+            poor_compression_level: 0,
+            threads: 0,
+        }
+    }
+}
+
+impl AgtBuilderOptions {
+    fn validate(&self) -> anyhow::Result<()> {
+        for level in [self.compression_level, self.poor_compression_level] {
+            if level > 9 {
+                anyhow::bail!("compression level must be 0-9, got {}", level);
+            }
         }
+        Ok(())
+    }
+
+    fn thread_count(&self) -> usize {
+        match self.threads {
+            0 => std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+            threads => threads,
+        }
+    }
+
+    fn compress_chunk(&self, chunk: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let compressed_chunk = compress_chunk(chunk, self.compression_level)?;
+
+        match self.poor_compression_ratio {
+            Some(ratio)
+                if self.poor_compression_level != self.compression_level
+                    && compressed_chunk.len() as f32 > chunk.len() as f32 * ratio =>
+            {
+                compress_chunk(chunk, self.poor_compression_level)
+            }
+            _ => Ok(compressed_chunk),
+        }
+    }
+
+    /// Compress chunks on several threads, results are in the same order as the input
+    fn compress_chunks(&self, chunks: &[&[u8]]) -> anyhow::Result<Vec<Vec<u8>>> {
+        let thread_count = self.thread_count().min(chunks.len());
+        if thread_count <= 1 {
+            return chunks
+                .iter()
+                .map(|chunk| self.compress_chunk(chunk))
+                .collect();
+        }
+
+        // Threads take the next chunk as they go, each result lands in the chunk's own slot
+        let next_chunk = AtomicUsize::new(0);
+        let mut compressed_chunks = Vec::with_capacity(chunks.len());
+        compressed_chunks.resize_with(chunks.len(), || None);
+        let compressed_chunks = Mutex::new(compressed_chunks);
+
+        std::thread::scope(|scope| -> anyhow::Result<()> {
+            let workers = (0..thread_count)
+                .map(|_| {
+                    scope.spawn(|| -> anyhow::Result<()> {
+                        loop {
+                            let chunk_index = next_chunk.fetch_add(1, Ordering::Relaxed);
+                            let Some(chunk) = chunks.get(chunk_index) else {
+                                return Ok(());
+                            };
+                            let compressed_chunk = self.compress_chunk(chunk)?;
+                            compressed_chunks.lock().unwrap()[chunk_index] = Some(compressed_chunk);
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            for worker in workers {
+                worker
+                    .join()
+                    .map_err(|_| anyhow::anyhow!("compression thread panicked"))??;
+            }
+            Ok(())
+        })?;
+
+        Ok(compressed_chunks
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|compressed_chunk| compressed_chunk.unwrap())
+            .collect())
+    }
+}
+
+fn compress_chunk(chunk: &[u8], level: u32) -> anyhow::Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(level));
+    encoder.write_all(chunk)?;
+    Ok(encoder.finish()?)
+}
+
+/// Uncompressed entries waiting to be compressed together, so small entries still fill every thread
+#[derive(Default)]
+struct CompressionBatch {
+    /// Index of each entry in the builder and its uncompressed data
+    entries: Vec<(usize, Vec<u8>)>,
+    size: usize,
+}
+
+/// Uncompressed bytes to collect before compressing a batch
+const COMPRESSION_BATCH_SIZE: usize = 64 * 1024 * 1024;
+
+impl CompressionBatch {
+    fn push(&mut self, entry_index: usize, data: Vec<u8>) {
+        self.size += data.len();
+        self.entries.push((entry_index, data));
+    }
+
+    fn is_full(&self) -> bool {
+        self.size >= COMPRESSION_BATCH_SIZE
+    }
+
+    /// Compress and write every entry in the batch, recording where each one starts
+    fn flush<W: Write + Seek>(
+        &mut self,
+        options: &AgtBuilderOptions,
+        writer: &mut W,
+        data_offsets: &mut [u64],
+    ) -> anyhow::Result<()> {
+        let chunks = self
+            .entries
+            .iter()
+            .flat_map(|(_, data)| data.chunks(CHUNK_SIZE))
+            .collect::<Vec<_>>();
+        let mut compressed_chunks = options.compress_chunks(&chunks)?.into_iter();
+
+        for (entry_index, data) in self.entries.iter() {
+            data_offsets[*entry_index] = writer.stream_position()?;
+
+            let entry_chunks = compressed_chunks
+                .by_ref()
+                .take(data.len().div_ceil(CHUNK_SIZE))
+                .collect::<Vec<_>>();
+            let entry_chunks_lengths = entry_chunks
+                .iter()
+                .map(|chunk| u16::try_from(chunk.len()))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            writer.write_le(&entry_chunks_lengths)?;
+            for chunk in entry_chunks {
+                writer.write_all(&chunk)?;
+            }
+        }
+
+        self.entries.clear();
+        self.size = 0;
+        Ok(())
+    }
+}
+
+/// Source archives stay open while writing, entries are often copied from the same one
+#[derive(Default)]
+struct AgtFileCache {
+    files: HashMap<PathBuf, BufReader<File>>,
+}
+
+impl AgtFileCache {
+    fn open<'a>(
+        &'a mut self,
+        source: &'a AgtFileSource,
+    ) -> anyhow::Result<XorReader<'a, &'a mut BufReader<File>>> {
+        let file = match self.files.entry(source.path.clone()) {
+            hash_map::Entry::Occupied(occupied) => occupied.into_mut(),
+            hash_map::Entry::Vacant(vacant) => {
+                let file = File::open(vacant.key())?;
+                vacant.insert(BufReader::new(file))
+            }
+        };
+        Ok(XorReader::new(file, &source.cipher, 32))
     }
 }
 
 pub struct AgtBuilder {
-    entry_sources: BTreeMap<String, AgtBuilderEntrySource>,
+    header: Header,
+    preserve_order: bool,
+    options: AgtBuilderOptions,
+    codepage: Codepage,
+    entries: Vec<AgtBuilderEntry>,
+    /// Index into `entries` by raw entry path, so paths that only look the same
+    /// after lossy decoding stay apart
+    entry_indices: HashMap<Vec<u8>, usize>,
 }
 
 impl AgtBuilder {
     pub fn new() -> Self {
         Self {
-            entry_sources: Default::default(),
-        }
-    }
-
-    // /// Add an entry that will be read from an agt file
-    // pub fn add_agt_entry(&mut self, agt_path: String, entry: Entry) {
-    //     self.entry_sources.insert(
-    //         entry.path.clone(),
-    //         AgtBuilderEntrySource::AgtFile {
-    //             path: agt_path,
-    //             entry,
-    //         },
-    //     );
-    // }
-    // /// Add an entry that will be read from a file
-    // pub fn add_entry_file(
-    //     &mut self,
-    //     entry_path: String,
-    //     file_path: String,
-    //     offset: usize,
-    //     size: usize,
-    // ) {
-    //     self.entry_sources.insert(
-    //         entry_path,
-    //         AgtBuilderEntrySource::File {
-    //             path: file_path,
-    //             offset,
-    //             size,
-    //         },
-    //     );
-    // }
+            header: Default::default(),
+            preserve_order: false,
+            options: Default::default(),
+            codepage: Default::default(),
+            entries: Default::default(),
+            entry_indices: Default::default(),
+        }
+    }
+
+    /// Start from an existing AGT file, keeping its header fields and entry order.
+    ///
+    /// Every entry is copied from the source archive without recompressing it,
+    /// so writing the builder as-is reproduces the original file. Entries shadowed by
+    /// an earlier one with the same path are kept in place, but adding or removing an
+    /// entry with that path only affects the earlier one the game reads.
+    pub fn from_agt<P: AsRef<Path>>(agt_path: P, cipher: &[u8]) -> anyhow::Result<Self> {
+        Self::from_agt_with_codepage(agt_path, cipher, Default::default())
+    }
+
+    /// Start from an existing AGT file whose entry paths are in the given codepage,
+    /// which is also used for entries added later
+    pub fn from_agt_with_codepage<P: AsRef<Path>>(
+        agt_path: P,
+        cipher: &[u8],
+        codepage: Codepage,
+    ) -> anyhow::Result<Self> {
+        let mut file = BufReader::new(File::open(agt_path.as_ref())?);
+        let mut agt_reader = AgtReader::new(&mut file, cipher);
+        agt_reader.set_codepage(codepage);
+
+        let header = agt_reader.read_header()?;
+        let entries = agt_reader.read_entries(header.file_count)?;
+
+        let mut builder = Self::new();
+        builder.set_codepage(codepage);
+        builder.set_header(header);
+        builder.set_preserve_order(true);
+
+        // Key of the first entry with each path
+        let mut seen_paths: HashMap<Vec<u8>, Vec<u8>> = HashMap::new();
+        for entry in entries {
+            // Like the game, the first entry wins if a path appears twice. Paths that don't
+            // decode in the codepage can't be normalized and only match their exact bytes.
+            let seen_path = match entry.decode_path(codepage) {
+                Ok(path) => normalize_entry_path(&path).into_bytes(),
+                Err(_) => entry.raw_path.clone(),
+            };
+            match seen_paths.entry(seen_path) {
+                hash_map::Entry::Occupied(occupied) => {
+                    let shadowed_by = occupied.get().clone();
+                    builder.entries.push(AgtBuilderEntry {
+                        path: entry.path.clone(),
+                        key: entry.raw_path.clone(),
+                        source: AgtBuilderEntrySource::AgtFile(AgtFileSource {
+                            path: agt_path.as_ref().to_owned(),
+                            cipher: cipher.to_vec(),
+                            entry,
+                        }),
+                        original: None,
+                        shadowed_by: Some(shadowed_by),
+                    });
+                }
+                hash_map::Entry::Vacant(vacant) => {
+                    vacant.insert(entry.raw_path.clone());
+                    builder.add_agt_entry(agt_path.as_ref(), cipher, entry);
+                }
+            }
+        }
+
+        Ok(builder)
+    }
+
+    /// Header to write, the file count is always filled in from the entries
+    pub fn set_header(&mut self, header: Header) {
+        self.header = header;
+    }
+
+    /// Write entries in insertion order instead of sorting them by path.
+    /// Replacing an existing entry keeps its position.
+    pub fn set_preserve_order(&mut self, preserve_order: bool) {
+        self.preserve_order = preserve_order;
+    }
+
+    /// Codepage new entry paths are encoded in, UTF-8 by default.
+    /// Paths of entries copied from an existing archive keep their original bytes.
+    ///
+    /// Entries are looked up by their encoded path, so set this before adding any.
+    pub fn set_codepage(&mut self, codepage: Codepage) {
+        self.codepage = codepage;
+    }
+
+    /// How new or changed entries are compressed
+    pub fn set_options(&mut self, options: AgtBuilderOptions) {
+        self.options = options;
+    }
+
+    /// Raw path bytes an entry path is looked up by.
+    ///
+    /// Paths the codepage can't represent fall back to their UTF-8 bytes, writing them fails.
+    fn entry_key(&self, path: &str) -> Vec<u8> {
+        self.codepage
+            .encode(path)
+            .unwrap_or_else(|_| path.as_bytes().to_vec())
+    }
+
+    fn insert(&mut self, path: String, key: Vec<u8>, source: AgtBuilderEntrySource) {
+        // Shadowed entries aren't indexed, but still take new data for their exact path
+        // rather than getting a second entry with it
+        let index = self.entry_indices.get(&key).copied().or_else(|| {
+            self.entries
+                .iter()
+                .position(|entry| entry.shadowed_by.is_some() && entry.key == key)
+        });
+
+        match index {
+            Some(index) => {
+                let existing = &mut self.entries[index];
+                let previous_source = std::mem::replace(&mut existing.source, source);
+
+                let original = match previous_source {
+                    AgtBuilderEntrySource::AgtFile(agt_file_source) => Some(agt_file_source),
+                    _ => existing.original.take(),
+                };
+                existing.original = match existing.source {
+                    AgtBuilderEntrySource::AgtFile(_) => None,
+                    _ => original,
+                };
+            }
+            None => {
+                self.entry_indices.insert(key.clone(), self.entries.len());
+                self.entries.push(AgtBuilderEntry {
+                    path,
+                    key,
+                    source,
+                    original: None,
+                    shadowed_by: None,
+                });
+            }
+        }
+    }
+
+    /// Whether an entry with exactly this path has been added
+    pub fn contains_entry(&self, path: &str) -> bool {
+        self.entry_indices.contains_key(&self.entry_key(path))
+    }
+
+    /// Paths of all added entries, in insertion order
+    pub fn entry_paths(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|entry| entry.path.as_str())
+    }
+
+    /// Remove a previously added entry along with any it shadows, returns whether it existed
+    pub fn remove_entry(&mut self, path: &str) -> bool {
+        let key = self.entry_key(path);
+        let Some(index) = self.entry_indices.remove(&key) else {
+            return false;
+        };
+
+        self.entries.remove(index);
+        self.entries
+            .retain(|entry| entry.shadowed_by.as_ref() != Some(&key));
+
+        self.entry_indices = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.shadowed_by.is_none())
+            .map(|(index, entry)| (entry.key.clone(), index))
+            .collect();
+        true
+    }
+
+    /// Add an entry that will be copied from an agt file without recompressing it.
+    /// It replaces an added entry with the same raw path bytes.
+    pub fn add_agt_entry<P: AsRef<Path>>(&mut self, agt_path: P, cipher: &[u8], entry: Entry) {
+        self.insert(
+            entry.path.clone(),
+            entry.raw_path.clone(),
+            AgtBuilderEntrySource::AgtFile(AgtFileSource {
+                path: agt_path.as_ref().to_owned(),
+                cipher: cipher.to_vec(),
+                entry,
+            }),
+        );
+    }
+
+    /// Add an entry that will be read from a file
+    pub fn add_entry_file<P: AsRef<Path>>(
+        &mut self,
+        entry_path: String,
+        file_path: P,
+        offset: u64,
+        size: u64,
+    ) {
+        let key = self.entry_key(&entry_path);
+        self.insert(
+            entry_path,
+            key,
+            AgtBuilderEntrySource::File {
+                path: file_path.as_ref().to_owned(),
+                offset,
+                size,
+            },
+        );
+    }
 
     /// Add an entry from memory
     pub fn add_entry_memory(&mut self, path: String, data: &[u8]) {
-        self.entry_sources.insert(
+        let key = self.entry_key(&path);
+        self.insert(
             path,
+            key,
             AgtBuilderEntrySource::Memory {
                 data: data.to_vec(),
             },
         );
     }
 
-    pub fn write<W: Write + Seek>(self, writer: &mut W, cipher: &[u8]) -> anyhow::Result<()> {
+    pub fn write<W: Write + Seek>(mut self, writer: &mut W, cipher: &[u8]) -> anyhow::Result<()> {
+        self.options.validate()?;
+
         let mut writer = XorWriter::new(writer, cipher, 32);
 
+        if !self.preserve_order {
+            self.entries.sort_by(|a, b| a.path.cmp(&b.path));
+        }
+
         writer.write_le(&Header {
-            file_count: self.entry_sources.len() as u32,
-            ..Default::default()
+            file_count: self.entries.len() as u32,
+            ..self.header
         })?;
 
         // Offsets to entries in the table of contents, for backfilling chunk offsets
         let mut entry_offsets = Vec::new();
-        // Chunk counts, to skip chunk lengths segment later
-        let mut chunk_counts = Vec::new();
 
-        for (new_entry_path, entry_source) in self.entry_sources.iter() {
+        for builder_entry in self.entries.iter() {
             // Record location where we're going to write this entry
             entry_offsets.push(writer.stream_position()?);
-            // Create an incomplete entry, store the chunk count and write the entry
-            let entry = entry_source.entry(new_entry_path.to_owned());
-            chunk_counts.push(entry.chunk_count);
+            // Create an incomplete entry and write it
+            let entry = builder_entry
+                .source
+                .entry(builder_entry.path.clone(), self.codepage)?;
             writer.write_le(&entry)?;
         }
 
         // Offsets to [chunk lengths, chunks], to fill in chunk offsets
-        let mut data_offsets = Vec::new();
-
-        // Go through all the entry data sources, compress and write the chunks, then backfill the chunk lengths
-        for (entry_source, entry_chunks_count) in self
-            .entry_sources
-            .into_values()
-            .zip(chunk_counts.into_iter())
-        {
-            // Record the position and skip the chunk lengths
-            let data_offset = writer.stream_position()?;
-            data_offsets.push(data_offset);
-            writer.seek(SeekFrom::Current(entry_chunks_count as i64 * 2))?;
-
-            // Compressed chunk lengths, for backfilling the chunk header
-            let mut entry_chunks_lengths = Vec::new();
+        let mut data_offsets = vec![0u64; self.entries.len()];
+
+        let mut agt_files = AgtFileCache::default();
+        let mut batch = CompressionBatch::default();
+
+        // Go through all the entry data sources, copying chunks from existing archives as-is and
+        // collecting everything else into batches that are compressed across threads
+        for (entry_index, builder_entry) in self.entries.into_iter().enumerate() {
+            // Fall back to the replaced entry's chunks if the data did not actually change
+            let entry_source = match builder_entry.original {
+                Some(original)
+                    if is_unchanged(&builder_entry.source, &original, &mut agt_files)? =>
+                {
+                    AgtBuilderEntrySource::AgtFile(original)
+                }
+                _ => builder_entry.source,
+            };
+
             match entry_source {
-                // AgtBuilderEntrySource::AgtFile { path, entry } => todo!(),
-                AgtBuilderEntrySource::Memory { mut data } => {
-                    for chunk in data.chunks_mut(16384) {
-                        let compressed_chunk = {
-                            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
-                            encoder.write_all(chunk)?;
-                            encoder.finish()?
-                        };
-
-                        let compressed_len = compressed_chunk.len() as u16;
-                        entry_chunks_lengths.push(compressed_len);
-                        writer.write_le(&compressed_chunk)?;
+                AgtBuilderEntrySource::AgtFile(source) => {
+                    // Entries must stay in order, write out everything queued before this one
+                    batch.flush(&self.options, &mut writer, &mut data_offsets)?;
+                    data_offsets[entry_index] = writer.stream_position()?;
+
+                    let mut reader = agt_files.open(&source)?;
+
+                    // Chunks are copied as-is, only the cipher position changes
+                    reader.seek(SeekFrom::Start(source.entry.chunks_offset as u64))?;
+                    let mut len_buf = vec![0u8; source.entry.chunk_count as usize * 2];
+                    reader.read_exact(&mut len_buf)?;
+                    writer.write_all(&len_buf)?;
+
+                    let compressed_length = len_buf
+                        .chunks_exact(2)
+                        .map(|len| u16::from_le_bytes([len[0], len[1]]) as u64)
+                        .sum::<u64>();
+                    let copied_length =
+                        std::io::copy(&mut (&mut reader).take(compressed_length), &mut writer)?;
+                    if copied_length != compressed_length {
+                        anyhow::bail!(
+                            "source archive ended while copying chunks for {}",
+                            source.entry.path
+                        );
                     }
                 }
+                AgtBuilderEntrySource::File { path, offset, size } => {
+                    let mut file = BufReader::new(File::open(path)?);
+                    file.seek(SeekFrom::Start(offset))?;
+
+                    let mut data = vec![0u8; size as usize];
+                    file.read_exact(&mut data)?;
+                    batch.push(entry_index, data);
+                }
+                AgtBuilderEntrySource::Memory { data } => {
+                    batch.push(entry_index, data);
+                }
+            }
+
+            if batch.is_full() {
+                batch.flush(&self.options, &mut writer, &mut data_offsets)?;
             }
-            // Backfill chunk header then return to position
-            let post_data_offset = writer.stream_position()?;
-            writer.seek(SeekFrom::Start(data_offset))?;
-            writer.write_le(&entry_chunks_lengths)?;
-            writer.seek(SeekFrom::Start(post_data_offset))?;
         }
+        batch.flush(&self.options, &mut writer, &mut data_offsets)?;
 
         // Backfill data offsets in entries
-        for (entry_offset, data_offset) in entry_offsets.into_iter().zip(data_offsets.into_iter()) {
+        for (entry_offset, data_offset) in entry_offsets.into_iter().zip(data_offsets) {
+            let data_offset = u32::try_from(data_offset).map_err(|_| {
+                anyhow::anyhow!(
+                    "entry data at {} is past the 4 GiB an archive can address",
+                    data_offset
+                )
+            })?;
             writer.seek(SeekFrom::Start(entry_offset))?;
-            writer.write_le(&(data_offset as u32))?;
+            writer.write_le(&data_offset)?;
         }
 
         Ok(())
     }
 }
 
+/// Check whether a new entry source holds exactly the same data as an entry in an existing AGT file
+fn is_unchanged(
+    source: &AgtBuilderEntrySource,
+    original: &AgtFileSource,
+    agt_files: &mut AgtFileCache,
+) -> anyhow::Result<bool> {
+    let (chunk_count, decompressed_length) = match source {
+        AgtBuilderEntrySource::AgtFile(_) => return Ok(false),
+        AgtBuilderEntrySource::File { size, .. } => (size.div_ceil(CHUNK_SIZE as u64), *size),
+        AgtBuilderEntrySource::Memory { data } => (
+            (data.len() as u64).div_ceil(CHUNK_SIZE as u64),
+            data.len() as u64,
+        ),
+    };
+    if decompressed_length != original.entry.decompressed_length as u64
+        || chunk_count != original.entry.chunk_count as u64
+    {
+        return Ok(false);
+    }
+
+    let mut original_reader = AgtEntryReader::new(agt_files.open(original)?, &original.entry)?;
+    let mut original_chunk = vec![0u8; CHUNK_SIZE];
+
+    match source {
+        AgtBuilderEntrySource::AgtFile(_) => Ok(false),
+        AgtBuilderEntrySource::File { path, offset, size } => {
+            let mut file = BufReader::new(File::open(path)?);
+            file.seek(SeekFrom::Start(*offset))?;
+            let mut file = file.take(*size);
+
+            let mut chunk = vec![0u8; CHUNK_SIZE];
+            let mut remaining = *size;
+            while remaining > 0 {
+                let chunk_length = remaining.min(CHUNK_SIZE as u64) as usize;
+                file.read_exact(&mut chunk[..chunk_length])?;
+                original_reader.read_exact(&mut original_chunk[..chunk_length])?;
+                if chunk[..chunk_length] != original_chunk[..chunk_length] {
+                    return Ok(false);
+                }
+                remaining -= chunk_length as u64;
+            }
+            Ok(true)
+        }
+        AgtBuilderEntrySource::Memory { data } => {
+            for chunk in data.chunks(CHUNK_SIZE) {
+                original_reader.read_exact(&mut original_chunk[..chunk.len()])?;
+                if chunk != &original_chunk[..chunk.len()] {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+    }
+}
+
 impl Default for AgtBuilder {
     fn default() -> Self {
         Self::new()
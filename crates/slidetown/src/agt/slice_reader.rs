@@ -0,0 +1,287 @@
+use flate2::read::ZlibDecoder;
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::parsers::agt::{Entry, Header};
+use crate::parsers::Codepage;
+
+use super::{
+    normalize_entry_path, AgtEntryReader, AgtReader, XorReader, CHUNK_SIZE, MAX_INFLATE_RATIO,
+};
+
+/// Read-only archive over data that is already in memory, e.g. a `Vec<u8>`, a `&[u8]` or
+/// a memory-mapped file.
+///
+/// The header and table of contents are decoded once up front. Every other method takes
+/// `&self`, so a single reader can be shared between threads and entries read concurrently
+/// without seeking a shared stream.
+pub struct AgtSliceReader<D: AsRef<[u8]>> {
+    data: D,
+    cipher: Vec<u8>,
+    header: Header,
+    entries: Vec<Entry>,
+    /// Index into `entries` by normalized path
+    entry_indices: HashMap<String, usize>,
+}
+
+#[cfg(feature = "mmap")]
+impl AgtSliceReader<memmap2::Mmap> {
+    /// Memory-map an archive file and decode its table of contents
+    pub fn open_mmap<P: AsRef<std::path::Path>>(
+        path: P,
+        cipher: &[u8],
+        codepage: Codepage,
+    ) -> anyhow::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        // Safety: the archive must not be modified while mapped, like any other mmap user
+        let data = unsafe { memmap2::Mmap::map(&file)? };
+        Self::with_codepage(data, cipher, codepage)
+    }
+}
+
+impl<D: AsRef<[u8]>> AgtSliceReader<D> {
+    pub fn new(data: D, cipher: &[u8]) -> anyhow::Result<Self> {
+        Self::with_codepage(data, cipher, Default::default())
+    }
+
+    /// Decode the table of contents with entry paths in the given codepage
+    pub fn with_codepage(data: D, cipher: &[u8], codepage: Codepage) -> anyhow::Result<Self> {
+        if cipher.is_empty() {
+            anyhow::bail!("cipher must not be empty");
+        }
+
+        let (header, entries) = {
+            let mut cursor = Cursor::new(data.as_ref());
+            let mut agt_reader = AgtReader::new(&mut cursor, cipher);
+            agt_reader.set_codepage(codepage);
+            let header = agt_reader.read_header()?;
+            let entries = agt_reader.read_entries_strict(header.file_count)?;
+            (header, entries)
+        };
+
+        let mut entry_indices = HashMap::with_capacity(entries.len());
+        for (index, entry) in entries.iter().enumerate() {
+            // Like the game, the first entry wins if a path appears twice
+            entry_indices
+                .entry(normalize_entry_path(&entry.path))
+                .or_insert(index);
+        }
+
+        Ok(Self {
+            data,
+            cipher: cipher.to_vec(),
+            header,
+            entries,
+            entry_indices,
+        })
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Look up an entry ignoring case and slash direction
+    pub fn find_entry(&self, path: &str) -> Option<&Entry> {
+        self.entry_indices
+            .get(&normalize_entry_path(path))
+            .map(|&index| &self.entries[index])
+    }
+
+    /// Open a streaming reader over the decompressed data for the given entry
+    pub fn open_entry(
+        &self,
+        entry: &Entry,
+    ) -> anyhow::Result<AgtEntryReader<XorReader<'_, Cursor<&[u8]>>>> {
+        AgtEntryReader::open(Cursor::new(self.data.as_ref()), &self.cipher, entry)
+    }
+
+    /// Decompress the data for the given entry on the calling thread
+    pub fn read_entry_data(&self, entry: &Entry) -> anyhow::Result<Vec<u8>> {
+        let chunk_ranges = self.chunk_ranges(entry)?;
+
+        // Grown chunk by chunk, so a bogus length fails before it is allocated in full
+        let mut result = Vec::new();
+        let mut buf = Vec::new();
+        for (chunk_index, &chunk_range) in chunk_ranges.iter().enumerate() {
+            let chunk_start = result.len();
+            let chunk_length = (entry.decompressed_length as usize - chunk_start).min(CHUNK_SIZE);
+            result.resize(chunk_start + chunk_length, 0);
+
+            self.decompress_chunk(chunk_range, &mut result[chunk_start..], &mut buf)
+                .map_err(|e| {
+                    anyhow::anyhow!("entry {} chunk {}: {}", entry.path, chunk_index, e)
+                })?;
+        }
+
+        Ok(result)
+    }
+
+    /// Absolute (offset, length) of every compressed chunk of an entry
+    fn chunk_ranges(&self, entry: &Entry) -> anyhow::Result<Vec<(usize, usize)>> {
+        let expected_chunk_count = (entry.decompressed_length as usize).div_ceil(CHUNK_SIZE);
+        if entry.chunk_count as usize != expected_chunk_count {
+            anyhow::bail!(
+                "entry {} has {} chunk(s), expected {} for {} bytes",
+                entry.path,
+                entry.chunk_count,
+                expected_chunk_count,
+                entry.decompressed_length
+            );
+        }
+
+        let mut len_buf = Vec::new();
+        self.decrypt(
+            entry.chunks_offset as usize,
+            entry.chunk_count as usize * 2,
+            &mut len_buf,
+        )?;
+
+        let mut chunk_offset = entry.chunks_offset as usize + len_buf.len();
+        let chunk_ranges = len_buf
+            .chunks_exact(2)
+            .map(|len| {
+                let len = u16::from_le_bytes([len[0], len[1]]) as usize;
+                let range = (chunk_offset, len);
+                chunk_offset += len;
+                range
+            })
+            .collect::<Vec<_>>();
+
+        // Check what the table of contents claims before anything is allocated for it
+        if chunk_offset > self.data.as_ref().len() {
+            anyhow::bail!(
+                "entry {} chunks end at {}, past the end of the {} byte archive",
+                entry.path,
+                chunk_offset,
+                self.data.as_ref().len()
+            );
+        }
+        let compressed_length = chunk_offset - entry.chunks_offset as usize - len_buf.len();
+        if entry.decompressed_length as u64 > compressed_length as u64 * MAX_INFLATE_RATIO {
+            anyhow::bail!(
+                "entry {} claims {} bytes, more than its {} compressed bytes can hold",
+                entry.path,
+                entry.decompressed_length,
+                compressed_length
+            );
+        }
+
+        Ok(chunk_ranges)
+    }
+
+    /// Decrypt and decompress one chunk into `out`, which must be exactly its decompressed size
+    fn decompress_chunk(
+        &self,
+        (offset, length): (usize, usize),
+        out: &mut [u8],
+        buf: &mut Vec<u8>,
+    ) -> anyhow::Result<()> {
+        self.decrypt(offset, length, buf)?;
+
+        let mut decoder = ZlibDecoder::new(buf.as_slice());
+        decoder
+            .read_exact(out)
+            .map_err(|e| anyhow::anyhow!("decompressing failed: {}", e))?;
+        if decoder.read(&mut [0u8])? != 0 {
+            anyhow::bail!("decompressed to more than {} bytes", out.len());
+        }
+
+        Ok(())
+    }
+
+    /// Copy and decrypt a range of the archive into `buf`
+    fn decrypt(&self, offset: usize, length: usize, buf: &mut Vec<u8>) -> anyhow::Result<()> {
+        let data = self.data.as_ref();
+        let Some(encrypted) = offset
+            .checked_add(length)
+            .and_then(|end| data.get(offset..end))
+        else {
+            anyhow::bail!(
+                "range {}..{} is outside the {} byte archive",
+                offset,
+                offset.saturating_add(length),
+                data.len()
+            );
+        };
+
+        let cipher_len = self.cipher.len();
+        buf.clear();
+        buf.extend(
+            encrypted
+                .iter()
+                .enumerate()
+                .map(|(i, byte)| byte ^ self.cipher[(offset + i) % cipher_len]),
+        );
+
+        Ok(())
+    }
+}
+
+impl<D: AsRef<[u8]> + Sync> AgtSliceReader<D> {
+    /// Decompress the data for the given entry, spreading its chunks over up to `threads`
+    /// threads, 0 uses every core
+    pub fn read_entry_data_threaded(
+        &self,
+        entry: &Entry,
+        threads: usize,
+    ) -> anyhow::Result<Vec<u8>> {
+        let thread_count = match threads {
+            0 => std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+            threads => threads,
+        }
+        .min(entry.chunk_count as usize);
+        if thread_count <= 1 {
+            return self.read_entry_data(entry);
+        }
+
+        let chunk_ranges = self.chunk_ranges(entry)?;
+        let mut result = vec![0u8; entry.decompressed_length as usize];
+
+        // Threads take the next chunk as they go, each writing straight into its part of the result
+        let next_chunk = AtomicUsize::new(0);
+        let out_chunks = result
+            .chunks_mut(CHUNK_SIZE)
+            .map(Mutex::new)
+            .collect::<Vec<_>>();
+
+        std::thread::scope(|scope| -> anyhow::Result<()> {
+            let workers = (0..thread_count)
+                .map(|_| {
+                    scope.spawn(|| -> anyhow::Result<()> {
+                        let mut buf = Vec::new();
+                        loop {
+                            let chunk_index = next_chunk.fetch_add(1, Ordering::Relaxed);
+                            let Some(out_chunk) = out_chunks.get(chunk_index) else {
+                                return Ok(());
+                            };
+                            let mut out_chunk = out_chunk.lock().unwrap();
+                            self.decompress_chunk(
+                                chunk_ranges[chunk_index],
+                                &mut out_chunk,
+                                &mut buf,
+                            )
+                            .map_err(|e| {
+                                anyhow::anyhow!("entry {} chunk {}: {}", entry.path, chunk_index, e)
+                            })?;
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            for worker in workers {
+                worker
+                    .join()
+                    .map_err(|_| anyhow::anyhow!("decompression thread panicked"))??;
+            }
+            Ok(())
+        })?;
+
+        Ok(result)
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::{hash_map, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::parsers::Codepage;
+
+use super::{normalize_entry_path, AgtEditor, AgtReader, DiffEntry, CHUNK_SIZE};
+
+/// Loose file that has no entry in the archive yet
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncFile {
+    /// Entry path the file would be stored under
+    pub path: String,
+    pub file_path: PathBuf,
+    pub length: u64,
+    /// SHA-1 of the file contents, in hex
+    pub hash: String,
+}
+
+/// Archive entry whose loose file has different content
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncChange {
+    /// Path as stored in the archive
+    pub path: String,
+    pub file_path: PathBuf,
+    pub entry_length: u32,
+    pub file_length: u64,
+    pub entry_hash: String,
+    pub file_hash: String,
+}
+
+/// Differences between an archive and a set of loose files, e.g. an extracted copy
+#[derive(Debug, Default, Serialize)]
+pub struct SyncReport {
+    /// Entries whose file differs, in archive order
+    pub changed: Vec<SyncChange>,
+    /// Entries without a file, in archive order
+    pub missing: Vec<DiffEntry>,
+    /// Files without an entry, in the order they were given
+    pub new: Vec<SyncFile>,
+    pub unchanged: usize,
+}
+
+impl SyncReport {
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.missing.is_empty() && self.new.is_empty()
+    }
+}
+
+/// SHA-1 and length of a file's contents
+fn hash_file(path: &Path) -> anyhow::Result<(String, u64)> {
+    let mut file = BufReader::new(File::open(path)?);
+
+    let mut hasher = sha1_smol::Sha1::new();
+    let mut length = 0u64;
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    loop {
+        let bytes_read = file.read(&mut buf)?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buf[..bytes_read]);
+        length += bytes_read as u64;
+    }
+
+    if length > u32::MAX as u64 {
+        anyhow::bail!("{} is too large for an archive entry", path.display());
+    }
+
+    Ok((hasher.digest().to_string(), length))
+}
+
+/// Compare an archive with loose files given as (entry path, file path) pairs.
+///
+/// Entries and files are matched by path regardless of case and slashes and compared by
+/// the hash of their decompressed content.
+pub fn sync_agt<P: AsRef<Path>>(
+    archive_path: P,
+    cipher: &[u8],
+    codepage: Codepage,
+    files: impl IntoIterator<Item = (String, PathBuf)>,
+) -> anyhow::Result<SyncReport> {
+    let mut files_by_path = HashMap::new();
+    let mut file_order = Vec::new();
+    for (entry_path, file_path) in files {
+        match files_by_path.entry(normalize_entry_path(&entry_path)) {
+            hash_map::Entry::Occupied(occupied) => {
+                let (_, existing_file_path): &(String, PathBuf) = occupied.get();
+                anyhow::bail!(
+                    "{} and {} both map to entry {}",
+                    existing_file_path.display(),
+                    file_path.display(),
+                    entry_path
+                );
+            }
+            hash_map::Entry::Vacant(vacant) => {
+                file_order.push(vacant.key().clone());
+                vacant.insert((entry_path, file_path));
+            }
+        }
+    }
+
+    let mut archive_file = BufReader::new(File::open(archive_path)?);
+    let mut agt_reader = AgtReader::new(&mut archive_file, cipher);
+    agt_reader.set_codepage(codepage);
+
+    let header = agt_reader.read_header()?;
+    let entries = agt_reader.read_entries_strict(header.file_count)?;
+
+    let mut report = SyncReport::default();
+    let mut seen_paths = HashSet::new();
+
+    for entry in entries {
+        let normalized_path = normalize_entry_path(&entry.path);
+        // Only the first of several entries with the same path is ever read by the game
+        if !seen_paths.insert(normalized_path.clone()) {
+            continue;
+        }
+        let entry_hash = agt_reader.hash_entry(&entry)?;
+
+        let Some((_, file_path)) = files_by_path.remove(&normalized_path) else {
+            report.missing.push(DiffEntry {
+                path: entry.path,
+                decompressed_length: entry.decompressed_length,
+                hash: entry_hash,
+            });
+            continue;
+        };
+
+        let (file_hash, file_length) = hash_file(&file_path)?;
+        if file_hash == entry_hash {
+            report.unchanged += 1;
+        } else {
+            report.changed.push(SyncChange {
+                path: entry.path,
+                file_path,
+                entry_length: entry.decompressed_length,
+                file_length,
+                entry_hash,
+                file_hash,
+            });
+        }
+    }
+
+    for normalized_path in file_order {
+        if let Some((entry_path, file_path)) = files_by_path.remove(&normalized_path) {
+            let (hash, length) = hash_file(&file_path)?;
+            report.new.push(SyncFile {
+                path: entry_path,
+                file_path,
+                length,
+                hash,
+            });
+        }
+    }
+
+    Ok(report)
+}
+
+impl AgtEditor {
+    /// Queue the differences found by [`sync_agt`] for this archive.
+    ///
+    /// Changed entries are replaced in place and new files are appended, so entry order and
+    /// the chunks of unchanged entries are kept. Missing entries are only removed if
+    /// `remove_missing` is set.
+    pub fn apply_sync(&mut self, report: &SyncReport, remove_missing: bool) -> anyhow::Result<()> {
+        for changed in report.changed.iter() {
+            self.replace_file(&changed.path, &changed.file_path)?;
+        }
+
+        for new in report.new.iter() {
+            self.add_file(new.path.clone(), &new.file_path)?;
+        }
+
+        if remove_missing {
+            for missing in report.missing.iter() {
+                self.remove(&missing.path)?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,189 @@
+use binrw::io::{Read, Seek, SeekFrom};
+use flate2::read::ZlibDecoder;
+use serde::Serialize;
+
+use crate::parsers::agt::{Entry, Header};
+
+use super::{AgtReader, CHUNK_SIZE};
+
+/// Result of checking every entry of an archive
+#[derive(Debug, Serialize)]
+pub struct VerifyReport {
+    pub file_length: u64,
+    pub header: Header,
+    /// Problems with the archive as a whole, like a truncated table of contents
+    pub errors: Vec<String>,
+    pub entries: Vec<EntryReport>,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty() && self.entries.iter().all(EntryReport::is_ok)
+    }
+
+    pub fn failed_entries(&self) -> impl Iterator<Item = &EntryReport> {
+        self.entries.iter().filter(|entry| !entry.is_ok())
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct EntryReport {
+    pub path: String,
+    pub chunks_offset: u32,
+    pub chunk_count: u32,
+    pub decompressed_length: u32,
+    /// Everything wrong with the entry, empty if it is intact
+    pub errors: Vec<String>,
+}
+
+impl EntryReport {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+impl<'cipher, 'reader, T: Read + Seek> AgtReader<'cipher, 'reader, T> {
+    /// Check that every entry points inside the file and decompresses to its stated length.
+    ///
+    /// Problems are collected into the report rather than returned as errors, only an
+    /// unreadable header or I/O failure fails the whole call.
+    pub fn verify(&mut self) -> anyhow::Result<VerifyReport> {
+        let file_length = self.reader.seek(SeekFrom::End(0))?;
+        let header = self.read_header()?;
+
+        let mut report = VerifyReport {
+            file_length,
+            header,
+            errors: Vec::new(),
+            entries: Vec::new(),
+        };
+
+        // Parse entries one at a time so a damaged table still reports the ones before it
+        let mut entries = Vec::new();
+        self.reader.seek(SeekFrom::Start(32))?;
+        for entry_index in 0..report.header.file_count {
+            match Entry::parse(&mut self.reader, self.codepage) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => {
+                    let cause = match e.downcast_ref::<binrw::Error>() {
+                        Some(binrw_error) => binrw_error.root_cause().to_string(),
+                        None => e.to_string(),
+                    };
+                    report.errors.push(format!(
+                        "table of contents is damaged at entry {} of {}: {}",
+                        entry_index, report.header.file_count, cause
+                    ));
+                    break;
+                }
+            }
+        }
+        // Where entry data may start, unknown past the header if the table is damaged
+        let toc_end = if report.errors.is_empty() {
+            self.reader.stream_position()?
+        } else {
+            32
+        };
+
+        for entry in entries {
+            let errors = self.verify_entry(&entry, toc_end, file_length)?;
+            report.entries.push(EntryReport {
+                path: entry.path,
+                chunks_offset: entry.chunks_offset,
+                chunk_count: entry.chunk_count,
+                decompressed_length: entry.decompressed_length,
+                errors,
+            });
+        }
+
+        Ok(report)
+    }
+
+    fn verify_entry(
+        &mut self,
+        entry: &Entry,
+        toc_end: u64,
+        file_length: u64,
+    ) -> anyhow::Result<Vec<String>> {
+        let mut errors = Vec::new();
+
+        let expected_chunk_count = (entry.decompressed_length as u64).div_ceil(CHUNK_SIZE as u64);
+        if entry.chunk_count as u64 != expected_chunk_count {
+            errors.push(format!(
+                "has {} chunk(s) but {} bytes need {}",
+                entry.chunk_count, entry.decompressed_length, expected_chunk_count
+            ));
+        }
+
+        let chunks_offset = entry.chunks_offset as u64;
+        let lengths_end = chunks_offset + entry.chunk_count as u64 * 2;
+        if chunks_offset < toc_end || lengths_end > file_length {
+            errors.push(format!(
+                "chunk lengths at {}..{} are outside the data area {}..{}",
+                chunks_offset, lengths_end, toc_end, file_length
+            ));
+            return Ok(errors);
+        }
+
+        self.reader.seek(SeekFrom::Start(chunks_offset))?;
+        let mut len_buf = vec![0u8; entry.chunk_count as usize * 2];
+        self.reader.read_exact(&mut len_buf)?;
+        let chunk_lengths = len_buf
+            .chunks_exact(2)
+            .map(|len| u16::from_le_bytes([len[0], len[1]]) as u64)
+            .collect::<Vec<_>>();
+
+        let data_end = lengths_end + chunk_lengths.iter().sum::<u64>();
+        if data_end > file_length {
+            errors.push(format!(
+                "chunks end at {}, past the end of the file at {}",
+                data_end, file_length
+            ));
+            return Ok(errors);
+        }
+
+        let mut chunk_offset = lengths_end;
+        let mut total_length = 0u64;
+        let mut chunk_data = Vec::with_capacity(CHUNK_SIZE);
+        for (chunk_index, &chunk_length) in chunk_lengths.iter().enumerate() {
+            self.reader.seek(SeekFrom::Start(chunk_offset))?;
+            chunk_offset += chunk_length;
+
+            // Read one byte more than a chunk may hold so oversized chunks are noticed
+            chunk_data.clear();
+            let decoder = ZlibDecoder::new((&mut self.reader).take(chunk_length));
+            if let Err(e) = decoder
+                .take(CHUNK_SIZE as u64 + 1)
+                .read_to_end(&mut chunk_data)
+            {
+                errors.push(format!(
+                    "chunk {} is not valid zlib data: {}",
+                    chunk_index, e
+                ));
+                continue;
+            }
+
+            let chunk_start = chunk_index as u64 * CHUNK_SIZE as u64;
+            let expected_length = (entry.decompressed_length as u64)
+                .saturating_sub(chunk_start)
+                .min(CHUNK_SIZE as u64);
+            if chunk_data.len() as u64 != expected_length {
+                errors.push(format!(
+                    "chunk {} decompressed to {} bytes, expected {}",
+                    chunk_index,
+                    chunk_data.len(),
+                    expected_length
+                ));
+            }
+            total_length += chunk_data.len() as u64;
+        }
+
+        if total_length != entry.decompressed_length as u64 && errors.is_empty() {
+            errors.push(format!(
+                "decompressed to {} bytes, expected {}",
+                total_length, entry.decompressed_length
+            ));
+        }
+
+        Ok(errors)
+    }
+}
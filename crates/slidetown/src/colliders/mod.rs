@@ -0,0 +1,173 @@
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use serde::Serialize;
+
+use crate::parsers::loi::{Collider, Vec3f};
+
+/// Capsules store no usable radius, their `size` is left uninitialized
+pub const CAPSULE_RADIUS: f32 = 0.5;
+const CAPSULE_SEGMENTS: u16 = 16;
+const CAPSULE_RINGS: u16 = 4;
+
+/// Shape of a collider as far as its type is understood
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColliderShape {
+    /// Types 1 and 2, spanning `size` around the collider's position
+    Box { half_size: Vec3f },
+    /// Type 4, assumed to run along Z with `unknown5` as half the height of the cylinder
+    /// between the caps, with a fixed radius
+    Capsule { radius: f32, half_height: f32 },
+}
+
+impl ColliderShape {
+    /// Shape of a collider, or none if its type is unknown
+    pub fn new(collider: &Collider) -> Option<Self> {
+        match collider.r#type {
+            1 | 2 => {
+                let (x, y, z) = collider.size;
+                Some(Self::Box {
+                    half_size: (x / 2.0, y / 2.0, z / 2.0),
+                })
+            }
+            4 => Some(Self::Capsule {
+                radius: CAPSULE_RADIUS,
+                half_height: collider.unknown5,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Triangle mesh of a collider around its own origin, before rotation and translation
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColliderMesh {
+    pub vertices: Vec<Vec3f>,
+    pub triangles: Vec<[u16; 3]>,
+}
+
+impl ColliderMesh {
+    /// Mesh of a collider, or none if its type is unknown
+    pub fn new(collider: &Collider) -> Option<Self> {
+        Some(match ColliderShape::new(collider)? {
+            ColliderShape::Box { half_size } => box_mesh(half_size),
+            ColliderShape::Capsule {
+                radius,
+                half_height,
+            } => capsule_mesh(radius, half_height),
+        })
+    }
+
+    /// Vertices rotated and moved into place in the world
+    pub fn world_vertices(&self, collider: &Collider) -> Vec<Vec3f> {
+        let (x, y, z) = collider.rotation;
+        let dot =
+            |row: Vec3f, vertex: Vec3f| row.0 * vertex.0 + row.1 * vertex.1 + row.2 * vertex.2;
+        let position = collider.position;
+
+        self.vertices
+            .iter()
+            .map(|&vertex| {
+                (
+                    dot(x, vertex) + position.0,
+                    dot(y, vertex) + position.1,
+                    dot(z, vertex) + position.2,
+                )
+            })
+            .collect()
+    }
+}
+
+/// Collider fields exported alongside its mesh, e.g. as glTF extras
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ColliderExtras {
+    pub object_index: u32,
+    pub collider_index: u32,
+    pub r#type: u32,
+}
+
+impl From<&Collider> for ColliderExtras {
+    fn from(collider: &Collider) -> Self {
+        Self {
+            object_index: collider.object_index,
+            collider_index: collider.collider_index,
+            r#type: collider.r#type,
+        }
+    }
+}
+
+pub fn collider_name(collider: &Collider) -> String {
+    format!(
+        "Object{}Collider{}",
+        collider.object_index, collider.collider_index
+    )
+}
+
+fn box_mesh(half_size: Vec3f) -> ColliderMesh {
+    // Corner i has its x, y and z at the positive side if bit 0, 1 and 2 are set
+    let vertices = (0..8)
+        .map(|corner| {
+            let sign = |bit: u32| if corner & (1 << bit) != 0 { 1.0 } else { -1.0 };
+            (
+                half_size.0 * sign(0),
+                half_size.1 * sign(1),
+                half_size.2 * sign(2),
+            )
+        })
+        .collect();
+
+    let faces: [[u16; 4]; 6] = [
+        [0, 4, 6, 2],
+        [1, 3, 7, 5],
+        [0, 1, 5, 4],
+        [2, 6, 7, 3],
+        [0, 2, 3, 1],
+        [4, 5, 7, 6],
+    ];
+    let triangles = faces
+        .iter()
+        .flat_map(|&[a, b, c, d]| [[a, b, c], [a, c, d]])
+        .collect();
+
+    ColliderMesh {
+        vertices,
+        triangles,
+    }
+}
+
+fn capsule_mesh(radius: f32, half_height: f32) -> ColliderMesh {
+    // Rings of vertices from the bottom pole to the top pole, the two equator rings
+    // are joined by the cylinder
+    let mut vertices = Vec::new();
+    for (cap_z, first_latitude) in [(-half_height, -FRAC_PI_2), (half_height, 0.0)] {
+        for ring in 0..=CAPSULE_RINGS {
+            let latitude = first_latitude + FRAC_PI_2 * ring as f32 / CAPSULE_RINGS as f32;
+            for segment in 0..CAPSULE_SEGMENTS {
+                let longitude = TAU * segment as f32 / CAPSULE_SEGMENTS as f32;
+                vertices.push((
+                    radius * latitude.cos() * longitude.cos(),
+                    radius * latitude.cos() * longitude.sin(),
+                    cap_z + radius * latitude.sin(),
+                ));
+            }
+        }
+    }
+
+    let ring_count = 2 * (CAPSULE_RINGS + 1);
+    let mut triangles = Vec::new();
+    for ring in 0..ring_count - 1 {
+        for segment in 0..CAPSULE_SEGMENTS {
+            let next_segment = (segment + 1) % CAPSULE_SEGMENTS;
+            let index = |ring: u16, segment: u16| ring * CAPSULE_SEGMENTS + segment;
+
+            let (a, b) = (index(ring, segment), index(ring, next_segment));
+            let (c, d) = (index(ring + 1, next_segment), index(ring + 1, segment));
+            triangles.push([a, b, c]);
+            triangles.push([a, c, d]);
+        }
+    }
+
+    ColliderMesh {
+        vertices,
+        triangles,
+    }
+}
@@ -0,0 +1,537 @@
+use crate::parsers::hit::Hit;
+
+pub type Point = (f32, f32, f32);
+
+/// Triangles per leaf, small enough that leaves are cheap to test one by one
+const MAX_LEAF_TRIANGLES: usize = 4;
+
+/// Tolerance for rays passing through triangle edges
+const EPSILON: f32 = 1e-6;
+
+/// Coordinate axis, e.g. to sample heights along
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    fn index(self) -> usize {
+        match self {
+            Axis::X => 0,
+            Axis::Y => 1,
+            Axis::Z => 2,
+        }
+    }
+}
+
+/// Where a ray first meets the collision area
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    /// Distance along the ray, in multiples of its direction's length
+    pub distance: f32,
+    pub point: Point,
+    /// Index of the triangle in [`Hit::triangles`] order
+    pub triangle_index: usize,
+}
+
+/// Point of the collision area nearest to a query point
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClosestPoint {
+    pub distance: f32,
+    pub point: Point,
+    /// Index of the triangle in [`Hit::triangles`] order
+    pub triangle_index: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bounds {
+    min: [f32; 3],
+    max: [f32; 3],
+}
+
+impl Bounds {
+    const EMPTY: Self = Self {
+        min: [f32::MAX; 3],
+        max: [f32::MIN; 3],
+    };
+
+    fn grow(&mut self, point: [f32; 3]) {
+        self.min = std::array::from_fn(|axis| self.min[axis].min(point[axis]));
+        self.max = std::array::from_fn(|axis| self.max[axis].max(point[axis]));
+    }
+
+    fn longest_axis(&self) -> usize {
+        let extent = sub(self.max, self.min);
+        (0..3)
+            .max_by(|&a, &b| extent[a].total_cmp(&extent[b]))
+            .unwrap_or(0)
+    }
+
+    /// Distance along the ray where it enters the box, if it does within `max_distance`
+    fn ray_entry(
+        &self,
+        origin: [f32; 3],
+        inverse_direction: [f32; 3],
+        max_distance: f32,
+    ) -> Option<f32> {
+        let (mut near, mut far) = (0.0f32, max_distance);
+        for axis in 0..3 {
+            let t0 = (self.min[axis] - origin[axis]) * inverse_direction[axis];
+            let t1 = (self.max[axis] - origin[axis]) * inverse_direction[axis];
+            // NaN comes from a ray lying in one of the box's planes, which counts as inside
+            let (t0, t1) = if t0 <= t1 { (t0, t1) } else { (t1, t0) };
+            if !t0.is_nan() {
+                near = near.max(t0);
+            }
+            if !t1.is_nan() {
+                far = far.min(t1);
+            }
+        }
+        (near <= far).then_some(near)
+    }
+
+    fn distance_squared(&self, point: [f32; 3]) -> f32 {
+        (0..3)
+            .map(|axis| {
+                let outside = (self.min[axis] - point[axis])
+                    .max(point[axis] - self.max[axis])
+                    .max(0.0);
+                outside * outside
+            })
+            .sum()
+    }
+
+    fn contains_xy(&self, x: f32, y: f32) -> bool {
+        x >= self.min[0] && x <= self.max[0] && y >= self.min[1] && y <= self.max[1]
+    }
+}
+
+#[derive(Debug)]
+enum NodeKind {
+    /// Range of `HitBvh::triangles`
+    Leaf { start: usize, count: usize },
+    /// Indices of both children in `HitBvh::nodes`
+    Inner { left: usize, right: usize },
+}
+
+#[derive(Debug)]
+struct Node {
+    bounds: Bounds,
+    kind: NodeKind,
+}
+
+#[derive(Debug)]
+struct Triangle {
+    vertices: [[f32; 3]; 3],
+    /// Position in the `Hit`, as triangles are reordered while building
+    index: usize,
+}
+
+/// Bounding volume hierarchy over the triangles of a [`Hit`] collision area, for spatial
+/// queries such as ground height or whether a position is on the track.
+///
+/// Triangles without any area are left out as nothing can touch them.
+#[derive(Debug)]
+pub struct HitBvh {
+    triangles: Vec<Triangle>,
+    nodes: Vec<Node>,
+}
+
+impl HitBvh {
+    /// Build the hierarchy, failing if the area has incomplete triangles or indices
+    /// past its vertices
+    pub fn new(hit: &Hit) -> anyhow::Result<Self> {
+        if !hit.indices.len().is_multiple_of(3) {
+            anyhow::bail!(
+                "{} indices do not make up whole triangles",
+                hit.indices.len()
+            );
+        }
+
+        let mut triangles = Vec::with_capacity(hit.indices.len() / 3);
+        for (index, triangle) in hit.triangles().enumerate() {
+            let mut vertices = [[0.0; 3]; 3];
+            for (vertex, &vert_index) in vertices.iter_mut().zip(triangle.iter()) {
+                let Some(&(x, y, z)) = hit.verts.get(vert_index as usize) else {
+                    anyhow::bail!(
+                        "triangle {} refers to vertex {} of {}",
+                        index,
+                        vert_index,
+                        hit.verts.len()
+                    );
+                };
+                *vertex = [x, y, z];
+            }
+
+            let [a, b, c] = vertices;
+            let normal = cross(sub(b, a), sub(c, a));
+            if dot(normal, normal) > 0.0 {
+                triangles.push(Triangle { vertices, index });
+            }
+        }
+
+        let mut bvh = Self {
+            triangles,
+            nodes: Vec::new(),
+        };
+        if !bvh.triangles.is_empty() {
+            bvh.build(0, bvh.triangles.len());
+        }
+
+        Ok(bvh)
+    }
+
+    /// Number of triangles that can be queried
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    /// Corners of the box around every triangle, or none if there aren't any
+    pub fn bounds(&self) -> Option<(Point, Point)> {
+        self.nodes
+            .first()
+            .map(|root| (to_point(root.bounds.min), to_point(root.bounds.max)))
+    }
+
+    /// Add a node for a range of triangles and everything under it, returning its index
+    fn build(&mut self, start: usize, count: usize) -> usize {
+        let triangles = &mut self.triangles[start..start + count];
+
+        let mut bounds = Bounds::EMPTY;
+        let mut centroid_bounds = Bounds::EMPTY;
+        for triangle in triangles.iter() {
+            for &vertex in triangle.vertices.iter() {
+                bounds.grow(vertex);
+            }
+            centroid_bounds.grow(centroid(triangle));
+        }
+
+        let node_index = self.nodes.len();
+        self.nodes.push(Node {
+            bounds,
+            kind: NodeKind::Leaf { start, count },
+        });
+        if count <= MAX_LEAF_TRIANGLES {
+            return node_index;
+        }
+
+        // Split at the median along the axis the triangles are most spread out on
+        let axis = centroid_bounds.longest_axis();
+        let half = count / 2;
+        triangles
+            .select_nth_unstable_by(half, |a, b| centroid(a)[axis].total_cmp(&centroid(b)[axis]));
+
+        let left = self.build(start, half);
+        let right = self.build(start + half, count - half);
+        self.nodes[node_index].kind = NodeKind::Inner { left, right };
+
+        node_index
+    }
+
+    /// Nearest triangle along a ray within `max_distance`, both sides of triangles count.
+    ///
+    /// `direction` doesn't need to be normalized, distances are in multiples of its length.
+    pub fn raycast(&self, origin: Point, direction: Point, max_distance: f32) -> Option<RayHit> {
+        let origin = from_point(origin);
+        let direction = from_point(direction);
+        let inverse_direction = direction.map(|component| 1.0 / component);
+
+        let mut nearest: Option<(f32, &Triangle)> = None;
+        let mut stack = Vec::new();
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+
+        while let Some(node_index) = stack.pop() {
+            let limit = nearest.map_or(max_distance, |(distance, _)| distance);
+            let node = &self.nodes[node_index];
+            if node
+                .bounds
+                .ray_entry(origin, inverse_direction, limit)
+                .is_none()
+            {
+                continue;
+            }
+
+            match node.kind {
+                NodeKind::Leaf { start, count } => {
+                    for triangle in self.triangles[start..start + count].iter() {
+                        let limit = nearest.map_or(max_distance, |(distance, _)| distance);
+                        if let Some(distance) = ray_triangle(origin, direction, triangle) {
+                            if distance <= limit {
+                                nearest = Some((distance, triangle));
+                            }
+                        }
+                    }
+                }
+                NodeKind::Inner { left, right } => {
+                    // Visit the child the ray enters first next, so it can prune the other one
+                    let entry = |child: usize| {
+                        self.nodes[child]
+                            .bounds
+                            .ray_entry(origin, inverse_direction, limit)
+                    };
+                    match (entry(left), entry(right)) {
+                        (Some(l), Some(r)) if l <= r => stack.extend([right, left]),
+                        (Some(_), Some(_)) => stack.extend([left, right]),
+                        (Some(_), None) => stack.push(left),
+                        (None, Some(_)) => stack.push(right),
+                        (None, None) => {}
+                    }
+                }
+            }
+        }
+
+        nearest.map(|(distance, triangle)| RayHit {
+            distance,
+            point: to_point(add(origin, scale(direction, distance))),
+            triangle_index: triangle.index,
+        })
+    }
+
+    /// Nearest point on any triangle to `point`
+    pub fn closest_point(&self, point: Point) -> Option<ClosestPoint> {
+        let point = from_point(point);
+
+        let mut nearest: Option<(f32, [f32; 3], &Triangle)> = None;
+        let mut stack = Vec::new();
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+
+        while let Some(node_index) = stack.pop() {
+            let limit = nearest.map_or(f32::MAX, |(distance_squared, _, _)| distance_squared);
+            let node = &self.nodes[node_index];
+            if node.bounds.distance_squared(point) > limit {
+                continue;
+            }
+
+            match node.kind {
+                NodeKind::Leaf { start, count } => {
+                    for triangle in self.triangles[start..start + count].iter() {
+                        let candidate = closest_point_on_triangle(point, &triangle.vertices);
+                        let offset = sub(candidate, point);
+                        let distance_squared = dot(offset, offset);
+                        if nearest.is_none_or(|(nearest_squared, _, _)| {
+                            distance_squared < nearest_squared
+                        }) {
+                            nearest = Some((distance_squared, candidate, triangle));
+                        }
+                    }
+                }
+                NodeKind::Inner { left, right } => {
+                    let distance = |child: usize| self.nodes[child].bounds.distance_squared(point);
+                    if distance(left) <= distance(right) {
+                        stack.extend([right, left]);
+                    } else {
+                        stack.extend([left, right]);
+                    }
+                }
+            }
+        }
+
+        nearest.map(|(distance_squared, point, triangle)| ClosestPoint {
+            distance: distance_squared.sqrt(),
+            point: to_point(point),
+            triangle_index: triangle.index,
+        })
+    }
+
+    /// Highest surface along `axis` on the line through `position` parallel to it, e.g.
+    /// the ground height at an XY position with [`Axis::Z`].
+    ///
+    /// The coordinate of `position` along `axis` is ignored.
+    pub fn height_at(&self, position: Point, axis: Axis) -> Option<f32> {
+        let (_, max) = self.bounds()?;
+
+        let mut origin = from_point(position);
+        // Start a bit above everything so surfaces at the very top are hit as well
+        origin[axis.index()] = from_point(max)[axis.index()] + 1.0;
+        self.height_below(to_point(origin), axis)
+    }
+
+    /// First surface at or below `position` along `axis`, e.g. the road under a car
+    /// driving beneath a bridge
+    pub fn height_below(&self, position: Point, axis: Axis) -> Option<f32> {
+        let (min, _) = self.bounds()?;
+        let axis = axis.index();
+
+        let origin = from_point(position);
+        let mut direction = [0.0; 3];
+        direction[axis] = -1.0;
+        let max_distance = origin[axis] - from_point(min)[axis];
+        if max_distance < 0.0 {
+            return None;
+        }
+
+        self.raycast(position, to_point(direction), max_distance)
+            .map(|ray_hit| from_point(ray_hit.point)[axis])
+    }
+
+    /// Whether any triangle covers the XY position when seen from above, i.e. the position
+    /// is within the area regardless of its height. Points on edges count as inside.
+    pub fn contains_xy(&self, x: f32, y: f32) -> bool {
+        let mut stack = Vec::new();
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if !node.bounds.contains_xy(x, y) {
+                continue;
+            }
+
+            match node.kind {
+                NodeKind::Leaf { start, count } => {
+                    if self.triangles[start..start + count]
+                        .iter()
+                        .any(|triangle| triangle_contains_xy(&triangle.vertices, x, y))
+                    {
+                        return true;
+                    }
+                }
+                NodeKind::Inner { left, right } => stack.extend([left, right]),
+            }
+        }
+
+        false
+    }
+}
+
+fn from_point((x, y, z): Point) -> [f32; 3] {
+    [x, y, z]
+}
+
+fn to_point([x, y, z]: [f32; 3]) -> Point {
+    (x, y, z)
+}
+
+fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(a: [f32; 3], factor: f32) -> [f32; 3] {
+    a.map(|component| component * factor)
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn centroid(triangle: &Triangle) -> [f32; 3] {
+    let [a, b, c] = triangle.vertices;
+    scale(add(add(a, b), c), 1.0 / 3.0)
+}
+
+/// Möller–Trumbore intersection, returning the distance along the ray
+fn ray_triangle(origin: [f32; 3], direction: [f32; 3], triangle: &Triangle) -> Option<f32> {
+    let [a, b, c] = triangle.vertices;
+    let edge1 = sub(b, a);
+    let edge2 = sub(c, a);
+
+    let p = cross(direction, edge2);
+    let determinant = dot(edge1, p);
+    // Parallel to the triangle's plane
+    if determinant == 0.0 {
+        return None;
+    }
+    let inverse_determinant = 1.0 / determinant;
+
+    let s = sub(origin, a);
+    let u = dot(s, p) * inverse_determinant;
+    if !(-EPSILON..=1.0 + EPSILON).contains(&u) {
+        return None;
+    }
+
+    let q = cross(s, edge1);
+    let v = dot(direction, q) * inverse_determinant;
+    if v < -EPSILON || u + v > 1.0 + EPSILON {
+        return None;
+    }
+
+    let distance = dot(edge2, q) * inverse_determinant;
+    (distance >= 0.0).then_some(distance)
+}
+
+/// Nearest point of a triangle, by finding the Voronoi region `point` is in
+/// (as in Ericson's Real-Time Collision Detection)
+fn closest_point_on_triangle(point: [f32; 3], [a, b, c]: &[[f32; 3]; 3]) -> [f32; 3] {
+    let (a, b, c) = (*a, *b, *c);
+    let ab = sub(b, a);
+    let ac = sub(c, a);
+
+    let ap = sub(point, a);
+    let d1 = dot(ab, ap);
+    let d2 = dot(ac, ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+
+    let bp = sub(point, b);
+    let d3 = dot(ab, bp);
+    let d4 = dot(ac, bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return add(a, scale(ab, d1 / (d1 - d3)));
+    }
+
+    let cp = sub(point, c);
+    let d5 = dot(ab, cp);
+    let d6 = dot(ac, cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return add(a, scale(ac, d2 / (d2 - d6)));
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+        return add(b, scale(sub(c, b), (d4 - d3) / ((d4 - d3) + (d5 - d6))));
+    }
+
+    let denominator = 1.0 / (va + vb + vc);
+    add(
+        a,
+        add(scale(ab, vb * denominator), scale(ac, vc * denominator)),
+    )
+}
+
+fn triangle_contains_xy([a, b, c]: &[[f32; 3]; 3], x: f32, y: f32) -> bool {
+    // Walls cover no area when seen from above
+    let double_area = (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0]);
+    if double_area == 0.0 {
+        return false;
+    }
+
+    let edge = |from: &[f32; 3], to: &[f32; 3]| {
+        (to[0] - from[0]) * (y - from[1]) - (to[1] - from[1]) * (x - from[0])
+    };
+    let (ab, bc, ca) = (edge(a, b), edge(b, c), edge(c, a));
+
+    // Either winding, as long as the point isn't on opposite sides of two edges
+    let has_negative = ab < 0.0 || bc < 0.0 || ca < 0.0;
+    let has_positive = ab > 0.0 || bc > 0.0 || ca > 0.0;
+    !(has_negative && has_positive)
+}
@@ -5,3 +5,15 @@ pub mod agt;
 
 #[cfg(feature = "xlt")]
 pub mod xlt;
+
+#[cfg(feature = "hit")]
+pub mod hit;
+
+#[cfg(feature = "loi")]
+pub mod colliders;
+
+#[cfg(feature = "vfs")]
+pub mod vfs;
+
+#[cfg(feature = "world")]
+pub mod world;
@@ -1,10 +1,11 @@
-use crate::parsers::strings;
+use crate::parsers::strings::{self, Codepage};
 use binrw::{
     io::{Read, Seek},
     BinRead, BinReaderExt, BinWrite,
 };
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, BinRead, BinWrite)]
+#[derive(Debug, PartialEq, BinRead, BinWrite, Clone, Serialize, Deserialize)]
 #[brw(magic = b"NayaPack")]
 pub struct Header {
     pub what: u32,
@@ -28,13 +29,20 @@ impl Default for Header {
     }
 }
 #[derive(Debug, PartialEq, BinRead, BinWrite, Clone)]
+#[br(import(codepage: Codepage))]
 pub struct Entry {
     pub chunks_offset: u32,
     pub chunk_count: u32,
     pub decompressed_length: u32,
 
-    #[br(parse_with = strings::read_int_prefixed_string)]
-    #[bw(write_with = strings::write_int_prefixed_string)]
+    /// Path bytes exactly as stored, written back as-is
+    #[br(parse_with = strings::read_int_prefixed_bytes)]
+    #[bw(write_with = strings::write_int_prefixed_bytes)]
+    pub raw_path: Vec<u8>,
+
+    /// Path decoded with the reader's codepage, invalid bytes replaced with U+FFFD
+    #[br(calc = codepage.decode_lossy(&raw_path))]
+    #[bw(ignore)]
     pub path: String,
 }
 
@@ -45,16 +53,39 @@ impl Header {
 }
 
 impl Entry {
-    pub fn parse<R: Read + Seek>(reader: &mut R) -> anyhow::Result<Entry> {
-        Ok(reader.read_le()?)
+    /// Create an entry with its path encoded in the given codepage
+    pub fn new(
+        path: String,
+        codepage: Codepage,
+        chunks_offset: u32,
+        chunk_count: u32,
+        decompressed_length: u32,
+    ) -> anyhow::Result<Entry> {
+        Ok(Entry {
+            chunks_offset,
+            chunk_count,
+            decompressed_length,
+            raw_path: codepage.encode(&path)?,
+            path,
+        })
+    }
+
+    /// Decode the path, failing if it isn't valid in the given codepage
+    pub fn decode_path(&self, codepage: Codepage) -> anyhow::Result<String> {
+        codepage.decode(&self.raw_path)
+    }
+
+    pub fn parse<R: Read + Seek>(reader: &mut R, codepage: Codepage) -> anyhow::Result<Entry> {
+        Ok(reader.read_le_args((codepage,))?)
     }
 
     pub fn parse_entries<R: Read + Seek>(
         reader: &mut R,
         entry_count: u32,
+        codepage: Codepage,
     ) -> anyhow::Result<Vec<Entry>> {
         let entries = (0..entry_count)
-            .map(|_| reader.read_le::<Entry>())
+            .map(|_| reader.read_le_args::<Entry>((codepage,)))
             .collect::<Result<Vec<Entry>, _>>()?;
 
         Ok(entries)
@@ -1,4 +1,9 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{Read, Seek, SeekFrom, Write},
+    rc::Rc,
+};
 
 use binrw::BinWrite;
 
@@ -15,3 +20,133 @@ pub(crate) fn record_entry_offset(
     }
     value.write_options(writer, endian, ())
 }
+
+/// Location of a file embedded after an archive's table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbeddedBlob {
+    /// Identifies the blob within its archive, e.g. a block index or model file name
+    pub key: String,
+    pub offset: u32,
+    pub length: u32,
+}
+
+impl EmbeddedBlob {
+    /// Read the blob's data from its archive
+    pub fn read<R: Read + Seek>(&self, reader: &mut R) -> anyhow::Result<Vec<u8>> {
+        reader.seek(SeekFrom::Start(self.offset as u64))?;
+
+        // Don't trust the length with an allocation up front, corrupt files can claim gigabytes
+        let mut data = Vec::new();
+        reader
+            .take(self.length as u64)
+            .read_to_end(&mut data)
+            .map_err(|e| anyhow::anyhow!("could not read blob {}: {}", self.key, e))?;
+        if data.len() != self.length as usize {
+            anyhow::bail!(
+                "could not read blob {}: {} of {} bytes left",
+                self.key,
+                data.len(),
+                self.length
+            );
+        }
+
+        Ok(data)
+    }
+}
+
+/// Archive whose table is followed by embedded files (usually NIFs), each referenced by
+/// a `file_offset`/`file_length` pair in the table.
+///
+/// Blobs are addressed by their position in table order.
+pub trait EmbeddedBlobArchive {
+    /// Every embedded file in table order
+    fn blobs(&self) -> Vec<EmbeddedBlob>;
+
+    /// Point the table entry of a blob at new data
+    fn set_blob_location(&mut self, index: usize, offset: u32, length: u32);
+
+    /// Write the table without data, recording the position of every blob's offset
+    fn write_table<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        entry_offsets: EntryOffsets,
+    ) -> anyhow::Result<()>;
+
+    /// Write the table followed by the data of every blob as written by `write_blob`,
+    /// then fill in the offsets and lengths in both the output and `self`
+    fn write_with_blobs<W, F>(&mut self, writer: &mut W, mut write_blob: F) -> anyhow::Result<()>
+    where
+        W: Write + Seek,
+        F: FnMut(usize, &mut W) -> anyhow::Result<()>,
+    {
+        let entry_offsets = EntryOffsets::default();
+        self.write_table(writer, entry_offsets.clone())?;
+
+        let header_offsets = entry_offsets.take();
+        let blob_count = self.blobs().len();
+        if header_offsets.len() != blob_count {
+            anyhow::bail!(
+                "table recorded {} offset(s) for {} blob(s)",
+                header_offsets.len(),
+                blob_count
+            );
+        }
+
+        for (index, header_offset) in header_offsets.into_iter().enumerate() {
+            let start = writer.stream_position()?;
+            write_blob(index, writer)?;
+            let end = writer.stream_position()?;
+
+            let (Ok(offset), Ok(length)) = (u32::try_from(start), u32::try_from(end - start))
+            else {
+                anyhow::bail!("blob {} does not fit in a 4 GiB archive", index);
+            };
+
+            // Go back and fill in header offsets
+            writer.seek(SeekFrom::Start(header_offset))?;
+            writer.write_all(&offset.to_le_bytes())?;
+            writer.write_all(&length.to_le_bytes())?;
+            writer.seek(SeekFrom::Start(end))?;
+
+            self.set_blob_location(index, offset, length);
+        }
+
+        Ok(())
+    }
+
+    /// Rewrite an archive read from `reader`, replacing the data of blobs by index and
+    /// copying every other blob as is
+    fn write_replacing_blobs<R, W>(
+        &mut self,
+        reader: &mut R,
+        writer: &mut W,
+        replacements: &HashMap<usize, Vec<u8>>,
+    ) -> anyhow::Result<()>
+    where
+        R: Read + Seek,
+        W: Write + Seek,
+    {
+        let blobs = self.blobs();
+
+        self.write_with_blobs(writer, |index, writer| {
+            if let Some(data) = replacements.get(&index) {
+                writer.write_all(data)?;
+                return Ok(());
+            }
+
+            let blob = &blobs[index];
+            reader.seek(SeekFrom::Start(blob.offset as u64))?;
+            let copied = std::io::copy(&mut reader.by_ref().take(blob.length as u64), writer)?;
+            if copied != blob.length as u64 {
+                anyhow::bail!(
+                    "blob {} is truncated, read {} of {} bytes",
+                    blob.key,
+                    copied,
+                    blob.length
+                );
+            }
+
+            Ok(())
+        })
+    }
+}
@@ -13,6 +13,18 @@ pub struct Header {
     pub version_date: u32,
 }
 
+/// Triangles with no more than half this area count as degenerate
+const MIN_DOUBLE_AREA: f32 = 1e-6;
+
+impl Default for Header {
+    fn default() -> Self {
+        Self {
+            version_date: 20060720,
+        }
+    }
+}
+
+/// Collision area as a triangle list, `indices` holding three vertex indices per triangle
 #[binrw]
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Hit {
@@ -37,4 +49,73 @@ impl Hit {
     pub fn write<W: Write + Seek>(&self, writer: &mut W) -> anyhow::Result<()> {
         Ok(writer.write_le(self)?)
     }
+
+    /// Build a collision area from a triangle list, failing if it doesn't pass [`Hit::check`]
+    pub fn from_triangles(verts: Vec<(f32, f32, f32)>, indices: Vec<u32>) -> anyhow::Result<Self> {
+        let hit = Self {
+            header: Header::default(),
+            indices,
+            verts,
+        };
+        hit.check()?;
+
+        Ok(hit)
+    }
+
+    /// Vertex indices of every triangle, ignoring a trailing incomplete one
+    pub fn triangles(&self) -> impl Iterator<Item = [u32; 3]> + '_ {
+        self.indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+    }
+
+    /// Check that the indices form whole triangles of existing vertices, none of them
+    /// degenerate (repeating a vertex or having no area)
+    pub fn check(&self) -> anyhow::Result<()> {
+        if !self.indices.len().is_multiple_of(3) {
+            anyhow::bail!(
+                "{} indices do not make up whole triangles",
+                self.indices.len()
+            );
+        }
+
+        for (triangle_index, triangle) in self.triangles().enumerate() {
+            let mut positions = [(0.0, 0.0, 0.0); 3];
+            for (position, &vert_index) in positions.iter_mut().zip(triangle.iter()) {
+                let Some(&vert) = self.verts.get(vert_index as usize) else {
+                    anyhow::bail!(
+                        "triangle {} refers to vertex {} of {}",
+                        triangle_index,
+                        vert_index,
+                        self.verts.len()
+                    );
+                };
+                *position = vert;
+            }
+
+            let [a, b, c] = triangle;
+            if a == b || b == c || a == c {
+                anyhow::bail!(
+                    "triangle {} repeats a vertex: {:?}",
+                    triangle_index,
+                    triangle
+                );
+            }
+
+            let [p, q, r] = positions;
+            let u = (q.0 - p.0, q.1 - p.1, q.2 - p.2);
+            let v = (r.0 - p.0, r.1 - p.1, r.2 - p.2);
+            let cross = (
+                u.1 * v.2 - u.2 * v.1,
+                u.2 * v.0 - u.0 * v.2,
+                u.0 * v.1 - u.1 * v.0,
+            );
+            let double_area = (cross.0 * cross.0 + cross.1 * cross.1 + cross.2 * cross.2).sqrt();
+            if double_area.is_nan() || double_area <= MIN_DOUBLE_AREA {
+                anyhow::bail!("triangle {} has no area: {:?}", triangle_index, positions);
+            }
+        }
+
+        Ok(())
+    }
 }
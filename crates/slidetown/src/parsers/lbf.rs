@@ -1,32 +1,45 @@
 use binrw::{
-    io::{Read, Seek},
-    BinRead, BinReaderExt,
+    binrw,
+    io::{Read, Seek, Write},
+    BinReaderExt, BinWriterExt,
 };
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, BinRead, Serialize, Deserialize)]
-#[br(magic = b"LBF\0kjc\0ag\0\0")]
+use super::{archives::record_entry_offset, EmbeddedBlob, EmbeddedBlobArchive, EntryOffsets};
+
+#[binrw]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[brw(magic = b"LBF\0kjc\0ag\0\0")]
 pub struct Header {
     pub version_date: u32,
 
     pub unknown2: u32,
+    /// Checked against the blocks when writing, see [`Lbf::update_counts`]
     pub block_count: u32,
 
+    /// Checked against the blocks when writing, see [`Lbf::update_counts`]
     pub block_object_count: u32,
 }
 
-#[derive(Debug, PartialEq, BinRead, Serialize, Deserialize)]
+#[binrw]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[bw(import(entry_offsets: Option<EntryOffsets>))]
 pub struct Block {
+    #[bw(calc = objects.len() as u32)]
     pub object_count: u32,
     #[br(count = object_count)]
+    #[bw(args(entry_offsets))]
     pub objects: Vec<BlockObject>,
 }
 
-#[derive(Debug, PartialEq, BinRead, Serialize, Deserialize)]
+#[binrw]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[bw(import(entry_offsets: Option<EntryOffsets>))]
 pub struct BlockObject {
     pub unk: u32,
     pub block_index: u32,
 
+    #[bw(args(entry_offsets), write_with = record_entry_offset)]
     #[serde(skip)]
     pub file_offset: u32,
 
@@ -34,11 +47,28 @@ pub struct BlockObject {
     pub file_length: u32,
 }
 
-#[derive(Debug, PartialEq, BinRead, Serialize, Deserialize)]
+#[binrw]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[bw(import(entry_offsets: Option<EntryOffsets>))]
 pub struct Lbf {
     pub header: Header,
 
     #[br(count = header.block_count)]
+    #[bw(
+        assert(
+            header.block_count as usize == blocks.len(),
+            "header block count {} does not match {} block(s)",
+            header.block_count,
+            blocks.len()
+        ),
+        assert(
+            header.block_object_count as usize
+                == blocks.iter().map(|block| block.objects.len()).sum::<usize>(),
+            "header block object count {} does not match the blocks",
+            header.block_object_count
+        ),
+        args(entry_offsets)
+    )]
     pub blocks: Vec<Block>,
 }
 
@@ -58,4 +88,63 @@ impl Lbf {
     pub fn parse<R: Read + Seek>(reader: &mut R) -> anyhow::Result<Self> {
         Ok(reader.read_le()?)
     }
+
+    /// Write the header and block table, recording the position of every object's
+    /// file offset so the data can be filled in after it
+    pub fn write_without_data<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        entry_offsets: EntryOffsets,
+    ) -> anyhow::Result<()> {
+        Ok(writer.write_le_args(self, (Some(entry_offsets),))?)
+    }
+
+    /// Recalculate the header counts after blocks or objects were added or removed
+    pub fn update_counts(&mut self) {
+        self.header.block_count = self.blocks.len() as u32;
+        self.header.block_object_count = self
+            .blocks
+            .iter()
+            .map(|block| block.objects.len() as u32)
+            .sum();
+    }
+}
+
+impl EmbeddedBlobArchive for Lbf {
+    fn blobs(&self) -> Vec<EmbeddedBlob> {
+        self.blocks
+            .iter()
+            .flat_map(|block| {
+                // What unk holds is unknown, so objects are told apart by their position
+                block
+                    .objects
+                    .iter()
+                    .enumerate()
+                    .map(|(object_index, object)| EmbeddedBlob {
+                        key: format!("{}_{}", object.block_index, object_index),
+                        offset: object.file_offset,
+                        length: object.file_length,
+                    })
+            })
+            .collect()
+    }
+
+    fn set_blob_location(&mut self, index: usize, offset: u32, length: u32) {
+        let object = self
+            .blocks
+            .iter_mut()
+            .flat_map(|block| block.objects.iter_mut())
+            .nth(index)
+            .expect("block object index out of range");
+        object.file_offset = offset;
+        object.file_length = length;
+    }
+
+    fn write_table<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        entry_offsets: EntryOffsets,
+    ) -> anyhow::Result<()> {
+        self.write_without_data(writer, entry_offsets)
+    }
 }
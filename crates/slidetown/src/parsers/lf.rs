@@ -5,7 +5,7 @@ use binrw::{
 };
 use serde::{Deserialize, Serialize};
 
-use super::{archives::record_entry_offset, EntryOffsets};
+use super::{archives::record_entry_offset, EmbeddedBlob, EmbeddedBlobArchive, EntryOffsets};
 
 #[binrw]
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
@@ -69,3 +69,30 @@ impl Lf {
         Ok(writer.write_le_args(self, (Some(entry_offsets),))?)
     }
 }
+
+impl EmbeddedBlobArchive for Lf {
+    fn blobs(&self) -> Vec<EmbeddedBlob> {
+        self.blocks
+            .iter()
+            .map(|block| EmbeddedBlob {
+                key: block.index.to_string(),
+                offset: block.file_offset,
+                length: block.file_length,
+            })
+            .collect()
+    }
+
+    fn set_blob_location(&mut self, index: usize, offset: u32, length: u32) {
+        let block = &mut self.blocks[index];
+        block.file_offset = offset;
+        block.file_length = length;
+    }
+
+    fn write_table<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        entry_offsets: EntryOffsets,
+    ) -> anyhow::Result<()> {
+        self.write_without_data(writer, entry_offsets)
+    }
+}
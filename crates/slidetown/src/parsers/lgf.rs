@@ -5,6 +5,8 @@ use binrw::{
 };
 use serde::{Deserialize, Serialize};
 
+use super::{archives::record_entry_offset, EmbeddedBlob, EmbeddedBlobArchive, EntryOffsets};
+
 #[binrw]
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 #[brw(magic = b"LGF\0kjc\0ag\0\0")]
@@ -16,9 +18,11 @@ pub struct Header {
 
 #[binrw]
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[bw(import(entry_offsets: Option<EntryOffsets>))]
 pub struct Block {
     pub block_index: u32,
 
+    #[bw(args(entry_offsets), write_with = record_entry_offset)]
     #[serde(skip)]
     pub file_offset: u32,
 
@@ -28,11 +32,13 @@ pub struct Block {
 
 #[binrw]
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[bw(import(entry_offsets: Option<EntryOffsets>))]
 pub struct Lgf {
     pub header: Header,
     #[bw(calc = blocks.len() as u32)]
     pub block_count: u32,
     #[br(count = block_count)]
+    #[bw(args(entry_offsets))]
     pub blocks: Vec<Block>,
 }
 
@@ -42,6 +48,41 @@ impl Lgf {
     }
 
     pub fn write<W: Write + Seek>(&self, writer: &mut W) -> anyhow::Result<()> {
-        Ok(writer.write_le(self)?)
+        Ok(writer.write_le_args(self, (None,))?)
+    }
+
+    pub fn write_without_data<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        entry_offsets: EntryOffsets,
+    ) -> anyhow::Result<()> {
+        Ok(writer.write_le_args(self, (Some(entry_offsets),))?)
+    }
+}
+
+impl EmbeddedBlobArchive for Lgf {
+    fn blobs(&self) -> Vec<EmbeddedBlob> {
+        self.blocks
+            .iter()
+            .map(|block| EmbeddedBlob {
+                key: block.block_index.to_string(),
+                offset: block.file_offset,
+                length: block.file_length,
+            })
+            .collect()
+    }
+
+    fn set_blob_location(&mut self, index: usize, offset: u32, length: u32) {
+        let block = &mut self.blocks[index];
+        block.file_offset = offset;
+        block.file_length = length;
+    }
+
+    fn write_table<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        entry_offsets: EntryOffsets,
+    ) -> anyhow::Result<()> {
+        self.write_without_data(writer, entry_offsets)
     }
 }
@@ -1,4 +1,4 @@
-use crate::parsers::strings;
+use crate::parsers::strings::{self, Codepage};
 use binrw::{
     binrw,
     io::{Read, Seek, Write},
@@ -6,7 +6,7 @@ use binrw::{
 };
 use serde::{Deserialize, Serialize};
 
-use super::archives::{record_entry_offset, EntryOffsets};
+use super::archives::{record_entry_offset, EmbeddedBlob, EmbeddedBlobArchive, EntryOffsets};
 
 #[binrw]
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
@@ -18,7 +18,8 @@ pub struct Header {
 
 #[binrw]
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
-#[bw(import(entry_offsets: Option<EntryOffsets>))]
+#[br(import { codepage: Codepage })]
+#[bw(import { entry_offsets: Option<EntryOffsets> = None, codepage: Codepage })]
 pub struct Model {
     pub index: u32,
     pub unknown1: u32,
@@ -27,12 +28,12 @@ pub struct Model {
     pub lighting: u32, // turns on light at night?
     pub effect_id: u32,
 
-    #[br(parse_with = strings::parse_null_terminated_euc_kr_string )]
-    #[bw(map = strings::string_to_null_terminated_euc_kr)]
+    #[br(parse_with = strings::parse_null_terminated_string, args(codepage))]
+    #[bw(write_with = strings::write_null_terminated_string, args(codepage))]
     pub name: String,
 
-    #[br(parse_with = strings::parse_null_terminated_euc_kr_string )]
-    #[bw(map = strings::string_to_null_terminated_euc_kr)]
+    #[br(parse_with = strings::parse_null_terminated_string, args(codepage))]
+    #[bw(write_with = strings::write_null_terminated_string, args(codepage))]
     pub file_name: String,
 
     pub animation_duration: f32,
@@ -45,24 +46,54 @@ pub struct Model {
     pub file_length: u32,
 }
 
+/// Model table, names are written back in the codepage they were read in.
+///
+/// binrw ignores defaults of omitted named args, so reading always takes a codepage.
 #[binrw]
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[br(import { codepage: Codepage })]
 #[bw(import(entry_offsets: Option<EntryOffsets>))]
 pub struct Lof {
     pub header: Header,
     #[bw(calc = models.len() as u32)]
     pub model_count: u32,
     pub max_file_size: u32,
-    #[br(count = model_count)]
-    #[bw(args(entry_offsets))]
+    #[br(count = model_count, args { inner: binrw::args! { codepage } })]
+    #[bw(args { entry_offsets: entry_offsets.clone(), codepage: *codepage })]
     pub models: Vec<Model>,
+
+    /// Codepage of the model names
+    #[br(calc = codepage)]
+    #[bw(ignore)]
+    #[serde(skip, default = "Lof::default_codepage")]
+    pub codepage: Codepage,
 }
 
 impl Lof {
+    /// Codepage of model names in files from Korean clients
+    pub const DEFAULT_CODEPAGE: Codepage = Codepage::EucKr;
+
+    fn default_codepage() -> Codepage {
+        Self::DEFAULT_CODEPAGE
+    }
+
+    /// Read a model table whose names are in [`Lof::DEFAULT_CODEPAGE`].
+    ///
+    /// Names that aren't valid in the codepage are an error. Older versions replaced
+    /// the invalid bytes instead, read such files with their actual codepage.
     pub fn read_without_data<R: Read + Seek>(reader: &mut R) -> anyhow::Result<Self> {
-        Ok(reader.read_le()?)
+        Self::read_without_data_with_codepage(reader, Self::DEFAULT_CODEPAGE)
     }
 
+    /// Read a model table whose names are in the given codepage
+    pub fn read_without_data_with_codepage<R: Read + Seek>(
+        reader: &mut R,
+        codepage: Codepage,
+    ) -> anyhow::Result<Self> {
+        Ok(reader.read_le_args::<Self>(binrw::args! { codepage })?)
+    }
+
+    /// Write the model table with names encoded in [`Lof::codepage`]
     pub fn write_without_data<W: Write + Seek>(
         &self,
         writer: &mut W,
@@ -71,3 +102,30 @@ impl Lof {
         Ok(writer.write_le_args(self, (Some(entry_offsets),))?)
     }
 }
+
+impl EmbeddedBlobArchive for Lof {
+    fn blobs(&self) -> Vec<EmbeddedBlob> {
+        self.models
+            .iter()
+            .map(|model| EmbeddedBlob {
+                key: model.file_name.clone(),
+                offset: model.file_offset,
+                length: model.file_length,
+            })
+            .collect()
+    }
+
+    fn set_blob_location(&mut self, index: usize, offset: u32, length: u32) {
+        let model = &mut self.models[index];
+        model.file_offset = offset;
+        model.file_length = length;
+    }
+
+    fn write_table<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        entry_offsets: EntryOffsets,
+    ) -> anyhow::Result<()> {
+        self.write_without_data(writer, entry_offsets)
+    }
+}
@@ -1,7 +1,8 @@
 mod archives;
 mod strings;
 
-pub use archives::EntryOffsets;
+pub use archives::{EmbeddedBlob, EmbeddedBlobArchive, EntryOffsets};
+pub use strings::Codepage;
 
 #[cfg(feature = "agt")]
 pub mod agt;
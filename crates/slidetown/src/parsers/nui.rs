@@ -133,10 +133,8 @@ impl DialogList {
                         dialogs.push(Dialog::from_event(bytes_start, reader)?);
                     }
                 }
-                Event::End(bytes_end) => {
-                    if bytes_end.name() == b"DIALOGLIST" {
-                        break;
-                    }
+                Event::End(bytes_end) if bytes_end.name() == b"DIALOGLIST" => {
+                    break;
                 }
                 _ => {}
             }
@@ -1,66 +1,148 @@
-#![allow(dead_code)]
+use std::str::FromStr;
 
-use encoding_rs::EUC_KR;
+use encoding_rs::{Encoding, BIG5, EUC_KR, GBK, SHIFT_JIS, UTF_8, WINDOWS_1252};
+use serde::{Deserialize, Serialize};
 
-use binrw::{io::Read, BinRead, BinResult, BinWrite};
+use binrw::{
+    io::{Read, Seek},
+    BinRead, BinResult, BinWrite,
+};
 
-#[binrw::parser(reader, endian)]
-pub fn read_int_prefixed_string() -> BinResult<String> {
-    let pos = reader.stream_position()?;
-    let count = u32::read_options(reader, endian, ())?;
+/// Text encoding used for strings stored as raw bytes, which differs between client regions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Codepage {
+    #[default]
+    Utf8,
+    /// Korean clients
+    EucKr,
+    /// Taiwanese clients
+    Big5,
+    /// Chinese clients
+    Gbk,
+    /// Japanese clients
+    ShiftJis,
+    /// Western European clients
+    Cp1252,
+}
 
-    String::from_utf8(
-        reader
-            .take(count as u64)
-            .bytes()
-            .filter_map(Result::ok)
-            .collect(),
-    )
-    .map_err(|e| binrw::Error::Custom {
-        pos,
-        err: Box::new(e),
-    })
+impl Codepage {
+    pub fn name(&self) -> &'static str {
+        self.encoding().name()
+    }
+
+    fn encoding(&self) -> &'static Encoding {
+        match self {
+            Codepage::Utf8 => UTF_8,
+            Codepage::EucKr => EUC_KR,
+            Codepage::Big5 => BIG5,
+            Codepage::Gbk => GBK,
+            Codepage::ShiftJis => SHIFT_JIS,
+            Codepage::Cp1252 => WINDOWS_1252,
+        }
+    }
+
+    /// Decode bytes, failing on anything that isn't valid in this codepage
+    pub fn decode(&self, bytes: &[u8]) -> anyhow::Result<String> {
+        self.encoding()
+            .decode_without_bom_handling_and_without_replacement(bytes)
+            .map(|cow| cow.into_owned())
+            .ok_or_else(|| anyhow::anyhow!("{:?} is not valid {}", bytes, self.name()))
+    }
+
+    /// Decode bytes, replacing anything invalid with U+FFFD
+    pub fn decode_lossy(&self, bytes: &[u8]) -> String {
+        let (cow, _had_errors) = self.encoding().decode_without_bom_handling(bytes);
+        cow.into_owned()
+    }
+
+    /// Encode a string, failing on characters this codepage can't represent
+    pub fn encode(&self, s: &str) -> anyhow::Result<Vec<u8>> {
+        let (cow, _encoding_used, had_errors) = self.encoding().encode(s);
+        if had_errors {
+            anyhow::bail!("{:?} can't be encoded as {}", s, self.name());
+        }
+        Ok(cow.into_owned())
+    }
 }
 
-#[binrw::writer(writer, endian)]
-pub fn write_int_prefixed_string(value: &String) -> BinResult<()> {
-    let str_bytes = value.as_bytes();
-    (str_bytes.len() as u32).write_options(writer, endian, ())?;
-    str_bytes.write_options(writer, endian, ())
+impl FromStr for Codepage {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('_', "-").as_str() {
+            "utf-8" | "utf8" => Ok(Codepage::Utf8),
+            "euc-kr" | "cp949" => Ok(Codepage::EucKr),
+            "big5" | "cp950" => Ok(Codepage::Big5),
+            "gbk" | "cp936" => Ok(Codepage::Gbk),
+            "shift-jis" | "sjis" | "cp932" => Ok(Codepage::ShiftJis),
+            "cp1252" | "windows-1252" => Ok(Codepage::Cp1252),
+            _ => anyhow::bail!(
+                "unknown codepage {}, expected utf-8, euc-kr, big5, gbk, shift-jis or cp1252",
+                s
+            ),
+        }
+    }
 }
 
-#[binrw::parser(reader)]
-pub fn parse_lf_terminated_string() -> BinResult<String> {
-    let pos = reader.stream_position()?;
+/// Read bytes up to (and consuming) the terminator, or until the end of the stream
+fn read_until<R: Read>(reader: &mut R, terminator: u8) -> std::io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut byte = [0u8; 1];
+    while reader.read(&mut byte)? == 1 && byte[0] != terminator {
+        bytes.push(byte[0]);
+    }
+    Ok(bytes)
+}
 
-    String::from_utf8(
-        reader
-            .bytes()
-            .filter_map(Result::ok)
-            .take_while(|&b| b != b'\n')
-            .collect(),
-    )
-    .map_err(|e| binrw::Error::Custom {
+/// Decode string bytes read from `pos`, turning invalid bytes into a binrw error
+fn decode_at(bytes: &[u8], codepage: Codepage, pos: u64) -> BinResult<String> {
+    codepage.decode(bytes).map_err(|e| binrw::Error::Custom {
         pos,
-        err: Box::new(e),
+        err: Box::new(e.to_string()),
     })
 }
 
+/// Encode a string about to be written at the writer's position, turning
+/// unrepresentable characters into a binrw error
+fn encode_at<W: Seek>(writer: &mut W, value: &str, codepage: Codepage) -> BinResult<Vec<u8>> {
+    let pos = writer.stream_position()?;
+    codepage.encode(value).map_err(|e| binrw::Error::Custom {
+        pos,
+        err: Box::new(e.to_string()),
+    })
+}
+
+#[binrw::parser(reader, endian)]
+pub fn read_int_prefixed_bytes() -> BinResult<Vec<u8>> {
+    let count = u32::read_options(reader, endian, ())?;
+
+    // Don't trust the count with an allocation up front, corrupt files can claim gigabytes
+    let mut bytes = Vec::new();
+    reader.take(count as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != count as usize {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+    }
+
+    Ok(bytes)
+}
+
+#[binrw::writer(writer, endian)]
+pub fn write_int_prefixed_bytes(value: &Vec<u8>) -> BinResult<()> {
+    (value.len() as u32).write_options(writer, endian, ())?;
+    value.write_options(writer, endian, ())
+}
+
 #[binrw::parser(reader)]
-pub fn parse_null_terminated_euc_kr_string() -> BinResult<String> {
-    let bytes: Vec<u8> = reader
-        .bytes()
-        .filter_map(Result::ok)
-        .take_while(|&b| b != 0)
-        .collect();
-
-    let (cow, _encoding_used, _had_errors) = EUC_KR.decode(&bytes);
-    Ok(cow.to_string())
+pub fn parse_null_terminated_string(codepage: Codepage) -> BinResult<String> {
+    let pos = reader.stream_position()?;
+    decode_at(&read_until(reader, 0)?, codepage, pos)
 }
 
 #[allow(clippy::ptr_arg)]
-pub fn string_to_null_terminated_euc_kr(s: &String) -> Vec<u8> {
-    let null_terminated = format!("{}\0", s);
-    let (cow, _encoding_used, _had_errors) = EUC_KR.encode(&null_terminated);
-    cow.to_vec()
+#[binrw::writer(writer, endian)]
+pub fn write_null_terminated_string(value: &String, codepage: Codepage) -> BinResult<()> {
+    let mut str_bytes = encode_at(writer, value, codepage)?;
+    str_bytes.push(0);
+    str_bytes.write_options(writer, endian, ())
 }
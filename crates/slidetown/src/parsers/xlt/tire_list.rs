@@ -25,7 +25,7 @@ impl TireList {
             .next()
             .context("expected tire list header to be on row 3")?;
         let mut entries = Vec::new();
-        for row in row_iter.take_while(|r| r.first().is_some() && !r[0].trim().is_empty()) {
+        for row in row_iter.take_while(|r| !r.is_empty() && !r[0].trim().is_empty()) {
             let entry =
                 TireListEntry::from_xlt_row(row).context("failed to parse tire list entry")?;
             entries.push(entry);
@@ -277,7 +277,7 @@ impl VisualItemListEntry {
             .take_while(|s| !s.is_empty())
             .enumerate()
             .filter_map(|(i, s)| s.parse().map(|n: usize| (i, n)).ok())
-            .filter(|&(_i, n)| (n != 0))
+            .filter(|&(_i, n)| n != 0)
             .map(|(i, _n)| i)
             .collect();
         Ok(Self {
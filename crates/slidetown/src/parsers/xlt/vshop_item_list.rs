@@ -25,7 +25,7 @@ impl VShopItemList {
             .next()
             .context("expected vshop item list header to be on row 3")?;
         let mut entries = Vec::new();
-        for row in row_iter.take_while(|r| r.first().is_some() && !r[0].trim().is_empty()) {
+        for row in row_iter.take_while(|r| !r.is_empty() && !r[0].trim().is_empty()) {
             let entry = VShopItemListEntry::from_xlt_row(row)
                 .context("failed to parse vshop item list entry")?;
             entries.push(entry);
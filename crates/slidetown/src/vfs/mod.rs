@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::agt::{normalize_entry_path, AgtEntryReader, AgtReader, XorReader};
+use crate::parsers::{agt::Entry, Codepage};
+
+enum Mount {
+    Agt {
+        path: PathBuf,
+        cipher: Vec<u8>,
+        entries: Vec<Entry>,
+    },
+    Directory {
+        path: PathBuf,
+        /// Entry paths and file paths of every file under the directory
+        files: Vec<(String, PathBuf)>,
+    },
+}
+
+/// Where the visible version of an entry lives
+#[derive(Debug, Clone, Copy)]
+struct Location {
+    mount_index: usize,
+    /// Index into the mount's entries or files
+    item_index: usize,
+}
+
+/// Information about an entry visible through the filesystem
+#[derive(Debug, Clone, PartialEq)]
+pub struct VfsEntry {
+    /// Path as stored in the archive, or relative to the mounted directory with backslashes
+    pub path: String,
+    /// Decompressed length of the entry
+    pub size: u64,
+    /// Index of the mount that provides this entry, in mounting order
+    pub mount_index: usize,
+}
+
+/// Overlays several AGT files and loose directories into a single set of entries.
+///
+/// Mounts added later take priority over earlier ones, the same way the game client
+/// lets patches override its base archives. Within one mount the first of several
+/// entries with the same path wins. Lookups ignore case and slash direction.
+#[derive(Default)]
+pub struct Vfs {
+    mounts: Vec<Mount>,
+    /// Visible entries by normalized path
+    index: HashMap<String, Location>,
+}
+
+impl Vfs {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Mount an AGT file on top of everything mounted so far
+    pub fn mount_agt<P: AsRef<Path>>(&mut self, path: P, cipher: &[u8]) -> anyhow::Result<()> {
+        self.mount_agt_with_codepage(path, cipher, Default::default())
+    }
+
+    /// Mount an AGT file whose entry paths are in the given codepage, failing if any path
+    /// isn't valid in it since lookups go by the decoded text
+    pub fn mount_agt_with_codepage<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        cipher: &[u8],
+        codepage: Codepage,
+    ) -> anyhow::Result<()> {
+        let mut file = BufReader::new(File::open(path.as_ref())?);
+        let mut agt_reader = AgtReader::new(&mut file, cipher);
+        agt_reader.set_codepage(codepage);
+
+        let header = agt_reader.read_header()?;
+        let entries = agt_reader.read_entries_strict(header.file_count)?;
+
+        self.index_mount(entries.iter().map(|entry| entry.path.as_str()));
+
+        self.mounts.push(Mount::Agt {
+            path: path.as_ref().to_owned(),
+            cipher: cipher.to_vec(),
+            entries,
+        });
+
+        Ok(())
+    }
+
+    /// Mount a directory of loose files on top of everything mounted so far.
+    ///
+    /// Files are listed once when mounting, paths are relative to the directory.
+    pub fn mount_dir<P: AsRef<Path>>(&mut self, path: P) -> anyhow::Result<()> {
+        let mut files = Vec::new();
+        collect_files(path.as_ref(), path.as_ref(), &mut files)?;
+
+        self.index_mount(files.iter().map(|(entry_path, _)| entry_path.as_str()));
+
+        self.mounts.push(Mount::Directory {
+            path: path.as_ref().to_owned(),
+            files,
+        });
+
+        Ok(())
+    }
+
+    /// Point the index at the items of the mount about to be pushed, shadowing earlier mounts
+    fn index_mount<'a>(&mut self, entry_paths: impl Iterator<Item = &'a str>) {
+        let mount_index = self.mounts.len();
+        let mut mount_index_entries = HashMap::new();
+        for (item_index, entry_path) in entry_paths.enumerate() {
+            // Like the game, the first entry wins if a path appears twice
+            mount_index_entries
+                .entry(normalize_entry_path(entry_path))
+                .or_insert(Location {
+                    mount_index,
+                    item_index,
+                });
+        }
+        self.index.extend(mount_index_entries);
+    }
+
+    /// Paths of the mounted archives and directories, in mounting order
+    pub fn mount_paths(&self) -> impl Iterator<Item = &Path> {
+        self.mounts.iter().map(|mount| match mount {
+            Mount::Agt { path, .. } => path.as_path(),
+            Mount::Directory { path, .. } => path.as_path(),
+        })
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.index.contains_key(&normalize_entry_path(path))
+    }
+
+    /// Find the visible version of an entry
+    pub fn lookup(&self, path: &str) -> Option<VfsEntry> {
+        self.index
+            .get(&normalize_entry_path(path))
+            .map(|&location| self.entry_at(location))
+    }
+
+    /// All visible entries, sorted by path
+    pub fn entries(&self) -> Vec<VfsEntry> {
+        let mut entries = self
+            .index
+            .values()
+            .map(|&location| self.entry_at(location))
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        entries
+    }
+
+    /// Open a streaming reader over the visible version of an entry
+    pub fn open(&self, path: &str) -> anyhow::Result<VfsReader<'_>> {
+        let location = self
+            .index
+            .get(&normalize_entry_path(path))
+            .ok_or_else(|| anyhow::anyhow!("no entry {} in any mount", path))?;
+
+        let inner = match &self.mounts[location.mount_index] {
+            Mount::Agt {
+                path,
+                cipher,
+                entries,
+            } => {
+                let file = BufReader::new(File::open(path)?);
+                VfsReaderInner::Agt(AgtEntryReader::open(
+                    file,
+                    cipher,
+                    &entries[location.item_index],
+                )?)
+            }
+            Mount::Directory { files, .. } => {
+                let (_, file_path) = &files[location.item_index];
+                VfsReaderInner::File(BufReader::new(File::open(file_path)?))
+            }
+        };
+
+        Ok(VfsReader { inner })
+    }
+
+    /// Read the whole visible version of an entry
+    pub fn read(&self, path: &str) -> anyhow::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.open(path)?.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    fn entry_at(&self, location: Location) -> VfsEntry {
+        let (path, size) = match &self.mounts[location.mount_index] {
+            Mount::Agt { entries, .. } => {
+                let entry = &entries[location.item_index];
+                (entry.path.clone(), entry.decompressed_length as u64)
+            }
+            Mount::Directory { files, .. } => {
+                let (entry_path, file_path) = &files[location.item_index];
+                let size = std::fs::metadata(file_path).map_or(0, |meta| meta.len());
+                (entry_path.clone(), size)
+            }
+        };
+
+        VfsEntry {
+            path,
+            size,
+            mount_index: location.mount_index,
+        }
+    }
+}
+
+/// Recursively list files, turning paths relative to the root into backslash-separated entry paths
+fn collect_files(
+    root: &Path,
+    dir: &Path,
+    files: &mut Vec<(String, PathBuf)>,
+) -> anyhow::Result<()> {
+    let mut dir_entries = std::fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    dir_entries.sort_by_key(|dir_entry| dir_entry.file_name());
+
+    for dir_entry in dir_entries {
+        let path = dir_entry.path();
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+        } else {
+            let relative = path.strip_prefix(root)?;
+            let entry_path = relative
+                .iter()
+                .map(|part| {
+                    part.to_str()
+                        .ok_or_else(|| anyhow::anyhow!("non-UTF-8 file name {:?}", path))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?
+                .join("\\");
+            files.push((entry_path, path));
+        }
+    }
+
+    Ok(())
+}
+
+enum VfsReaderInner<'vfs> {
+    File(BufReader<File>),
+    Agt(AgtEntryReader<XorReader<'vfs, BufReader<File>>>),
+}
+
+/// Streaming reader over an entry, either a loose file or decompressed from an archive
+pub struct VfsReader<'vfs> {
+    inner: VfsReaderInner<'vfs>,
+}
+
+impl<'vfs> Read for VfsReader<'vfs> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match &mut self.inner {
+            VfsReaderInner::File(file) => file.read(buf),
+            VfsReaderInner::Agt(entry_reader) => entry_reader.read(buf),
+        }
+    }
+}
+
+impl<'vfs> Seek for VfsReader<'vfs> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match &mut self.inner {
+            VfsReaderInner::File(file) => file.seek(pos),
+            VfsReaderInner::Agt(entry_reader) => entry_reader.seek(pos),
+        }
+    }
+}
@@ -0,0 +1,338 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::BufReader;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+use crate::parsers::{
+    chpath::Chpath, hit::Hit, lbf::Lbf, lf::Lf, lgf::Lgf, lif::Lif, llf::Llf, lof::Lof, loi::Loi,
+    Codepage,
+};
+
+pub const LF_FILE_NAME: &str = "terrain0.lf";
+pub const LBF_FILE_NAME: &str = "blockObj0.lbf";
+pub const LGF_FILE_NAME: &str = "guardrail0.lgf";
+pub const LLF_FILE_NAME: &str = "lane0.llf";
+pub const LOF_FILE_NAME: &str = "modeltable0.lof";
+pub const LOI_FILE_NAME: &str = "object0.loI";
+pub const LIF_FILE_NAME: &str = "terrain0.lif";
+pub const HIT_FILE_NAME: &str = "area.hit";
+pub const CHPATH_EXTENSION: &str = "chpath";
+
+/// Parsed file along with where it was loaded from, e.g. to read embedded NIF data later
+#[derive(Debug)]
+pub struct WorldFile<T> {
+    pub path: PathBuf,
+    pub contents: T,
+}
+
+impl<T> Deref for WorldFile<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.contents
+    }
+}
+
+/// Subdirectory of a city with its own objects and collision, e.g. `Main` or `Track1`
+#[derive(Debug)]
+pub struct WorldLayer {
+    name: String,
+    loi: Option<WorldFile<Loi>>,
+    lif: Option<WorldFile<Lif>>,
+    hit: Option<WorldFile<Hit>>,
+}
+
+impl WorldLayer {
+    /// Directory name as found on disk
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn loi(&self) -> Option<&WorldFile<Loi>> {
+        self.loi.as_ref()
+    }
+
+    pub fn lif(&self) -> Option<&WorldFile<Lif>> {
+        self.lif.as_ref()
+    }
+
+    pub fn hit(&self) -> Option<&WorldFile<Hit>> {
+        self.hit.as_ref()
+    }
+}
+
+/// Every per-city file of a world directory such as `data/world/mp`.
+///
+/// The LF terrain is required since other files are laid out by its blocks, everything
+/// else is loaded if present. File and directory names are matched regardless of case.
+#[derive(Debug)]
+pub struct World {
+    path: PathBuf,
+    lf: WorldFile<Lf>,
+    lbf: Option<WorldFile<Lbf>>,
+    lgf: Option<WorldFile<Lgf>>,
+    llf: Option<WorldFile<Llf>>,
+    lof: Option<WorldFile<Lof>>,
+    layers: Vec<WorldLayer>,
+    chpaths: Vec<WorldFile<Chpath>>,
+}
+
+impl World {
+    pub fn open<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        Self::open_with_codepage(path, Lof::DEFAULT_CODEPAGE)
+    }
+
+    /// Load a world whose model table names are in the given codepage
+    pub fn open_with_codepage<P: AsRef<Path>>(path: P, codepage: Codepage) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let dir_entries = read_dir_sorted(path)?;
+
+        let Some(lf) = load(&dir_entries, LF_FILE_NAME, |reader| {
+            Lf::read_without_data(reader)
+        })?
+        else {
+            anyhow::bail!("{} has no {}", path.display(), LF_FILE_NAME);
+        };
+        let block_count = lf.block_count as usize;
+
+        let lbf = load(&dir_entries, LBF_FILE_NAME, Lbf::parse)?;
+        let lgf = load(&dir_entries, LGF_FILE_NAME, Lgf::read)?;
+        let llf = load(&dir_entries, LLF_FILE_NAME, Llf::read)?;
+        let lof = load(&dir_entries, LOF_FILE_NAME, |reader| {
+            Lof::read_without_data_with_codepage(reader, codepage)
+        })?;
+
+        let mut layers = Vec::new();
+        let mut chpaths = Vec::new();
+        for dir_entry_path in dir_entries.iter() {
+            if dir_entry_path.is_dir() {
+                let layer_entries = read_dir_sorted(dir_entry_path)?;
+                let layer = WorldLayer {
+                    name: file_name(dir_entry_path).to_string(),
+                    loi: load(&layer_entries, LOI_FILE_NAME, |reader| {
+                        Loi::read(reader, block_count)
+                    })?,
+                    lif: load(&layer_entries, LIF_FILE_NAME, Lif::read)?,
+                    hit: load(&layer_entries, HIT_FILE_NAME, Hit::read)?,
+                };
+                if layer.loi.is_some() || layer.lif.is_some() || layer.hit.is_some() {
+                    layers.push(layer);
+                }
+            } else if dir_entry_path
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case(CHPATH_EXTENSION))
+            {
+                chpaths.push(load_file(dir_entry_path, Chpath::read)?);
+            }
+        }
+
+        Ok(Self {
+            path: path.to_owned(),
+            lf,
+            lbf,
+            lgf,
+            llf,
+            lof,
+            layers,
+            chpaths,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn lf(&self) -> &WorldFile<Lf> {
+        &self.lf
+    }
+
+    pub fn lbf(&self) -> Option<&WorldFile<Lbf>> {
+        self.lbf.as_ref()
+    }
+
+    pub fn lgf(&self) -> Option<&WorldFile<Lgf>> {
+        self.lgf.as_ref()
+    }
+
+    pub fn llf(&self) -> Option<&WorldFile<Llf>> {
+        self.llf.as_ref()
+    }
+
+    pub fn lof(&self) -> Option<&WorldFile<Lof>> {
+        self.lof.as_ref()
+    }
+
+    /// Layers in directory name order
+    pub fn layers(&self) -> &[WorldLayer] {
+        &self.layers
+    }
+
+    /// Look up a layer ignoring case, e.g. `main`
+    pub fn layer(&self, name: &str) -> Option<&WorldLayer> {
+        self.layers
+            .iter()
+            .find(|layer| layer.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Layer with the city's own objects, as opposed to per-track ones
+    pub fn main_layer(&self) -> Option<&WorldLayer> {
+        self.layer("Main")
+    }
+
+    /// AI and traffic paths in file name order
+    pub fn chpaths(&self) -> &[WorldFile<Chpath>] {
+        &self.chpaths
+    }
+
+    /// Number of terrain blocks, as given by the LF header
+    pub fn block_count(&self) -> usize {
+        self.lf.block_count as usize
+    }
+
+    /// Check that the loaded files agree with each other, returning every inconsistency found
+    pub fn check(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let block_count = self.block_count();
+
+        if self.lf.blocks.len() != block_count {
+            problems.push(format!(
+                "{} has {} blocks, header says {}",
+                LF_FILE_NAME,
+                self.lf.blocks.len(),
+                block_count
+            ));
+        }
+
+        if let Some(lbf) = self.lbf() {
+            if lbf.blocks.len() != lbf.header.block_count as usize {
+                problems.push(format!(
+                    "{} has {} blocks, header says {}",
+                    LBF_FILE_NAME,
+                    lbf.blocks.len(),
+                    lbf.header.block_count
+                ));
+            }
+            let object_count = lbf
+                .blocks
+                .iter()
+                .map(|block| block.objects.len())
+                .sum::<usize>();
+            if object_count != lbf.header.block_object_count as usize {
+                problems.push(format!(
+                    "{} has {} block objects, header says {}",
+                    LBF_FILE_NAME, object_count, lbf.header.block_object_count
+                ));
+            }
+        }
+
+        let mut check_block_indices = |file_name: &str, block_indices: Vec<u32>| {
+            for block_index in block_indices {
+                if block_index as usize >= block_count {
+                    problems.push(format!(
+                        "{} refers to block {} of {}",
+                        file_name, block_index, block_count
+                    ));
+                }
+            }
+        };
+        if let Some(lgf) = self.lgf() {
+            check_block_indices(
+                LGF_FILE_NAME,
+                lgf.blocks.iter().map(|block| block.block_index).collect(),
+            );
+        }
+        if let Some(llf) = self.llf() {
+            check_block_indices(
+                LLF_FILE_NAME,
+                llf.blocks.iter().map(|block| block.block_index).collect(),
+            );
+        }
+        for layer in self.layers.iter() {
+            if let Some(lif) = layer.lif() {
+                check_block_indices(
+                    &format!("{}/{}", layer.name, LIF_FILE_NAME),
+                    lif.blocks.iter().map(|block| block.index).collect(),
+                );
+            }
+        }
+
+        let model_indices = self.lof().map(|lof| {
+            lof.models
+                .iter()
+                .map(|model| model.index)
+                .collect::<HashSet<_>>()
+        });
+        for layer in self.layers.iter() {
+            let Some(loi) = layer.loi() else {
+                continue;
+            };
+            let loi_file_name = format!("{}/{}", layer.name, LOI_FILE_NAME);
+
+            if loi.blocks.len() > block_count {
+                problems.push(format!(
+                    "{} has {} blocks, terrain has {}",
+                    loi_file_name,
+                    loi.blocks.len(),
+                    block_count
+                ));
+            }
+
+            let Some(model_indices) = model_indices.as_ref() else {
+                continue;
+            };
+            for object in loi.blocks.iter().flat_map(|block| block.objects.iter()) {
+                if !model_indices.contains(&object.model_table_index) {
+                    problems.push(format!(
+                        "{} object {} uses missing model {}",
+                        loi_file_name, object.object_index, object.model_table_index
+                    ));
+                }
+            }
+        }
+
+        problems
+    }
+}
+
+fn file_name(path: &Path) -> &str {
+    path.file_name()
+        .and_then(|file_name| file_name.to_str())
+        .unwrap_or_default()
+}
+
+fn read_dir_sorted(path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut paths = std::fs::read_dir(path)
+        .map_err(|e| anyhow::anyhow!("could not read {}: {}", path.display(), e))?
+        .map(|dir_entry| Ok(dir_entry?.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    paths.sort_by_cached_key(|path| file_name(path).to_lowercase());
+    Ok(paths)
+}
+
+/// Parse the file with the given name from a directory listing, if there is one
+fn load<T>(
+    dir_entries: &[PathBuf],
+    name: &str,
+    read: impl FnOnce(&mut BufReader<File>) -> anyhow::Result<T>,
+) -> anyhow::Result<Option<WorldFile<T>>> {
+    dir_entries
+        .iter()
+        .find(|path| path.is_file() && file_name(path).eq_ignore_ascii_case(name))
+        .map(|path| load_file(path, read))
+        .transpose()
+}
+
+fn load_file<T>(
+    path: &Path,
+    read: impl FnOnce(&mut BufReader<File>) -> anyhow::Result<T>,
+) -> anyhow::Result<WorldFile<T>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let contents = read(&mut reader)
+        .map_err(|e| anyhow::anyhow!("could not read {}: {}", path.display(), e))?;
+
+    Ok(WorldFile {
+        path: path.to_owned(),
+        contents,
+    })
+}
@@ -0,0 +1,132 @@
+use binrw::BinWrite;
+use slidetown::agt::{
+    keys::{self, KeyRecovery},
+    AgtBuilder, AgtReader,
+};
+use std::io::Cursor;
+
+static SPOOKY_KEY: &[u8] = include_bytes!("../resources/agt/spooky_key.bin");
+
+#[test]
+fn known_keys() {
+    assert_eq!(keys::default_key().key, SPOOKY_KEY);
+    assert_eq!(keys::find_known_key("SPOOKY").unwrap().key, SPOOKY_KEY);
+    assert!(keys::find_known_key("missing").is_none());
+}
+
+#[test]
+fn dev_neodata_detect_key() -> anyhow::Result<()> {
+    let data = std::fs::read("resources/agt/dev_neodata.agt")?;
+
+    let known_key = keys::detect_key(&mut Cursor::new(&data)).expect("key should be detected");
+    assert_eq!(known_key.key, SPOOKY_KEY);
+
+    assert!(keys::is_valid_key(&mut Cursor::new(&data), SPOOKY_KEY));
+    assert!(!keys::is_valid_key(
+        &mut Cursor::new(&data),
+        &SPOOKY_KEY[1..]
+    ));
+    assert!(!keys::is_valid_key(&mut Cursor::new(&data), &[0x55; 16]));
+
+    // Damaged or missing entry data doesn't get in the way, only the table of contents counts
+    let mut damaged = data.clone();
+    let damaged_length = damaged.len() * 3 / 4;
+    damaged.truncate(damaged_length);
+    for byte in damaged[damaged_length - 64..].iter_mut() {
+        *byte = !*byte;
+    }
+    let known_key = keys::detect_key(&mut Cursor::new(&damaged)).expect("key should be detected");
+    assert_eq!(known_key.key, SPOOKY_KEY);
+
+    Ok(())
+}
+
+#[test]
+fn load_key_dir() -> anyhow::Result<()> {
+    let keys_dir = std::env::temp_dir().join(format!("slidetown_keys_{}", std::process::id()));
+    std::fs::create_dir_all(&keys_dir)?;
+    std::fs::write(keys_dir.join("tw.bin"), [0x55; 16])?;
+    std::fs::write(keys_dir.join("kr.bin"), SPOOKY_KEY)?;
+    std::fs::write(keys_dir.join("notes.txt"), b"not a key")?;
+
+    let key_files = keys::load_key_dir(&keys_dir)?;
+    assert_eq!(
+        key_files
+            .iter()
+            .map(|key_file| key_file.name.as_str())
+            .collect::<Vec<_>>(),
+        ["kr", "tw"]
+    );
+    assert_eq!(key_files[0].key, SPOOKY_KEY);
+
+    std::fs::write(keys_dir.join("empty.bin"), b"")?;
+    assert!(keys::load_key_dir(&keys_dir).is_err());
+
+    std::fs::remove_dir_all(&keys_dir)?;
+
+    Ok(())
+}
+
+#[test]
+fn dev_neodata_recover_key_from_plaintext() -> anyhow::Result<()> {
+    let data = std::fs::read("resources/agt/dev_neodata.agt")?;
+
+    // Too few entries to recover the key from structure alone, but a table of contents
+    // rebuilt from an extracted copy of the archive covers the whole key
+    let entries = {
+        let mut reader = Cursor::new(&data);
+        let mut agt_reader = AgtReader::new(&mut reader, SPOOKY_KEY);
+        let header = agt_reader.read_header()?;
+        agt_reader.read_entries(header.file_count)?
+    };
+    let mut known_toc = Cursor::new(Vec::new());
+    for entry in entries.iter() {
+        entry.write_le(&mut known_toc)?;
+    }
+
+    let mut recovery = KeyRecovery::new(&data);
+    recovery.set_max_key_length(128);
+    recovery.add_known_plaintext(32, &known_toc.into_inner());
+    assert_eq!(recovery.recover()?, SPOOKY_KEY);
+
+    // Without it there isn't enough to go on
+    assert!(keys::recover_key(&data).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn recover_key_from_structure() -> anyhow::Result<()> {
+    // Deterministic, compressible but varied entry contents
+    let mut seed = 0x1234_5678u32;
+    let mut next = move || {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        seed >> 16
+    };
+    let words = [
+        "drift", "city", "quest", "car", "tune", "race", "block", "terrain", "lane", "object",
+    ];
+
+    let mut builder = AgtBuilder::new();
+    for i in 0..400 {
+        let length = 200 + (next() % 30000) as usize;
+        let mut data = Vec::with_capacity(length);
+        while data.len() < length {
+            data.extend(words[next() as usize % words.len()].as_bytes());
+            data.push(b' ');
+            data.extend(next().to_string().as_bytes());
+            data.push(b'\n');
+        }
+        data.truncate(length);
+        builder.add_entry_memory(format!("Data\\folder{}\\file{}.txt", i % 7, i), &data);
+    }
+
+    let mut archive = Cursor::new(Vec::new());
+    builder.write(&mut archive, SPOOKY_KEY)?;
+    let archive = archive.into_inner();
+
+    let recovered_key = keys::recover_key(&archive)?;
+    assert_eq!(recovered_key, SPOOKY_KEY);
+
+    Ok(())
+}
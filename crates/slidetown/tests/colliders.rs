@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+use slidetown::{
+    colliders::{ColliderExtras, ColliderMesh, ColliderShape, CAPSULE_RADIUS},
+    parsers::loi::{Loi, Vec3f},
+};
+
+fn dot(a: Vec3f, b: Vec3f) -> f32 {
+    a.0 * b.0 + a.1 * b.1 + a.2 * b.2
+}
+
+/// Compare with a tolerance relative to the expected value when it's above 1, positions
+/// are thousands of units from the origin
+fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+    assert!(
+        (actual - expected).abs() < tolerance * expected.abs().max(1.0),
+        "{} is not close to {}",
+        actual,
+        expected
+    );
+}
+
+#[test]
+fn dcr_mp_main_colliders() -> anyhow::Result<()> {
+    let mut file = std::fs::File::open("resources/loi/dcr_mp_main_object0.loi")?;
+    let loi = Loi::read(&mut file, 3854)?;
+
+    let objects = loi
+        .blocks
+        .iter()
+        .flat_map(|block| block.objects.iter())
+        .map(|object| (object.object_index, object))
+        .collect::<HashMap<_, _>>();
+
+    let (mut box_count, mut capsule_count) = (0, 0);
+    for collider in loi.colliders.iter() {
+        // Rotations are orthonormal, so meshes keep their size
+        let (x, y, z) = collider.rotation;
+        for (a, b) in [(x, x), (y, y), (z, z)] {
+            assert_close(dot(a, b), 1.0, 1e-3);
+        }
+        for (a, b) in [(x, y), (y, z), (z, x)] {
+            assert_close(dot(a, b), 0.0, 1e-3);
+        }
+
+        // Every collider belongs to an object that refers back to it
+        let object = objects[&collider.object_index];
+        assert_eq!(object.collider_index, collider.collider_index as i32);
+        assert_eq!(
+            serde_json::to_value(ColliderExtras::from(collider))?,
+            serde_json::json!({
+                "object_index": object.object_index,
+                "collider_index": object.collider_index,
+                "type": collider.r#type,
+            })
+        );
+
+        let mesh = ColliderMesh::new(collider).expect("every collider type is known");
+        let world_vertices = mesh.world_vertices(collider);
+        let centroid = world_vertices.iter().fold((0.0, 0.0, 0.0), |sum, vertex| {
+            (sum.0 + vertex.0, sum.1 + vertex.1, sum.2 + vertex.2)
+        });
+        let vertex_count = world_vertices.len() as f32;
+        assert_close(centroid.0 / vertex_count, collider.position.0, 1e-5);
+        assert_close(centroid.1 / vertex_count, collider.position.1, 1e-5);
+        assert_close(centroid.2 / vertex_count, collider.position.2, 1e-5);
+
+        // Extents along each of the collider's own axes, in the world
+        let extents = [(x.0, y.0, z.0), (x.1, y.1, z.1), (x.2, y.2, z.2)].map(|axis| {
+            world_vertices
+                .iter()
+                .map(|&vertex| {
+                    let offset = (
+                        vertex.0 - collider.position.0,
+                        vertex.1 - collider.position.1,
+                        vertex.2 - collider.position.2,
+                    );
+                    dot(offset, axis)
+                })
+                .fold((f32::MAX, f32::MIN), |(min, max), projected| {
+                    (min.min(projected), max.max(projected))
+                })
+        });
+
+        match ColliderShape::new(collider).unwrap() {
+            ColliderShape::Box { half_size } => {
+                box_count += 1;
+                assert_eq!(mesh.vertices.len(), 8);
+                assert_eq!(mesh.triangles.len(), 12);
+
+                let size = [collider.size.0, collider.size.1, collider.size.2];
+                assert_eq!(
+                    [half_size.0, half_size.1, half_size.2],
+                    size.map(|s| s / 2.0)
+                );
+                for ((min, max), size) in extents.into_iter().zip(size) {
+                    assert!(size > 0.0);
+                    assert_close(min, -size / 2.0, 1e-3);
+                    assert_close(max, size / 2.0, 1e-3);
+                }
+            }
+            ColliderShape::Capsule {
+                radius,
+                half_height,
+            } => {
+                capsule_count += 1;
+                assert_eq!(radius, CAPSULE_RADIUS);
+                assert_eq!(half_height, collider.unknown5);
+
+                let [(x_min, x_max), (y_min, y_max), (z_min, z_max)] = extents;
+                for extent in [x_min, y_min] {
+                    assert_close(extent, -radius, 1e-3);
+                }
+                for extent in [x_max, y_max] {
+                    assert_close(extent, radius, 1e-3);
+                }
+                assert_close(z_min, -(half_height + radius), 1e-3);
+                assert_close(z_max, half_height + radius, 1e-3);
+            }
+        }
+    }
+
+    assert_eq!((box_count, capsule_count), (347, 3881));
+
+    Ok(())
+}
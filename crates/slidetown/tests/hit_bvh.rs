@@ -0,0 +1,172 @@
+use slidetown::{
+    hit::{Axis, HitBvh},
+    parsers::hit::Hit,
+};
+
+fn assert_close(actual: (f32, f32, f32), expected: (f32, f32, f32)) {
+    let offset = (
+        actual.0 - expected.0,
+        actual.1 - expected.1,
+        actual.2 - expected.2,
+    );
+    assert!(
+        offset.0.abs() < 1e-4 && offset.1.abs() < 1e-4 && offset.2.abs() < 1e-4,
+        "{:?} is not close to {:?}",
+        actual,
+        expected
+    );
+}
+
+/// A 10x10 ground square at height 0 with a 2x10 bridge deck at height 5 across it,
+/// and a wall 2 high along x = 8
+fn ground_and_bridge() -> anyhow::Result<Hit> {
+    Hit::from_triangles(
+        vec![
+            (0.0, 0.0, 0.0),
+            (10.0, 0.0, 0.0),
+            (10.0, 10.0, 0.0),
+            (0.0, 10.0, 0.0),
+            (4.0, 0.0, 5.0),
+            (6.0, 0.0, 5.0),
+            (6.0, 10.0, 5.0),
+            (4.0, 10.0, 5.0),
+            (8.0, 0.0, 0.0),
+            (8.0, 10.0, 0.0),
+            (8.0, 10.0, 2.0),
+            (8.0, 0.0, 2.0),
+        ],
+        vec![0, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7, 8, 9, 10, 8, 10, 11],
+    )
+}
+
+#[test]
+fn hit_bvh_queries() -> anyhow::Result<()> {
+    let bvh = HitBvh::new(&ground_and_bridge()?)?;
+    assert_eq!(bvh.triangle_count(), 6);
+    assert_eq!(bvh.bounds(), Some(((0.0, 0.0, 0.0), (10.0, 10.0, 5.0))));
+
+    let ray_hit = bvh
+        .raycast((2.0, 3.0, 10.0), (0.0, 0.0, -2.0), 100.0)
+        .unwrap();
+    assert_eq!(ray_hit.distance, 5.0);
+    assert_eq!(ray_hit.point, (2.0, 3.0, 0.0));
+    assert!(ray_hit.triangle_index < 2);
+
+    // The bridge is in the way from above, but not from below
+    let ray_hit = bvh
+        .raycast((5.0, 3.0, 10.0), (0.0, 0.0, -1.0), 100.0)
+        .unwrap();
+    assert_eq!(ray_hit.point, (5.0, 3.0, 5.0));
+    assert!((2..4).contains(&ray_hit.triangle_index));
+    let ray_hit = bvh
+        .raycast((5.0, 3.0, -1.0), (0.0, 0.0, 1.0), 100.0)
+        .unwrap();
+    assert_eq!(ray_hit.point, (5.0, 3.0, 0.0));
+    assert!(bvh
+        .raycast((5.0, 3.0, 10.0), (0.0, 0.0, -1.0), 4.0)
+        .is_none());
+    assert!(bvh
+        .raycast((5.0, 3.0, 10.0), (0.0, 0.0, 1.0), 100.0)
+        .is_none());
+
+    assert_eq!(bvh.height_at((2.0, 3.0, 0.0), Axis::Z), Some(0.0));
+    assert_eq!(bvh.height_at((5.0, 3.0, -50.0), Axis::Z), Some(5.0));
+    assert_eq!(bvh.height_below((5.0, 3.0, 4.0), Axis::Z), Some(0.0));
+    assert_eq!(bvh.height_below((5.0, 3.0, 5.0), Axis::Z), Some(5.0));
+    assert_eq!(bvh.height_below((5.0, 3.0, -1.0), Axis::Z), None);
+    assert_eq!(bvh.height_at((11.0, 3.0, 0.0), Axis::Z), None);
+    // Along X the ground and bridge are seen edge-on, only the wall is crossed
+    assert_eq!(bvh.height_at((0.0, 3.0, 1.0), Axis::X), Some(8.0));
+    assert_eq!(bvh.height_below((7.0, 3.0, 1.0), Axis::X), None);
+
+    let closest = bvh.closest_point((5.0, 3.0, 4.0)).unwrap();
+    assert_close(closest.point, (5.0, 3.0, 5.0));
+    assert!((closest.distance - 1.0).abs() < 1e-4);
+    let closest = bvh.closest_point((13.0, 14.0, 0.0)).unwrap();
+    assert_close(closest.point, (10.0, 10.0, 0.0));
+    assert!((closest.distance - 5.0).abs() < 1e-4);
+
+    assert!(bvh.contains_xy(5.0, 5.0));
+    assert!(bvh.contains_xy(10.0, 10.0));
+    assert!(!bvh.contains_xy(10.5, 5.0));
+    assert!(!bvh.contains_xy(-0.1, -0.1));
+
+    Ok(())
+}
+
+#[test]
+fn hit_bvh_rejects_bad_indices() {
+    let mut hit = ground_and_bridge().unwrap();
+    hit.indices.push(0);
+    assert!(HitBvh::new(&hit).is_err());
+
+    hit.indices.extend([1, 12]);
+    assert!(HitBvh::new(&hit).is_err());
+}
+
+#[test]
+fn hit_bvh_empty() -> anyhow::Result<()> {
+    let bvh = HitBvh::new(&Hit::from_triangles(vec![], vec![])?)?;
+
+    assert_eq!(bvh.bounds(), None);
+    assert!(bvh
+        .raycast((0.0, 0.0, 0.0), (0.0, 0.0, -1.0), 1.0)
+        .is_none());
+    assert!(bvh.closest_point((0.0, 0.0, 0.0)).is_none());
+    assert!(bvh.height_at((0.0, 0.0, 0.0), Axis::Z).is_none());
+    assert!(!bvh.contains_xy(0.0, 0.0));
+
+    Ok(())
+}
+
+#[test]
+fn mp_track1_area_hit_bvh() -> anyhow::Result<()> {
+    let mut file = std::fs::File::open("resources/hit/dcr_mp_track1_area.hit")?;
+    let hit = Hit::read(&mut file)?;
+    let bvh = HitBvh::new(&hit)?;
+    assert_eq!(bvh.triangle_count(), hit.triangles().count());
+
+    let (_, max) = bvh.bounds().unwrap();
+    for [a, b, c] in hit.triangles().step_by(97) {
+        let [a, b, c] = [a, b, c].map(|index| hit.verts[index as usize]);
+        let centroid = (
+            (a.0 + b.0 + c.0) / 3.0,
+            (a.1 + b.1 + c.1) / 3.0,
+            (a.2 + b.2 + c.2) / 3.0,
+        );
+
+        // Slivers lose some precision this far from the origin
+        assert!(bvh.closest_point(centroid).unwrap().distance < 1e-2);
+
+        // Walls and slivers close to them can't be found reliably from above
+        let (u, v) = (
+            (b.0 - a.0, b.1 - a.1, b.2 - a.2),
+            (c.0 - a.0, c.1 - a.1, c.2 - a.2),
+        );
+        let normal = (
+            u.1 * v.2 - u.2 * v.1,
+            u.2 * v.0 - u.0 * v.2,
+            u.0 * v.1 - u.1 * v.0,
+        );
+        let length = (normal.0 * normal.0 + normal.1 * normal.1 + normal.2 * normal.2).sqrt();
+        if normal.2.abs() < 0.1 * length {
+            continue;
+        }
+        assert!(bvh.contains_xy(centroid.0, centroid.1));
+
+        // Some surface is at least as high as this triangle, maybe one above it
+        let height = bvh.height_at(centroid, Axis::Z).unwrap();
+        assert!(height >= centroid.2 - 1e-3);
+        assert!(height <= max.2);
+
+        let below = bvh
+            .height_below((centroid.0, centroid.1, centroid.2 + 1e-3), Axis::Z)
+            .unwrap();
+        assert!((below - centroid.2).abs() < 1e-2);
+    }
+
+    assert!(!bvh.contains_xy(0.0, -1000.0));
+    assert_eq!(bvh.height_at((0.0, -1000.0, 0.0), Axis::Z), None);
+
+    Ok(())
+}
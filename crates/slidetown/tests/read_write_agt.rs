@@ -1,8 +1,14 @@
 use slidetown::{
-    agt::{AgtBuilder, AgtReader},
-    parsers::agt::{Entry, Header},
+    agt::{
+        delta_builder, diff_agt, merge_agt, sync_agt, AgtBuilder, AgtBuilderOptions, AgtEditor,
+        AgtReader, AgtSliceReader, ConflictPolicy, MergeReport,
+    },
+    parsers::{
+        agt::{Entry, Header},
+        Codepage,
+    },
 };
-use std::io::Cursor;
+use std::io::{Cursor, Read, Seek, SeekFrom};
 
 fn check_neodata(agt_buffer: &[u8], agt_key: &[u8]) -> anyhow::Result<Vec<(Entry, Vec<u8>)>> {
     let mut agt_file = Cursor::new(agt_buffer);
@@ -74,12 +80,862 @@ fn dev_neodata() -> anyhow::Result<()> {
 
     let new_entries_with_data = check_neodata(&out_buf, spooky_key)?;
 
-    for ((_old_entry, old_data), (_new_entry, new_data)) in entries_with_data
-        .into_iter()
-        .zip(new_entries_with_data.into_iter())
+    for ((_old_entry, old_data), (_new_entry, new_data)) in
+        entries_with_data.into_iter().zip(new_entries_with_data)
+    {
+        assert_eq!(old_data, new_data);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn dev_neodata_copy_entries() -> anyhow::Result<()> {
+    let spooky_key: &[u8] = include_bytes!("../resources/agt/spooky_key.bin");
+    let agt_path = "resources/agt/dev_neodata.agt";
+    let agt_buffer = std::fs::read(agt_path)?;
+
+    let entries_with_data = check_neodata(&agt_buffer, spooky_key)?;
+
+    let mut builder = AgtBuilder::new();
+    for (entry, _data) in entries_with_data.iter() {
+        builder.add_agt_entry(agt_path, spooky_key, entry.clone());
+    }
+
+    let mut out_buf = Vec::new();
+    let mut out_file = Cursor::new(&mut out_buf);
+    builder.write(&mut out_file, spooky_key)?;
+
+    // Copied chunks are not recompressed, so the layout matches the original
+    assert_eq!(agt_buffer[32..], out_buf[32..]);
+
+    let new_entries_with_data = check_neodata(&out_buf, spooky_key)?;
+
+    for ((_old_entry, old_data), (_new_entry, new_data)) in
+        entries_with_data.into_iter().zip(new_entries_with_data)
     {
         assert_eq!(old_data, new_data);
     }
 
     Ok(())
 }
+
+#[test]
+fn dev_neodata_stream_entries() -> anyhow::Result<()> {
+    let spooky_key: &[u8] = include_bytes!("../resources/agt/spooky_key.bin");
+    let agt_buffer = include_bytes!("../resources/agt/dev_neodata.agt");
+
+    let entries_with_data = check_neodata(agt_buffer, spooky_key)?;
+
+    let mut agt_file = Cursor::new(agt_buffer);
+    let mut agt_reader = AgtReader::new(&mut agt_file, spooky_key);
+
+    for (entry, data) in entries_with_data.iter() {
+        let mut entry_reader = agt_reader.open_entry(entry)?;
+
+        // Read across chunk boundaries in odd-sized pieces
+        let mut streamed = Vec::new();
+        let mut buf = [0u8; 5000];
+        loop {
+            let bytes_read = entry_reader.read(&mut buf)?;
+            if bytes_read == 0 {
+                break;
+            }
+            streamed.extend_from_slice(&buf[..bytes_read]);
+        }
+        assert_eq!(&streamed, data);
+
+        // Seek backwards into earlier chunks and near the end
+        for &offset in &[
+            data.len() as u64 / 2,
+            16383,
+            16384,
+            0,
+            data.len() as u64 - 3,
+        ] {
+            let offset = offset.min(data.len() as u64 - 1);
+            entry_reader.seek(SeekFrom::Start(offset))?;
+            let mut byte = [0u8; 1];
+            entry_reader.read_exact(&mut byte)?;
+            assert_eq!(byte[0], data[offset as usize]);
+        }
+
+        assert_eq!(
+            entry_reader.seek(SeekFrom::End(0))?,
+            entry.decompressed_length as u64
+        );
+        assert_eq!(entry_reader.read(&mut buf)?, 0);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn dev_neodata_slice_reader() -> anyhow::Result<()> {
+    let spooky_key: &[u8] = include_bytes!("../resources/agt/spooky_key.bin");
+    let agt_path = "resources/agt/dev_neodata.agt";
+    let agt_buffer = std::fs::read(agt_path)?;
+
+    let entries_with_data = check_neodata(&agt_buffer, spooky_key)?;
+
+    let slice_reader = AgtSliceReader::new(agt_buffer.as_slice(), spooky_key)?;
+    assert_eq!(slice_reader.header().file_count, 5);
+    assert_eq!(
+        slice_reader.entries(),
+        entries_with_data
+            .iter()
+            .map(|(entry, _data)| entry.clone())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        slice_reader.find_entry("neodata/nc_quest.xlt"),
+        Some(&entries_with_data[4].0)
+    );
+    assert!(slice_reader.find_entry("NeoData\\missing.xlt").is_none());
+
+    // Every entry read from its own thread, once whole and once split over several threads
+    std::thread::scope(|scope| {
+        for (entry, data) in entries_with_data.iter() {
+            let slice_reader = &slice_reader;
+            scope.spawn(move || {
+                assert!(slice_reader.read_entry_data(entry).unwrap() == *data);
+                assert!(slice_reader.read_entry_data_threaded(entry, 4).unwrap() == *data);
+
+                let mut streamed = Vec::new();
+                slice_reader
+                    .open_entry(entry)
+                    .unwrap()
+                    .read_to_end(&mut streamed)
+                    .unwrap();
+                assert!(streamed == *data);
+            });
+        }
+    });
+
+    let mmap_reader = AgtSliceReader::open_mmap(agt_path, spooky_key, Default::default())?;
+    for (entry, data) in entries_with_data.iter() {
+        assert!(mmap_reader.read_entry_data_threaded(entry, 0)? == *data);
+    }
+
+    // Damaged data is an error, not a panic
+    let truncated = &agt_buffer[..agt_buffer.len() - 100];
+    let truncated_reader = AgtSliceReader::new(truncated, spooky_key)?;
+    let last_entry = &truncated_reader.entries()[4];
+    assert!(truncated_reader.read_entry_data(last_entry).is_err());
+    assert!(truncated_reader
+        .read_entry_data_threaded(last_entry, 4)
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn dev_neodata_identical_rebuild() -> anyhow::Result<()> {
+    let spooky_key: &[u8] = include_bytes!("../resources/agt/spooky_key.bin");
+    let agt_path = "resources/agt/dev_neodata.agt";
+    let agt_buffer = std::fs::read(agt_path)?;
+
+    let entries_with_data = check_neodata(&agt_buffer, spooky_key)?;
+
+    // Re-adding unchanged data reuses the original chunks
+    let mut builder = AgtBuilder::from_agt(agt_path, spooky_key)?;
+    for (entry, data) in entries_with_data.iter().rev() {
+        builder.add_entry_memory(entry.path.clone(), data);
+    }
+
+    let mut out_buf = Vec::new();
+    let mut out_file = Cursor::new(&mut out_buf);
+    builder.write(&mut out_file, spooky_key)?;
+
+    assert_eq!(agt_buffer, out_buf);
+
+    // Changed data is recompressed, everything else stays in place
+    let (changed_entry, changed_data) = &entries_with_data[1];
+    let mut changed_data = changed_data.clone();
+    changed_data[2] = b'X';
+
+    let mut builder = AgtBuilder::from_agt(agt_path, spooky_key)?;
+    builder.add_entry_memory(changed_entry.path.clone(), &changed_data);
+
+    let mut out_buf = Vec::new();
+    let mut out_file = Cursor::new(&mut out_buf);
+    builder.write(&mut out_file, spooky_key)?;
+
+    let new_entries_with_data = check_neodata(&out_buf, spooky_key)?;
+    assert_eq!(new_entries_with_data[1].1, changed_data);
+    assert_eq!(agt_buffer[..32], out_buf[..32]);
+    for (i, ((_old_entry, old_data), (_new_entry, new_data))) in entries_with_data
+        .into_iter()
+        .zip(new_entries_with_data)
+        .enumerate()
+    {
+        if i != 1 {
+            assert_eq!(old_data, new_data);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn dev_neodata_edit() -> anyhow::Result<()> {
+    let spooky_key: &[u8] = include_bytes!("../resources/agt/spooky_key.bin");
+    let agt_path = "resources/agt/dev_neodata.agt";
+    let agt_buffer = std::fs::read(agt_path)?;
+
+    let entries_with_data = check_neodata(&agt_buffer, spooky_key)?;
+
+    let mut editor = AgtEditor::open(agt_path, spooky_key)?;
+    editor.replace_memory("neodata/nc_quest.xlt", b"replaced")?;
+    editor.remove("NeoData\\NC_object.xlt")?;
+    editor.add_memory("NeoData\\NC_new.xlt".to_string(), b"added")?;
+
+    assert!(editor
+        .add_memory("NeoData\\NC_chapter.xlt".to_string(), b"dupe")
+        .is_err());
+    assert!(editor.remove("NeoData\\NC_missing.xlt").is_err());
+
+    let mut out_buf = Vec::new();
+    let mut out_file = Cursor::new(&mut out_buf);
+    editor.save(&mut out_file)?;
+
+    let mut agt_file = Cursor::new(&out_buf);
+    let mut agt_reader = AgtReader::new(&mut agt_file, spooky_key);
+    let header = agt_reader.read_header()?;
+    let entries = agt_reader.read_entries(header.file_count)?;
+
+    assert_eq!(
+        entries.iter().map(|e| e.path.as_str()).collect::<Vec<_>>(),
+        vec![
+            "NeoData\\NC_chapter.xlt",
+            "NeoData\\NC_mission.xlt",
+            "NeoData\\NC_objectDef.xlt",
+            "NeoData\\NC_quest.xlt",
+            "NeoData\\NC_new.xlt",
+        ]
+    );
+
+    assert_eq!(
+        agt_reader.read_entry_data(&entries[0])?,
+        entries_with_data[0].1
+    );
+    assert_eq!(
+        agt_reader.read_entry_data(&entries[1])?,
+        entries_with_data[1].1
+    );
+    assert_eq!(
+        agt_reader.read_entry_data(&entries[2])?,
+        entries_with_data[3].1
+    );
+    assert_eq!(agt_reader.read_entry_data(&entries[3])?, b"replaced");
+    assert_eq!(agt_reader.read_entry_data(&entries[4])?, b"added");
+
+    Ok(())
+}
+
+#[test]
+fn dev_neodata_verify() -> anyhow::Result<()> {
+    let spooky_key: &[u8] = include_bytes!("../resources/agt/spooky_key.bin");
+    let agt_buffer = include_bytes!("../resources/agt/dev_neodata.agt");
+
+    let verify = |buffer: &[u8]| {
+        let mut agt_file = Cursor::new(buffer);
+        AgtReader::new(&mut agt_file, spooky_key).verify()
+    };
+
+    let report = verify(agt_buffer)?;
+    assert!(report.is_ok());
+    assert_eq!(report.entries.len(), 5);
+
+    // Flip a byte inside the first entry's only chunk
+    let mut corrupt_buffer = agt_buffer.to_vec();
+    corrupt_buffer[300] ^= 0xFF;
+    let report = verify(&corrupt_buffer)?;
+    assert!(!report.is_ok());
+    assert_eq!(
+        report
+            .failed_entries()
+            .map(|entry| entry.path.as_str())
+            .collect::<Vec<_>>(),
+        vec!["NeoData\\NC_chapter.xlt"]
+    );
+
+    // Cut off in the middle of the second entry's chunks
+    let report = verify(&agt_buffer[..40000])?;
+    assert_eq!(report.failed_entries().count(), 4);
+    assert!(report.entries[0].is_ok());
+
+    // Cut off in the middle of the table of contents
+    let report = verify(&agt_buffer[..150])?;
+    assert_eq!(report.errors.len(), 1);
+    assert_eq!(report.entries.len(), 3);
+    assert!(!report.is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn dev_neodata_compression_options() -> anyhow::Result<()> {
+    let spooky_key: &[u8] = include_bytes!("../resources/agt/spooky_key.bin");
+    let agt_buffer = include_bytes!("../resources/agt/dev_neodata.agt");
+
+    let entries_with_data = check_neodata(agt_buffer, spooky_key)?;
+
+    let build = |options: AgtBuilderOptions, extra: Option<&[u8]>| -> anyhow::Result<Vec<u8>> {
+        let mut builder = AgtBuilder::new();
+        builder.set_preserve_order(true);
+        builder.set_options(options);
+        for (entry, data) in entries_with_data.iter() {
+            builder.add_entry_memory(entry.path.clone(), data);
+        }
+        if let Some(extra) = extra {
+            builder.add_entry_memory("NeoData\\noise.bin".to_string(), extra);
+        }
+
+        let mut out_buf = Vec::new();
+        builder.write(&mut Cursor::new(&mut out_buf), spooky_key)?;
+        Ok(out_buf)
+    };
+
+    // The output is the same no matter how many threads compress
+    let single_threaded = build(
+        AgtBuilderOptions {
+            threads: 1,
+            ..Default::default()
+        },
+        None,
+    )?;
+    let multi_threaded = build(
+        AgtBuilderOptions {
+            threads: 4,
+            ..Default::default()
+        },
+        None,
+    )?;
+    assert!(single_threaded == multi_threaded);
+    check_neodata(&multi_threaded, spooky_key)?;
+
+    // Random nibbles only compress to about 60%
+    let mut seed = 1u32;
+    let noise = (0..40000)
+        .map(|_| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            ((seed >> 16) & 0x0F) as u8
+        })
+        .collect::<Vec<_>>();
+
+    // Compressed size of the noise entry, which comes last
+    let noise_compressed_length = |buffer: &[u8]| -> anyhow::Result<u64> {
+        let mut agt_file = Cursor::new(buffer);
+        let mut agt_reader = AgtReader::new(&mut agt_file, spooky_key);
+        let header = agt_reader.read_header()?;
+        let entries = agt_reader.read_entries(header.file_count)?;
+        let noise_entry = entries.last().unwrap();
+        assert_eq!(agt_reader.read_entry_data(noise_entry)?, noise);
+
+        Ok(buffer.len() as u64
+            - noise_entry.chunks_offset as u64
+            - noise_entry.chunk_count as u64 * 2)
+    };
+
+    let compressed = build(AgtBuilderOptions::default(), Some(&noise))?;
+    let compressed_length = noise_compressed_length(&compressed)?;
+    assert!(compressed_length < 30000);
+
+    // Not bad enough to fall back
+    let lenient = build(
+        AgtBuilderOptions {
+            poor_compression_ratio: Some(0.9),
+            ..Default::default()
+        },
+        Some(&noise),
+    )?;
+    assert!(compressed == lenient);
+
+    // Stored chunks are 11 bytes of zlib framing larger than their data
+    let stored = build(
+        AgtBuilderOptions {
+            poor_compression_ratio: Some(0.5),
+            poor_compression_level: 0,
+            ..Default::default()
+        },
+        Some(&noise),
+    )?;
+    assert_eq!(
+        noise_compressed_length(&stored)?,
+        noise.len() as u64 + 3 * 11
+    );
+
+    let invalid_level = AgtBuilderOptions {
+        compression_level: 10,
+        ..Default::default()
+    };
+    assert!(build(invalid_level, None).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn dev_neodata_diff_delta() -> anyhow::Result<()> {
+    let spooky_key: &[u8] = include_bytes!("../resources/agt/spooky_key.bin");
+    let agt_path = "resources/agt/dev_neodata.agt";
+
+    let temp_dir = std::env::temp_dir().join(format!("slidetown_diff_{}", std::process::id()));
+    std::fs::create_dir_all(&temp_dir)?;
+    let new_path = temp_dir.join("new.agt");
+    let delta_path = temp_dir.join("delta.agt");
+
+    let mut editor = AgtEditor::open(agt_path, spooky_key)?;
+    editor.replace_memory("NeoData\\NC_quest.xlt", b"replaced")?;
+    editor.remove("NeoData\\NC_object.xlt")?;
+    editor.add_memory("NeoData\\NC_new.xlt".to_string(), b"added")?;
+    editor.save(&mut std::fs::File::create(&new_path)?)?;
+
+    assert!(diff_agt(
+        agt_path,
+        spooky_key,
+        agt_path,
+        spooky_key,
+        Default::default()
+    )?
+    .is_empty());
+
+    let diff = diff_agt(
+        agt_path,
+        spooky_key,
+        &new_path,
+        spooky_key,
+        Default::default(),
+    )?;
+    assert_eq!(diff.unchanged, 3);
+    assert_eq!(diff.added.len(), 1);
+    assert_eq!(diff.added[0].path, "NeoData\\NC_new.xlt");
+    assert_eq!(diff.removed.len(), 1);
+    assert_eq!(diff.removed[0].path, "NeoData\\NC_object.xlt");
+    assert_eq!(diff.changed.len(), 1);
+    assert_eq!(diff.changed[0].path, "NeoData\\NC_quest.xlt");
+    assert_eq!(diff.changed[0].new_length, 8);
+
+    let builder = delta_builder(&diff, &new_path, spooky_key, Default::default())?;
+    builder.write(&mut std::fs::File::create(&delta_path)?, spooky_key)?;
+
+    let mut delta_file = std::fs::File::open(&delta_path)?;
+    let mut agt_reader = AgtReader::new(&mut delta_file, spooky_key);
+    let header = agt_reader.read_header()?;
+    let entries = agt_reader.read_entries(header.file_count)?;
+    assert_eq!(
+        entries.iter().map(|e| e.path.as_str()).collect::<Vec<_>>(),
+        vec!["NeoData\\NC_quest.xlt", "NeoData\\NC_new.xlt"]
+    );
+    assert_eq!(agt_reader.read_entry_data(&entries[0])?, b"replaced");
+    assert_eq!(agt_reader.read_entry_data(&entries[1])?, b"added");
+
+    std::fs::remove_dir_all(&temp_dir)?;
+
+    Ok(())
+}
+
+/// Archive whose entries all have the same path, holding the given data in order.
+///
+/// The builder dedupes paths, so the entries are written with distinct paths of the same
+/// length first. The cipher only depends on the position, so the path bytes can then be
+/// patched in place.
+fn duplicate_path_agt(cipher: &[u8], path: &str, datas: &[&[u8]]) -> anyhow::Result<Vec<u8>> {
+    let mut builder = AgtBuilder::new();
+    builder.set_preserve_order(true);
+    let mut paths = Vec::new();
+    for (index, data) in datas.iter().enumerate() {
+        let unique_path = format!("{}{}", &path[..path.len() - 1], index);
+        builder.add_entry_memory(unique_path.clone(), data);
+        paths.push(unique_path);
+    }
+    let mut agt_buf = Vec::new();
+    builder.write(&mut Cursor::new(&mut agt_buf), cipher)?;
+
+    // Header, then each entry's offset, chunk count, length and path length before its path
+    let mut entry_offset = 32;
+    for unique_path in paths {
+        let path_offset = entry_offset + 16;
+        for (i, (old, new)) in unique_path.bytes().zip(path.bytes()).enumerate() {
+            agt_buf[path_offset + i] ^= old ^ new;
+        }
+        entry_offset = path_offset + unique_path.len();
+    }
+
+    Ok(agt_buf)
+}
+
+#[test]
+fn delta_of_duplicate_paths() -> anyhow::Result<()> {
+    let spooky_key: &[u8] = include_bytes!("../resources/agt/spooky_key.bin");
+
+    let temp_dir =
+        std::env::temp_dir().join(format!("slidetown_duplicate_diff_{}", std::process::id()));
+    std::fs::create_dir_all(&temp_dir)?;
+    let old_path = temp_dir.join("old.agt");
+    let new_path = temp_dir.join("new.agt");
+
+    let mut builder = AgtBuilder::new();
+    builder.add_entry_memory("NeoData\\dup.xlt".to_string(), b"old");
+    builder.write(&mut std::fs::File::create(&old_path)?, spooky_key)?;
+
+    let new_buf = duplicate_path_agt(spooky_key, "NeoData\\dup.xlt", &[b"first", b"shadowed"])?;
+    std::fs::write(&new_path, new_buf)?;
+
+    let diff = diff_agt(&old_path, spooky_key, &new_path, spooky_key, Codepage::Utf8)?;
+    assert_eq!(diff.changed.len(), 1);
+
+    // The delta carries the entry the game reads, not the shadowed one after it
+    let mut delta_buf = Vec::new();
+    delta_builder(&diff, &new_path, spooky_key, Codepage::Utf8)?
+        .write(&mut Cursor::new(&mut delta_buf), spooky_key)?;
+
+    let mut delta_file = Cursor::new(&delta_buf);
+    let mut agt_reader = AgtReader::new(&mut delta_file, spooky_key);
+    let header = agt_reader.read_header()?;
+    let entries = agt_reader.read_entries(header.file_count)?;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].path, "NeoData\\dup.xlt");
+    assert_eq!(agt_reader.read_entry_data(&entries[0])?, b"first");
+
+    std::fs::remove_dir_all(&temp_dir)?;
+
+    Ok(())
+}
+
+#[test]
+fn duplicate_paths_rebuild() -> anyhow::Result<()> {
+    let spooky_key: &[u8] = include_bytes!("../resources/agt/spooky_key.bin");
+
+    let agt_path =
+        std::env::temp_dir().join(format!("slidetown_duplicate_{}.agt", std::process::id()));
+    let agt_buf = duplicate_path_agt(spooky_key, "NeoData\\dup.xlt", &[b"first", b"shadowed"])?;
+    std::fs::write(&agt_path, &agt_buf)?;
+
+    let read_entries = |buf: &[u8]| -> anyhow::Result<Vec<Vec<u8>>> {
+        let mut agt_file = Cursor::new(buf);
+        let mut agt_reader = AgtReader::new(&mut agt_file, spooky_key);
+        let header = agt_reader.read_header()?;
+        let entries = agt_reader.read_entries(header.file_count)?;
+        entries
+            .iter()
+            .map(|entry| agt_reader.read_entry_data(entry))
+            .collect()
+    };
+
+    // Shadowed entries are kept, so the archive is written back as it was
+    let mut rebuilt_buf = Vec::new();
+    AgtBuilder::from_agt(&agt_path, spooky_key)?
+        .write(&mut Cursor::new(&mut rebuilt_buf), spooky_key)?;
+    assert!(rebuilt_buf == agt_buf);
+
+    // Replacing the path only touches the entry the game reads
+    let mut editor = AgtEditor::open(&agt_path, spooky_key)?;
+    editor.replace_memory("NeoData\\dup.xlt", b"changed")?;
+    let mut edited_buf = Vec::new();
+    editor.save(&mut Cursor::new(&mut edited_buf))?;
+    assert_eq!(
+        read_entries(&edited_buf)?,
+        [b"changed".to_vec(), b"shadowed".to_vec()]
+    );
+
+    // Removing it takes the shadowed entry along instead of uncovering it
+    let mut editor = AgtEditor::open(&agt_path, spooky_key)?;
+    editor.remove("NeoData\\dup.xlt")?;
+    let mut edited_buf = Vec::new();
+    editor.save(&mut Cursor::new(&mut edited_buf))?;
+    assert!(read_entries(&edited_buf)?.is_empty());
+
+    std::fs::remove_file(&agt_path)?;
+
+    Ok(())
+}
+
+#[test]
+fn dev_neodata_merge() -> anyhow::Result<()> {
+    let spooky_key: &[u8] = include_bytes!("../resources/agt/spooky_key.bin");
+    let agt_path = "resources/agt/dev_neodata.agt";
+
+    let temp_dir = std::env::temp_dir().join(format!("slidetown_merge_{}", std::process::id()));
+    std::fs::create_dir_all(&temp_dir)?;
+    let patch_path = temp_dir.join("patch.agt");
+
+    let mut patch_builder = AgtBuilder::new();
+    patch_builder.add_entry_memory("neodata\\nc_quest.xlt".to_string(), b"patched");
+    patch_builder.add_entry_memory("NeoData\\NC_new.xlt".to_string(), b"added");
+    patch_builder.write(&mut std::fs::File::create(&patch_path)?, spooky_key)?;
+    let patch_path = patch_path.to_str().unwrap();
+
+    let sources = [(agt_path, spooky_key), (patch_path, spooky_key)];
+
+    type Merged = (Vec<(Entry, Vec<u8>)>, MergeReport);
+    let merge = |policy| -> anyhow::Result<Merged> {
+        let (builder, report) = merge_agt(&sources, Default::default(), policy)?;
+        let mut out_buf = Vec::new();
+        builder.write(&mut Cursor::new(&mut out_buf), spooky_key)?;
+
+        let mut agt_file = Cursor::new(&out_buf);
+        let mut agt_reader = AgtReader::new(&mut agt_file, spooky_key);
+        let header = agt_reader.read_header()?;
+        let entries = agt_reader
+            .read_entries(header.file_count)?
+            .into_iter()
+            .map(|entry| {
+                let data = agt_reader.read_entry_data(&entry)?;
+                Ok((entry, data))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok((entries, report))
+    };
+
+    let (entries, report) = merge(ConflictPolicy::LastWins)?;
+    assert_eq!(entries.len(), 6);
+    // The patched entry keeps its original position and spelling
+    assert_eq!(entries[4].0.path, "NeoData\\NC_quest.xlt");
+    assert_eq!(entries[4].1, b"patched");
+    assert_eq!(entries[5].0.path, "NeoData\\NC_new.xlt");
+    assert_eq!(report.entries[4].source_index, 1);
+    assert_eq!(report.entries[4].shadowed_source_indices, vec![0]);
+    assert_eq!(report.entries[0].source_index, 0);
+    assert_eq!(report.entries[5].source_index, 1);
+
+    let (entries, report) = merge(ConflictPolicy::FirstWins)?;
+    assert_eq!(entries.len(), 6);
+    assert_eq!(entries[4].1.len(), 29016);
+    assert_eq!(report.entries[4].source_index, 0);
+    assert_eq!(report.entries[4].shadowed_source_indices, vec![1]);
+
+    assert!(merge(ConflictPolicy::Fail).is_err());
+
+    std::fs::remove_dir_all(&temp_dir)?;
+
+    Ok(())
+}
+
+#[test]
+fn codepage_entry_paths() -> anyhow::Result<()> {
+    let spooky_key: &[u8] = include_bytes!("../resources/agt/spooky_key.bin");
+
+    let temp_dir = std::env::temp_dir().join(format!("slidetown_codepage_{}", std::process::id()));
+    std::fs::create_dir_all(&temp_dir)?;
+
+    for (codepage, entry_path) in [
+        (Codepage::EucKr, "NeoData\\한글.xlt"),
+        (Codepage::Big5, "NeoData\\中文.xlt"),
+    ] {
+        let mut builder = AgtBuilder::new();
+        builder.set_codepage(codepage);
+        builder.add_entry_memory("NeoData\\ascii.xlt".to_string(), b"ascii");
+        builder.add_entry_memory(entry_path.to_string(), b"native");
+
+        let agt_path = temp_dir.join(format!("{}.agt", codepage.name()));
+        let mut out_buf = Vec::new();
+        builder.write(&mut Cursor::new(&mut out_buf), spooky_key)?;
+        std::fs::write(&agt_path, &out_buf)?;
+
+        let read_entries = |codepage| -> anyhow::Result<Vec<Entry>> {
+            let mut agt_file = Cursor::new(&out_buf);
+            let mut agt_reader = AgtReader::new(&mut agt_file, spooky_key);
+            agt_reader.set_codepage(codepage);
+            let header = agt_reader.read_header()?;
+            agt_reader.read_entries(header.file_count)
+        };
+
+        // Decoded with the right codepage the path comes back intact
+        let entries = read_entries(codepage)?;
+        assert_eq!(entries[1].path, entry_path);
+        assert_eq!(entries[1].raw_path, codepage.encode(entry_path)?);
+
+        // Decoded as UTF-8 it is lossy, but the stored bytes are untouched
+        let utf8_entries = read_entries(Codepage::Utf8)?;
+        assert_eq!(utf8_entries[0].path, "NeoData\\ascii.xlt");
+        assert_ne!(utf8_entries[1].path, entry_path);
+        assert_eq!(utf8_entries[1].raw_path, entries[1].raw_path);
+        assert!(utf8_entries[1].decode_path(Codepage::Utf8).is_err());
+        assert_eq!(utf8_entries[1].decode_path(codepage)?, entry_path);
+
+        // Rebuilding without knowing the codepage writes the same bytes back
+        let builder = AgtBuilder::from_agt(&agt_path, spooky_key)?;
+        let mut rebuilt_buf = Vec::new();
+        builder.write(&mut Cursor::new(&mut rebuilt_buf), spooky_key)?;
+        assert!(rebuilt_buf == out_buf);
+
+        // Replacing the entry by its decoded path keeps the encoding too
+        let mut editor = AgtEditor::open_with_codepage(&agt_path, spooky_key, codepage)?;
+        editor.replace_memory(entry_path, b"changed")?;
+        let mut edited_buf = Vec::new();
+        editor.save(&mut Cursor::new(&mut edited_buf))?;
+
+        let mut agt_file = Cursor::new(&edited_buf);
+        let mut agt_reader = AgtReader::new(&mut agt_file, spooky_key);
+        agt_reader.set_codepage(codepage);
+        let header = agt_reader.read_header()?;
+        let entries = agt_reader.read_entries(header.file_count)?;
+        assert_eq!(entries[1].path, entry_path);
+        assert_eq!(agt_reader.read_entry_data(&entries[1])?, b"changed");
+    }
+
+    // Paths that can't be represented are an error rather than silently mangled
+    let mut builder = AgtBuilder::new();
+    builder.set_codepage(Codepage::EucKr);
+    builder.add_entry_memory("NeoData\\🚗.xlt".to_string(), b"native");
+    assert!(builder
+        .write(&mut Cursor::new(Vec::new()), spooky_key)
+        .is_err());
+
+    std::fs::remove_dir_all(&temp_dir)?;
+
+    Ok(())
+}
+
+#[test]
+fn lossy_entry_paths_stay_apart() -> anyhow::Result<()> {
+    let spooky_key: &[u8] = include_bytes!("../resources/agt/spooky_key.bin");
+
+    let agt_path = std::env::temp_dir().join(format!("slidetown_lossy_{}.agt", std::process::id()));
+
+    // Both paths decode to the same replacement characters as UTF-8
+    let mut builder = AgtBuilder::new();
+    builder.set_codepage(Codepage::EucKr);
+    builder.add_entry_memory("NeoData\\가.xlt".to_string(), b"ga");
+    builder.add_entry_memory("NeoData\\나.xlt".to_string(), b"na");
+    let mut out_buf = Vec::new();
+    builder.write(&mut Cursor::new(&mut out_buf), spooky_key)?;
+    std::fs::write(&agt_path, &out_buf)?;
+
+    // Editing without knowing the codepage keeps both entries and their bytes
+    let mut editor = AgtEditor::open(&agt_path, spooky_key)?;
+    assert_eq!(editor.entry_paths().count(), 2);
+    let lossy_path = editor.entry_paths().next().unwrap().to_owned();
+    assert!(editor.remove(&lossy_path).is_err());
+    editor.add_memory("NeoData\\ascii.xlt".to_string(), b"ascii")?;
+    let mut edited_buf = Vec::new();
+    editor.save(&mut Cursor::new(&mut edited_buf))?;
+
+    let mut agt_file = Cursor::new(&edited_buf);
+    let mut agt_reader = AgtReader::new(&mut agt_file, spooky_key);
+    agt_reader.set_codepage(Codepage::EucKr);
+    let header = agt_reader.read_header()?;
+    let entries = agt_reader.read_entries(header.file_count)?;
+    assert_eq!(
+        entries
+            .iter()
+            .map(|entry| entry.path.as_str())
+            .collect::<Vec<_>>(),
+        ["NeoData\\가.xlt", "NeoData\\나.xlt", "NeoData\\ascii.xlt"]
+    );
+    assert_eq!(agt_reader.read_entry_data(&entries[1])?, b"na");
+
+    // Anything matching paths by their decoded text needs the right codepage
+    assert!(diff_agt(&agt_path, spooky_key, &agt_path, spooky_key, Codepage::Utf8).is_err());
+    assert!(diff_agt(
+        &agt_path,
+        spooky_key,
+        &agt_path,
+        spooky_key,
+        Codepage::EucKr
+    )?
+    .is_empty());
+    let sources = [(&agt_path, spooky_key)];
+    assert!(merge_agt(&sources, Codepage::Utf8, ConflictPolicy::Fail).is_err());
+    let (_, report) = merge_agt(&sources, Codepage::EucKr, ConflictPolicy::Fail)?;
+    assert_eq!(report.entries.len(), 2);
+
+    std::fs::remove_file(&agt_path)?;
+
+    Ok(())
+}
+
+#[test]
+fn dev_neodata_sync() -> anyhow::Result<()> {
+    let spooky_key: &[u8] = include_bytes!("../resources/agt/spooky_key.bin");
+    let agt_path = "resources/agt/dev_neodata.agt";
+    let agt_buffer = std::fs::read(agt_path)?;
+
+    let entries_with_data = check_neodata(&agt_buffer, spooky_key)?;
+
+    let temp_dir = std::env::temp_dir().join(format!("slidetown_sync_{}", std::process::id()));
+    std::fs::create_dir_all(&temp_dir)?;
+
+    // Loose copy with one entry edited, one deleted and one added, using different case
+    let mut files = Vec::new();
+    for (i, (entry, data)) in entries_with_data.iter().enumerate() {
+        if i == 2 {
+            continue;
+        }
+        let file_path = temp_dir.join(format!("{}.xlt", i));
+        let mut data = data.clone();
+        if i == 1 {
+            data[2] = b'X';
+        }
+        std::fs::write(&file_path, &data)?;
+        files.push((entry.path.to_lowercase(), file_path));
+    }
+    let new_file_path = temp_dir.join("new.txt");
+    std::fs::write(&new_file_path, b"new")?;
+    files.push(("NeoData\\new.txt".to_string(), new_file_path));
+
+    let report = sync_agt(agt_path, spooky_key, Default::default(), files)?;
+    assert_eq!(report.unchanged, 3);
+    assert_eq!(
+        report
+            .changed
+            .iter()
+            .map(|changed| changed.path.as_str())
+            .collect::<Vec<_>>(),
+        vec!["NeoData\\NC_mission.xlt"]
+    );
+    assert_eq!(report.changed[0].entry_hash, {
+        let mut agt_file = Cursor::new(&agt_buffer);
+        AgtReader::new(&mut agt_file, spooky_key).hash_entry(&entries_with_data[1].0)?
+    });
+    assert_eq!(report.missing.len(), 1);
+    assert_eq!(report.missing[0].path, "NeoData\\NC_object.xlt");
+    assert_eq!(report.new.len(), 1);
+    assert_eq!(report.new[0].path, "NeoData\\new.txt");
+
+    for remove_missing in [false, true] {
+        let mut editor = AgtEditor::open(agt_path, spooky_key)?;
+        editor.apply_sync(&report, remove_missing)?;
+        let mut out_buf = Vec::new();
+        editor.save(&mut Cursor::new(&mut out_buf))?;
+
+        let mut agt_file = Cursor::new(&out_buf);
+        let mut agt_reader = AgtReader::new(&mut agt_file, spooky_key);
+        let header = agt_reader.read_header()?;
+        let entries = agt_reader.read_entries(header.file_count)?;
+
+        // Original order kept, the new entry appended
+        let mut expected_paths = entries_with_data
+            .iter()
+            .map(|(entry, _)| entry.path.clone())
+            .collect::<Vec<_>>();
+        if remove_missing {
+            expected_paths.remove(2);
+        }
+        expected_paths.push("NeoData\\new.txt".to_string());
+        assert_eq!(
+            entries.iter().map(|e| e.path.clone()).collect::<Vec<_>>(),
+            expected_paths
+        );
+
+        assert_eq!(agt_reader.read_entry_data(&entries[1])?[2], b'X');
+        assert_eq!(agt_reader.read_entry_data(entries.last().unwrap())?, b"new");
+        // Unchanged entries keep their original chunks
+        assert_eq!(
+            agt_reader.read_entry_data(&entries[0])?,
+            entries_with_data[0].1
+        );
+    }
+
+    // Two files for the same entry are ambiguous
+    let duplicate_files = vec![
+        ("NeoData\\a.txt".to_string(), temp_dir.join("new.txt")),
+        ("neodata/A.TXT".to_string(), temp_dir.join("new.txt")),
+    ];
+    assert!(sync_agt(agt_path, spooky_key, Default::default(), duplicate_files).is_err());
+
+    std::fs::remove_dir_all(&temp_dir)?;
+
+    Ok(())
+}
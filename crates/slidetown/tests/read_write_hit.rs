@@ -6,3 +6,36 @@ use test_utils::test_full_rewrite;
 fn mp_track1_area_hit() {
     test_full_rewrite::<Hit>("resources/hit/dcr_mp_track1_area.hit", (), ()).unwrap();
 }
+
+#[test]
+fn mp_track1_area_hit_check() -> anyhow::Result<()> {
+    let mut file = std::fs::File::open("resources/hit/dcr_mp_track1_area.hit")?;
+    let hit = Hit::read(&mut file)?;
+    hit.check()?;
+    assert_eq!(hit.triangles().count() * 3, hit.indices.len());
+
+    let rebuilt = Hit::from_triangles(hit.verts.clone(), hit.indices.clone())?;
+    assert_eq!(rebuilt, hit);
+
+    Ok(())
+}
+
+#[test]
+fn hit_from_triangles_rejects_bad_triangles() {
+    let verts = vec![
+        (0.0, 0.0, 0.0),
+        (1.0, 0.0, 0.0),
+        (0.0, 1.0, 0.0),
+        (2.0, 0.0, 0.0),
+    ];
+
+    assert!(Hit::from_triangles(verts.clone(), vec![0, 1, 2]).is_ok());
+    // Incomplete triangle
+    assert!(Hit::from_triangles(verts.clone(), vec![0, 1, 2, 0]).is_err());
+    // Out of bounds
+    assert!(Hit::from_triangles(verts.clone(), vec![0, 1, 4]).is_err());
+    // Repeated vertex
+    assert!(Hit::from_triangles(verts.clone(), vec![0, 1, 1]).is_err());
+    // Collinear vertices
+    assert!(Hit::from_triangles(verts, vec![0, 1, 3]).is_err());
+}
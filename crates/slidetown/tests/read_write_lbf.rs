@@ -0,0 +1,179 @@
+use slidetown::parsers::{
+    lbf::{Block, BlockObject, Header, Lbf},
+    EmbeddedBlobArchive, EntryOffsets,
+};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+mod test_utils;
+use test_utils::test_blob_round_trip;
+
+#[test]
+fn lbf_write_with_data() -> anyhow::Result<()> {
+    let object = |unk, block_index| BlockObject {
+        unk,
+        block_index,
+        file_offset: 0,
+        file_length: 0,
+    };
+
+    let mut lbf = Lbf {
+        header: Header {
+            version_date: 20061222,
+            unknown2: 0,
+            block_count: 0,
+            block_object_count: 0,
+        },
+        blocks: vec![
+            Block {
+                objects: vec![object(0, 0), object(1, 0)],
+            },
+            Block { objects: vec![] },
+            Block {
+                objects: vec![object(2, 2)],
+            },
+        ],
+    };
+    lbf.update_counts();
+    assert_eq!(lbf.header.block_count, 3);
+    assert_eq!(lbf.header.block_object_count, 3);
+
+    let object_data: [&[u8]; 3] = [b"first object", b"second", b"third object data"];
+
+    let offsets = EntryOffsets::default();
+    let mut out_file = Cursor::new(Vec::new());
+    lbf.write_without_data(&mut out_file, offsets.clone())?;
+    assert_eq!(offsets.borrow().len(), 3);
+
+    for (data, &header_offset) in object_data.iter().zip(offsets.borrow().iter()) {
+        let file_offset = out_file.stream_position()? as u32;
+        out_file.write_all(data)?;
+
+        out_file.seek(SeekFrom::Start(header_offset))?;
+        out_file.write_all(&file_offset.to_le_bytes())?;
+        out_file.write_all(&(data.len() as u32).to_le_bytes())?;
+        out_file.seek(SeekFrom::End(0))?;
+    }
+
+    let out_buf = out_file.into_inner();
+    let mut in_file = Cursor::new(&out_buf);
+    let read_lbf = Lbf::parse(&mut in_file)?;
+
+    assert_eq!(read_lbf.header, lbf.header);
+    assert_eq!(read_lbf.blocks.len(), 3);
+    assert_eq!(read_lbf.blocks[0].objects.len(), 2);
+    assert_eq!(read_lbf.blocks[1].objects.len(), 0);
+
+    let read_objects = read_lbf
+        .blocks
+        .iter()
+        .flat_map(|block| block.objects.iter());
+    for (read_object, data) in read_objects.zip(object_data) {
+        let mut read_data = vec![0u8; read_object.file_length as usize];
+        in_file.seek(SeekFrom::Start(read_object.file_offset as u64))?;
+        in_file.read_exact(&mut read_data)?;
+        assert_eq!(read_data, data);
+    }
+
+    // Writing what was read reproduces the table exactly
+    let mut rewritten = Cursor::new(Vec::new());
+    read_lbf.write_without_data(&mut rewritten, EntryOffsets::default())?;
+    let rewritten = rewritten.into_inner();
+    assert_eq!(rewritten, out_buf[..rewritten.len()]);
+
+    Ok(())
+}
+
+#[test]
+fn lbf_write_with_blobs() -> anyhow::Result<()> {
+    let object = |unk, block_index| BlockObject {
+        unk,
+        block_index,
+        file_offset: 0,
+        file_length: 0,
+    };
+
+    let mut lbf = Lbf {
+        header: Header {
+            version_date: 20061222,
+            unknown2: 0,
+            block_count: 2,
+            block_object_count: 3,
+        },
+        blocks: vec![
+            Block {
+                objects: vec![object(3, 4), object(3, 4)],
+            },
+            Block {
+                objects: vec![object(1, 7)],
+            },
+        ],
+    };
+
+    let object_data: [&[u8]; 3] = [b"first object", b"second", b"third object data"];
+
+    let mut out_file = Cursor::new(Vec::new());
+    lbf.write_with_blobs(&mut out_file, |index, writer| {
+        writer.write_all(object_data[index])?;
+        Ok(())
+    })?;
+
+    let mut in_file = Cursor::new(out_file.into_inner());
+    let read_lbf = Lbf::parse(&mut in_file)?;
+    assert_eq!(read_lbf, lbf);
+
+    // Blobs are named after their block and their position within it
+    let blobs = read_lbf.blobs();
+    assert_eq!(
+        blobs
+            .iter()
+            .map(|blob| blob.key.as_str())
+            .collect::<Vec<_>>(),
+        ["4_0", "4_1", "7_0"]
+    );
+    for (blob, data) in blobs.iter().zip(object_data) {
+        assert_eq!(blob.read(&mut in_file)?, data);
+    }
+
+    // Counts that don't match the blocks are refused instead of written as is
+    lbf.blocks[1].objects.push(object(1, 7));
+    let mut out_file = Cursor::new(Vec::new());
+    assert!(lbf.write_with_blobs(&mut out_file, |_, _| Ok(())).is_err());
+
+    lbf.update_counts();
+    lbf.blocks.pop();
+    assert!(lbf
+        .write_without_data(&mut Cursor::new(Vec::new()), EntryOffsets::default())
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn lbf_unpack_pack() -> anyhow::Result<()> {
+    let object = |unk, block_index| BlockObject {
+        unk,
+        block_index,
+        file_offset: 0,
+        file_length: 0,
+    };
+
+    let mut lbf = Lbf {
+        header: Header {
+            version_date: 20061222,
+            unknown2: 0,
+            block_count: 0,
+            block_object_count: 0,
+        },
+        blocks: vec![
+            Block {
+                objects: vec![object(0, 0), object(1, 0)],
+            },
+            Block { objects: vec![] },
+            Block {
+                objects: vec![object(0, 2)],
+            },
+        ],
+    };
+    lbf.update_counts();
+
+    test_blob_round_trip(lbf, Lbf::parse)
+}
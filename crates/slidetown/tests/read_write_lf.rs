@@ -1,6 +1,6 @@
 use slidetown::parsers::lf::Lf;
 mod test_utils;
-use test_utils::test_full_rewrite;
+use test_utils::{test_blob_round_trip, test_full_rewrite};
 
 #[test]
 fn dcr_mp_terrain0_nodata_lf_rewrite() {
@@ -11,3 +11,11 @@ fn dcr_mp_terrain0_nodata_lf_rewrite() {
 fn dev_mp_terrain0_nodata_lf_rewrite() {
     test_full_rewrite::<Lf>("resources/lf/dev_mp_terrain0_nodata.lf", (), (None,)).unwrap();
 }
+
+#[test]
+fn dev_mp_terrain0_nodata_lf_unpack_pack() -> anyhow::Result<()> {
+    let lf = Lf::read_without_data(&mut std::fs::File::open(
+        "resources/lf/dev_mp_terrain0_nodata.lf",
+    )?)?;
+    test_blob_round_trip(lf, Lf::read_without_data)
+}
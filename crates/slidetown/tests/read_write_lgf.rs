@@ -1,13 +1,85 @@
-use slidetown::parsers::lgf::Lgf;
+use slidetown::parsers::{lgf::Lgf, EmbeddedBlobArchive, EntryOffsets};
+use std::collections::HashMap;
+use std::io::{Cursor, Write};
 mod test_utils;
-use test_utils::test_full_rewrite;
+use test_utils::{test_blob_round_trip, test_full_rewrite};
 
 #[test]
 fn dcr_mp_guardrail0_nodata_lgf_rewrite() {
-    test_full_rewrite::<Lgf>("resources/lgf/dcr_mp_guardrail0_nodata.lgf", (), ()).unwrap();
+    test_full_rewrite::<Lgf>("resources/lgf/dcr_mp_guardrail0_nodata.lgf", (), (None,)).unwrap();
 }
 
 #[test]
 fn dev_mp_guardrail0_nodata_lgf_rewrite() {
-    test_full_rewrite::<Lgf>("resources/lgf/dev_mp_guardrail0_nodata.lgf", (), ()).unwrap();
+    test_full_rewrite::<Lgf>("resources/lgf/dev_mp_guardrail0_nodata.lgf", (), (None,)).unwrap();
+}
+
+#[test]
+fn dev_mp_guardrail0_nodata_lgf_blobs() -> anyhow::Result<()> {
+    let mut lgf = Lgf::read(&mut std::fs::File::open(
+        "resources/lgf/dev_mp_guardrail0_nodata.lgf",
+    )?)?;
+    let blob_data = |index: usize| format!("guardrail nif {}", index).into_bytes();
+
+    let mut out_file = Cursor::new(Vec::new());
+    lgf.write_with_blobs(&mut out_file, |index, writer| {
+        Ok(writer.write_all(&blob_data(index))?)
+    })?;
+
+    let mut in_file = Cursor::new(out_file.into_inner());
+    let read_lgf = Lgf::read(&mut in_file)?;
+    assert_eq!(read_lgf, lgf);
+
+    let blobs = read_lgf.blobs();
+    assert_eq!(blobs.len(), lgf.blocks.len());
+    for (index, (blob, block)) in blobs.iter().zip(lgf.blocks.iter()).enumerate() {
+        assert_eq!(blob.key, block.block_index.to_string());
+        assert_eq!(blob.read(&mut in_file)?, blob_data(index));
+    }
+
+    // Replacing one blob copies all others
+    let replacements = HashMap::from([(1, b"replaced".to_vec())]);
+    in_file.set_position(0);
+    let mut replaced_lgf = Lgf::read(&mut in_file)?;
+    let mut out_file = Cursor::new(Vec::new());
+    replaced_lgf.write_replacing_blobs(&mut in_file, &mut out_file, &replacements)?;
+
+    let mut in_file = Cursor::new(out_file.into_inner());
+    let blobs = Lgf::read(&mut in_file)?.blobs();
+    assert_eq!(blobs, replaced_lgf.blobs());
+    for (index, blob) in blobs.iter().enumerate() {
+        let expected = replacements
+            .get(&index)
+            .cloned()
+            .unwrap_or_else(|| blob_data(index));
+        assert_eq!(blob.read(&mut in_file)?, expected);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn dev_mp_guardrail0_nodata_lgf_entry_offsets() -> anyhow::Result<()> {
+    let lgf = Lgf::read(&mut std::fs::File::open(
+        "resources/lgf/dev_mp_guardrail0_nodata.lgf",
+    )?)?;
+
+    let entry_offsets = EntryOffsets::default();
+    lgf.write_without_data(&mut Cursor::new(Vec::new()), entry_offsets.clone())?;
+
+    // Magic, version, unknown and block count, then a block index before each file offset
+    let expected_offsets = (0..lgf.blocks.len() as u64)
+        .map(|index| 24 + index * 12 + 4)
+        .collect::<Vec<_>>();
+    assert_eq!(*entry_offsets.borrow(), expected_offsets);
+
+    Ok(())
+}
+
+#[test]
+fn dev_mp_guardrail0_nodata_lgf_unpack_pack() -> anyhow::Result<()> {
+    let lgf = Lgf::read(&mut std::fs::File::open(
+        "resources/lgf/dev_mp_guardrail0_nodata.lgf",
+    )?)?;
+    test_blob_round_trip(lgf, Lgf::read)
 }
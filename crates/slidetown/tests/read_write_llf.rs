@@ -1,13 +1,40 @@
-use slidetown::parsers::llf::Llf;
+use slidetown::parsers::{llf::Llf, EntryOffsets};
+use std::io::Cursor;
 mod test_utils;
-use test_utils::test_full_rewrite;
+use test_utils::{test_blob_round_trip, test_full_rewrite};
 
 #[test]
 fn dcr_mp_lane0_nodata_llf_rewrite() {
-    test_full_rewrite::<Llf>("resources/llf/dcr_mp_lane0_nodata.llf", (), ()).unwrap();
+    test_full_rewrite::<Llf>("resources/llf/dcr_mp_lane0_nodata.llf", (), (None,)).unwrap();
 }
 
 #[test]
 fn dev_mp_lane0_nodata_llf_rewrite() {
-    test_full_rewrite::<Llf>("resources/llf/dev_mp_lane0_nodata.llf", (), ()).unwrap();
+    test_full_rewrite::<Llf>("resources/llf/dev_mp_lane0_nodata.llf", (), (None,)).unwrap();
+}
+
+#[test]
+fn dev_mp_lane0_nodata_llf_entry_offsets() -> anyhow::Result<()> {
+    let llf = Llf::read(&mut std::fs::File::open(
+        "resources/llf/dev_mp_lane0_nodata.llf",
+    )?)?;
+
+    let entry_offsets = EntryOffsets::default();
+    llf.write_without_data(&mut Cursor::new(Vec::new()), entry_offsets.clone())?;
+
+    // Magic, version, unknown and block count, then a block index before each file offset
+    let expected_offsets = (0..llf.blocks.len() as u64)
+        .map(|index| 24 + index * 12 + 4)
+        .collect::<Vec<_>>();
+    assert_eq!(*entry_offsets.borrow(), expected_offsets);
+
+    Ok(())
+}
+
+#[test]
+fn dev_mp_lane0_nodata_llf_unpack_pack() -> anyhow::Result<()> {
+    let llf = Llf::read(&mut std::fs::File::open(
+        "resources/llf/dev_mp_lane0_nodata.llf",
+    )?)?;
+    test_blob_round_trip(llf, Llf::read)
 }
@@ -1,8 +1,106 @@
-use slidetown::parsers::lof::Lof;
+use slidetown::parsers::{
+    lof::{Header, Lof, Model},
+    Codepage, EmbeddedBlobArchive, EntryOffsets,
+};
+use std::io::{Cursor, Write};
 mod test_utils;
-use test_utils::test_full_rewrite;
+use test_utils::{test_blob_round_trip, test_full_rewrite};
 
 #[test]
 fn dev_mp_modeltable0_nodata_lof_rewrite() {
-    test_full_rewrite::<Lof>("resources/lof/dev_mp_modeltable0_nodata.lof", (), (None,)).unwrap();
+    test_full_rewrite::<Lof>(
+        "resources/lof/dev_mp_modeltable0_nodata.lof",
+        binrw::args! { codepage: Lof::DEFAULT_CODEPAGE },
+        (None,),
+    )
+    .unwrap();
+}
+
+#[test]
+fn dev_mp_modeltable0_nodata_lof_unpack_pack() -> anyhow::Result<()> {
+    let lof = Lof::read_without_data(&mut std::fs::File::open(
+        "resources/lof/dev_mp_modeltable0_nodata.lof",
+    )?)?;
+    test_blob_round_trip(lof, Lof::read_without_data)
+}
+
+#[test]
+fn lof_codepages() -> anyhow::Result<()> {
+    let lof_with_name = |name: &str| Lof {
+        header: Header {
+            version_date: 20061222,
+        },
+        max_file_size: 0,
+        models: vec![Model {
+            index: 0,
+            unknown1: 0,
+            unknown2: 0,
+            unknown3: 0,
+            lighting: 0,
+            effect_id: 0,
+            name: name.to_string(),
+            file_name: format!("{}.nif", name),
+            animation_duration: 0.0,
+            r#loop: 0,
+            random_offset: 0,
+            file_offset: 0,
+            file_length: 0,
+        }],
+        codepage: Lof::DEFAULT_CODEPAGE,
+    };
+
+    for (codepage, name) in [
+        (Codepage::EucKr, "가로등"),
+        (Codepage::Big5, "路燈"),
+        (Codepage::Gbk, "路灯"),
+        (Codepage::ShiftJis, "街灯"),
+        (Codepage::Cp1252, "Lampadaire é"),
+    ] {
+        let mut lof = lof_with_name(name);
+        lof.codepage = codepage;
+
+        let mut out_buf = Vec::new();
+        lof.write_without_data(&mut Cursor::new(&mut out_buf), EntryOffsets::default())?;
+        assert!(out_buf
+            .windows(name.len())
+            .all(|window| window != name.as_bytes()));
+
+        let read_lof = Lof::read_without_data_with_codepage(&mut Cursor::new(&out_buf), codepage)?;
+        assert_eq!(read_lof, lof);
+
+        // Writing with blobs keeps the codepage too
+        let mut out_buf = Vec::new();
+        lof.write_with_blobs(&mut Cursor::new(&mut out_buf), |_, writer| {
+            Ok(writer.write_all(b"model nif")?)
+        })?;
+        let read_lof = Lof::read_without_data_with_codepage(&mut Cursor::new(&out_buf), codepage)?;
+        assert_eq!(read_lof, lof);
+        assert_eq!(
+            read_lof.blobs()[0].read(&mut Cursor::new(&out_buf))?,
+            b"model nif"
+        );
+    }
+
+    // Without a codepage the Korean default is used
+    let lof = lof_with_name("가로등");
+    let mut out_buf = Vec::new();
+    lof.write_without_data(&mut Cursor::new(&mut out_buf), EntryOffsets::default())?;
+    assert_eq!(Lof::read_without_data(&mut Cursor::new(&out_buf))?, lof);
+
+    // Names that can't be represented fail instead of being silently replaced
+    let lof = lof_with_name("路灯");
+    assert!(lof
+        .write_without_data(&mut Cursor::new(Vec::new()), EntryOffsets::default())
+        .is_err());
+
+    // Bytes that aren't valid in the codepage fail to read
+    let mut lof = lof_with_name("街灯");
+    lof.codepage = Codepage::ShiftJis;
+    let mut out_buf = Vec::new();
+    lof.write_without_data(&mut Cursor::new(&mut out_buf), EntryOffsets::default())?;
+    assert!(
+        Lof::read_without_data_with_codepage(&mut Cursor::new(&out_buf), Codepage::Utf8).is_err()
+    );
+
+    Ok(())
 }
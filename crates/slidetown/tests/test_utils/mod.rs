@@ -1,10 +1,14 @@
 use std::{
+    collections::HashMap,
     fs::File,
-    io::{BufReader, Cursor, Read, Seek, SeekFrom},
+    io::{BufReader, Cursor, Read, Seek, SeekFrom, Write},
 };
 
 use binrw::{BinRead, BinReaderExt, BinWrite, BinWriterExt};
+use serde::{de::DeserializeOwned, Serialize};
+use slidetown::parsers::{EmbeddedBlobArchive, EntryOffsets};
 
+#[allow(dead_code)]
 pub fn test_full_rewrite<'ra, 'wa, S: BinWrite + BinRead + std::fmt::Debug>(
     path: &str,
     read_args: <S as BinRead>::Args<'ra>,
@@ -54,3 +58,66 @@ where
 
     Ok(result)
 }
+
+/// Pack an archive with made up blob data and check every recorded entry offset points
+/// at its blob's offset and length. Then unpack it into a JSON manifest and blobs by key
+/// and pack it again, which has to reproduce the file.
+#[allow(dead_code)]
+pub fn test_blob_round_trip<A>(
+    mut archive: A,
+    read: impl Fn(&mut Cursor<Vec<u8>>) -> anyhow::Result<A>,
+) -> anyhow::Result<()>
+where
+    A: EmbeddedBlobArchive + Serialize + DeserializeOwned,
+{
+    let blob_data = |key: &str| format!("nif for {}", key).into_bytes();
+
+    let keys = archive
+        .blobs()
+        .into_iter()
+        .map(|blob| blob.key)
+        .collect::<Vec<_>>();
+    let mut packed = Cursor::new(Vec::new());
+    archive.write_with_blobs(&mut packed, |index, writer| {
+        Ok(writer.write_all(&blob_data(&keys[index]))?)
+    })?;
+    let packed = packed.into_inner();
+
+    let entry_offsets = EntryOffsets::default();
+    archive.write_table(&mut Cursor::new(Vec::new()), entry_offsets.clone())?;
+    let blobs = archive.blobs();
+    assert_eq!(entry_offsets.borrow().len(), blobs.len());
+    for (&entry_offset, blob) in entry_offsets.borrow().iter().zip(blobs.iter()) {
+        let entry_offset = entry_offset as usize;
+        assert_eq!(
+            packed[entry_offset..entry_offset + 4],
+            blob.offset.to_le_bytes()
+        );
+        assert_eq!(
+            packed[entry_offset + 4..entry_offset + 8],
+            blob.length.to_le_bytes()
+        );
+    }
+
+    // Unpack the way the CLI does
+    let mut in_file = Cursor::new(packed.clone());
+    let unpacked = read(&mut in_file)?;
+    let mut files = HashMap::new();
+    for blob in unpacked.blobs() {
+        let data = blob.read(&mut in_file)?;
+        assert_eq!(data, blob_data(&blob.key));
+        files.insert(blob.key, data);
+    }
+    let manifest = serde_json::to_string(&unpacked)?;
+
+    // And pack it again
+    let mut repacked_archive: A = serde_json::from_str(&manifest)?;
+    let blobs = repacked_archive.blobs();
+    let mut repacked = Cursor::new(Vec::new());
+    repacked_archive.write_with_blobs(&mut repacked, |index, writer| {
+        Ok(writer.write_all(&files[&blobs[index].key])?)
+    })?;
+    assert_eq!(repacked.into_inner(), packed);
+
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use slidetown::{
+    agt::{diff_agt, merge_agt, AgtBuilder, AgtEditor, ConflictPolicy},
+    vfs::Vfs,
+};
+use std::io::{Read, Seek, SeekFrom};
+
+#[test]
+fn dev_neodata_overlay() -> anyhow::Result<()> {
+    let spooky_key: &[u8] = include_bytes!("../resources/agt/spooky_key.bin");
+
+    let loose_dir = std::env::temp_dir().join(format!("slidetown_vfs_{}", std::process::id()));
+    std::fs::create_dir_all(loose_dir.join("NeoData"))?;
+    std::fs::write(
+        loose_dir.join("NeoData").join("NC_quest.xlt"),
+        b"loose quest",
+    )?;
+    std::fs::write(loose_dir.join("terrain0.lf"), b"loose terrain")?;
+
+    let mut vfs = Vfs::new();
+    vfs.mount_agt("resources/agt/dev_neodata.agt", spooky_key)?;
+    vfs.mount_dir(&loose_dir)?;
+
+    // Entries only in the archive
+    let chapter = vfs
+        .lookup("neodata/nc_chapter.XLT")
+        .expect("archive entry should be visible");
+    assert_eq!(chapter.path, "NeoData\\NC_chapter.xlt");
+    assert_eq!(chapter.size, 5454);
+    assert_eq!(chapter.mount_index, 0);
+
+    let mut chapter_reader = vfs.open("NeoData\\NC_chapter.xlt")?;
+    let mut chapter_data = Vec::new();
+    chapter_reader.read_to_end(&mut chapter_data)?;
+    assert_eq!(chapter_data.len(), 5454);
+    assert_eq!(&chapter_data[..2], &[0xFF, 0xFE]);
+
+    chapter_reader.seek(SeekFrom::Start(2))?;
+    let mut chapter_tail = Vec::new();
+    chapter_reader.read_to_end(&mut chapter_tail)?;
+    assert_eq!(chapter_tail, chapter_data[2..]);
+
+    // Loose files override the archive
+    let quest = vfs.lookup("NeoData\\NC_quest.xlt").unwrap();
+    assert_eq!(quest.mount_index, 1);
+    assert_eq!(vfs.read("NEODATA\\NC_QUEST.XLT")?, b"loose quest");
+    assert_eq!(vfs.read("terrain0.lf")?, b"loose terrain");
+
+    assert!(!vfs.contains("NeoData\\NC_missing.xlt"));
+    assert!(vfs.open("NeoData\\NC_missing.xlt").is_err());
+    assert_eq!(vfs.entries().len(), 6);
+
+    std::fs::remove_dir_all(&loose_dir)?;
+
+    Ok(())
+}
+
+#[test]
+fn first_duplicate_wins() -> anyhow::Result<()> {
+    let spooky_key: &[u8] = include_bytes!("../resources/agt/spooky_key.bin");
+    let agt_path = std::env::temp_dir().join(format!(
+        "slidetown_vfs_duplicates_{}.agt",
+        std::process::id()
+    ));
+
+    // Paths that only differ in case are the same entry to the game
+    let mut builder = AgtBuilder::new();
+    builder.set_preserve_order(true);
+    builder.add_entry_memory("Data\\a.txt".into(), b"first");
+    builder.add_entry_memory("data\\A.TXT".into(), b"second");
+    builder.write(&mut std::fs::File::create(&agt_path)?, spooky_key)?;
+
+    let mut vfs = Vfs::new();
+    vfs.mount_agt(&agt_path, spooky_key)?;
+    assert_eq!(vfs.entries().len(), 1);
+    assert_eq!(vfs.read("data\\a.txt")?, b"first");
+
+    // Rebuilding keeps the shadowed entry so the archive comes out the same
+    let builder = AgtBuilder::from_agt(&agt_path, spooky_key)?;
+    assert_eq!(
+        builder.entry_paths().collect::<Vec<_>>(),
+        ["Data\\a.txt", "data\\A.TXT"]
+    );
+    let mut editor = AgtEditor::open(&agt_path, spooky_key)?;
+    assert_eq!(
+        editor.find_entry("DATA/a.txt").as_deref(),
+        Some("Data\\a.txt")
+    );
+    editor.remove("DATA/a.txt")?;
+    assert_eq!(editor.entry_paths().count(), 0);
+
+    let (_, report) = merge_agt(
+        &[(&agt_path, spooky_key)],
+        Default::default(),
+        ConflictPolicy::Fail,
+    )?;
+    assert_eq!(report.entries.len(), 1);
+    assert_eq!(report.entries[0].path, "Data\\a.txt");
+
+    let diff = diff_agt(
+        &agt_path,
+        spooky_key,
+        &agt_path,
+        spooky_key,
+        Default::default(),
+    )?;
+    assert!(diff.is_empty());
+    assert_eq!(diff.unchanged, 1);
+
+    std::fs::remove_file(&agt_path)?;
+
+    Ok(())
+}
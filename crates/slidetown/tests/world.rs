@@ -0,0 +1,62 @@
+use slidetown::world::World;
+
+#[test]
+fn dcr_mp_world() -> anyhow::Result<()> {
+    let world_dir = std::env::temp_dir().join(format!("slidetown_world_{}", std::process::id()));
+    std::fs::create_dir_all(world_dir.join("MAIN"))?;
+    std::fs::create_dir_all(world_dir.join("Track1"))?;
+    std::fs::create_dir_all(world_dir.join("Empty"))?;
+
+    // Names are spelled differently from the game's on purpose
+    for (resource, file_name) in [
+        ("lf/dcr_mp_terrain0_nodata.lf", "TERRAIN0.LF"),
+        ("lgf/dcr_mp_guardrail0_nodata.LGF", "guardrail0.LGF"),
+        ("llf/dcr_mp_lane0_nodata.LLF", "lane0.LLF"),
+        ("lof/dev_mp_modeltable0_nodata.lof", "ModelTable0.lof"),
+        ("loi/dcr_mp_main_object0.loI", "MAIN/object0.loI"),
+        ("loi/dcr_mp_track1_object0.loI", "Track1/OBJECT0.LOI"),
+        ("lif/dcr_mp_track1_terrain0.lif", "Track1/terrain0.lif"),
+        ("hit/dcr_mp_track1_area.HIT", "Track1/Area.HIT"),
+        ("chpath/path_taipei.chpath", "path_taipei.chpath"),
+        ("chpath/path_Cras.chpath", "path_Cras.CHPATH"),
+    ] {
+        std::fs::copy(format!("resources/{}", resource), world_dir.join(file_name))?;
+    }
+
+    let world = World::open(&world_dir)?;
+
+    assert_eq!(world.block_count(), 3854);
+    assert_eq!(world.lf().blocks.len(), 3854);
+    assert!(world.lbf().is_none());
+    assert!(world.lgf().is_some());
+    assert!(world.llf().is_some());
+    assert!(!world.lof().unwrap().models.is_empty());
+
+    let layer_names = world
+        .layers()
+        .iter()
+        .map(|layer| layer.name())
+        .collect::<Vec<_>>();
+    assert_eq!(layer_names, ["MAIN", "Track1"]);
+
+    let main_layer = world.main_layer().unwrap();
+    assert!(main_layer.loi().is_some());
+    assert!(main_layer.lif().is_none());
+
+    let track_layer = world.layer("track1").unwrap();
+    assert!(track_layer.loi().is_some());
+    assert!(track_layer.lif().is_some());
+    assert!(!track_layer.hit().unwrap().verts.is_empty());
+    assert_eq!(
+        track_layer.hit().unwrap().path,
+        world_dir.join("Track1").join("Area.HIT")
+    );
+
+    assert_eq!(world.chpaths().len(), 2);
+
+    assert_eq!(world.check(), Vec::<String>::new());
+
+    std::fs::remove_dir_all(&world_dir)?;
+
+    Ok(())
+}